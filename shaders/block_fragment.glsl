@@ -5,17 +5,46 @@ precision mediump float;
 in vec3 v_normal;
 in vec3 v_position;
 in vec2 v_tex_coords;
+in vec2 v_atlas_tile;
+in vec2 v_light;
+in float v_ao;
+in vec3 v_tint;
 
 uniform vec3 camera_position;
 uniform vec3 sunlight_direction;
 uniform sampler2D texture_map;
+// Fragments more see-through than this are dropped, only cutout faces set it above 0
+uniform float alpha_cutoff;
+// How much of an LOD tile is drawn while it fades in or out, 1 for everything else
+uniform float fade;
+
+#define ATLAS_TILE_SIZE 0.0625
+// Brightness lost per light level below the maximum
+#define LIGHT_FALLOFF 0.8
+// Brightness of a fully occluded corner
+#define AMBIENT_OCCLUSION_MIN 0.45
 
 layout(location = 0) out vec4 color;
 
+// 4x4 ordered dither threshold of the pixel, between 0 and 1
+float dither_threshold() {
+    const float bayer[16] = float[16](0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    ivec2 pixel = ivec2(gl_FragCoord.xy) % 4;
+    return (bayer[pixel.y * 4 + pixel.x] + 0.5) / 16.0;
+}
+
 void main() {
-    vec4 tex_color = texture(texture_map, v_tex_coords).rgba;
-    if(tex_color.a < 0.5) { discard; }
+    // Merged quads span several tiles, so wrap within the block's own tile instead of sampling its neighbours
+    vec2 atlas_coords = v_atlas_tile + fract(v_tex_coords) * ATLAS_TILE_SIZE;
+    vec4 tex_color = texture(texture_map, atlas_coords).rgba;
+    tex_color.rgb *= v_tint;
+    if(tex_color.a < alpha_cutoff) { discard; }
+    // Fading meshes are drawn in a growing share of the pixels, so two levels of detail can blend without sorting
+    if(fade < 1.0 && dither_threshold() >= fade) { discard; }
 
     float diffuse = max(dot(normalize(v_normal), normalize(sunlight_direction)), 0.5);
-    color = vec4(vec3(0.001) + diffuse * tex_color.rgb, tex_color.a);
+    float light_level = max(v_light.x, v_light.y);
+    float brightness = pow(LIGHT_FALLOFF, 15.0 * (1.0 - light_level));
+    brightness *= mix(AMBIENT_OCCLUSION_MIN, 1.0, v_ao);
+    color = vec4(vec3(0.001) + brightness * diffuse * tex_color.rgb, tex_color.a);
 }
\ No newline at end of file
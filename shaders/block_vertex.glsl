@@ -6,6 +6,10 @@ in vec3 position;
 in vec3 normal;
 in vec2 tex_coords;
 in int vtype;
+in vec2 atlas_tile;
+in vec2 light;
+in float ao;
+in vec3 tint;
 
 uniform mat4 model_matrix;
 uniform mat4 view_matrix;
@@ -17,6 +21,10 @@ uniform float time;
 out vec3 v_normal;
 out vec3 v_position;
 out vec2 v_tex_coords;
+out vec2 v_atlas_tile;
+out vec2 v_light;
+out float v_ao;
+out vec3 v_tint;
 
 #define WIND_SPEED 5.0
 
@@ -37,6 +45,10 @@ void main() {
     v_position = pos4_new.xyz;
     v_normal = normal; //model_matrix * vec4(normal, 1.0).xyz;
     v_tex_coords = tex_coords;
+    v_atlas_tile = atlas_tile;
+    v_light = light;
+    v_ao = ao;
+    v_tint = tint;
 
     gl_Position = pos4_new;
 }
\ No newline at end of file
@@ -1,7 +1,20 @@
+use std::{io, path::Path};
+
 use cgmath::{Matrix4, Vector3};
 
-use crate::{physics::vectormath, entity::gameobject::GameObject, player::{Player, gui::Gui, camera::perspective_matrix}, world::World, renderer::{Renderer, mesh::Texture, shader::Shader}};
+use crate::{physics::{vectormath, collision::{Collider, Rect3, rect_vs_rect}}, entity::gameobject::GameObject, player::{Player, gui::Gui, camera::{DEFAULT_FAR_PLANE, perspective_matrix}}, world::{World, CHUNK_SIZE, block::{AIR, BlockRegistry}, lod, preset::GeneratorPreset}, renderer::{Renderer, chunk_cache::ChunkMeshCache, mesh::Texture, shader::Shader}};
+
 
+/// Default number of chunks generated per frame while streaming
+const CHUNK_GENERATION_BUDGET: usize = 2;
+/// Default number of chunk meshes built and uploaded per frame
+const CHUNK_MESH_BUDGET: usize = 4;
+/// Default number of LOD tiles queued for building per frame
+const LOD_MESH_BUDGET: usize = 2;
+/// Length of a world tick in seconds
+const TICK_LENGTH: f32 = 0.05;
+/// Most world ticks run in one frame, so a long frame doesn't stall the next while catching up
+const MAX_TICKS_PER_FRAME: usize = 10;
 
 #[derive(PartialEq, Eq)]
 pub enum PlayState {
@@ -14,12 +27,15 @@ pub enum PlayerMovement {
     Walk(f32, f32, f32),
     Inventory(usize),
     Interact(bool, bool),
+    /// Places the selected hotbar block against the face the player is looking at
+    Place,
     Jump,
     Stop,
 }
 
 pub struct Engine {
     terrain: World,
+    chunk_meshes: ChunkMeshCache,
     player: Player,
     entities: Vec<GameObject>,
     renderer: Renderer,
@@ -27,12 +43,45 @@ pub struct Engine {
 
     dimensions: (i32, i32),
     elapsed_time: f32,
+    /// Time since the last world tick
+    tick_time: f32,
     pub play_state: PlayState,
+
+    /// Radius, in chunks, of the area loaded around the player
+    pub view_distance: isize,
+    pub chunk_generation_budget: usize,
+    pub chunk_mesh_budget: usize,
+    /// Distance, in chunks, beyond which coarser LOD tiles are drawn instead of chunks, 0 to turn them off.
+    /// Shouldn't be more than `view_distance`, or there's a gap between the chunks and the tiles.
+    pub lod_distance: isize,
+    pub lod_mesh_budget: usize,
 }
 
 impl Engine {
 
     pub fn new(width: i32, height: i32, seed: u32, chunk_radius: isize) -> Self {
+        Engine::with_world(width, height, chunk_radius, World::with_worker_threads(seed, Engine::worker_threads()))
+    }
+
+    /// Like `new`, but the world's terrain is shaped by `preset` instead of the default preset
+    pub fn with_preset(width: i32, height: i32, seed: u32, chunk_radius: isize, preset: GeneratorPreset) -> Self {
+        let terrain = World::with_preset(seed, Engine::worker_threads(), BlockRegistry::core(), preset);
+        Engine::with_world(width, height, chunk_radius, terrain)
+    }
+
+    /// Like `new`, but the world is saved to and loaded from region files in `save_directory`
+    pub fn with_save_directory<P: AsRef<Path>>(width: i32, height: i32, seed: u32, chunk_radius: isize, save_directory: P) -> io::Result<Self> {
+        let mut terrain = World::with_worker_threads(seed, Engine::worker_threads());
+        terrain.open_save_directory(save_directory)?;
+        Ok(Engine::with_world(width, height, chunk_radius, terrain))
+    }
+
+    /// One worker per core, leaving a core for the render thread
+    fn worker_threads() -> usize {
+        std::thread::available_parallelism().map(|n| n.get().saturating_sub(1)).unwrap_or(1).max(1)
+    }
+
+    fn with_world(width: i32, height: i32, chunk_radius: isize, mut terrain: World) -> Self {
 
         let dimensions = (width, height);
 
@@ -40,9 +89,14 @@ impl Engine {
 
         let terrain_texture = Texture::from_dynamic_image_bytes(include_bytes!("../assets/terrain.png"), image::ImageFormat::Png);
         let world_shader = Shader::new(include_str!("../shaders/block_vertex.glsl"), include_str!("../shaders/block_fragment.glsl")).unwrap();
-        let terrain = World::new(terrain_texture, world_shader, seed, chunk_radius);
-        
         let player = Player::new(Vector3::new(0.0, 16.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+
+        // Queue the whole view distance up front, the player is held in place until the ground has loaded
+        if let Err(error) = terrain.load_chunks_around(&player.position, chunk_radius, usize::MAX) {
+            error!("Failed to save unloaded chunks: {}", error);
+        }
+        let chunk_meshes = ChunkMeshCache::new(terrain_texture, world_shader);
+
         let mut entities: Vec<GameObject> = Vec::new();
 
         // Test cube
@@ -61,12 +115,19 @@ impl Engine {
         Self {
             renderer,
             terrain,
+            chunk_meshes,
             player,
             entities,
             gui,
             dimensions,
             elapsed_time: 0.0,
+            tick_time: 0.0,
             play_state: PlayState::Running,
+            view_distance: chunk_radius,
+            chunk_generation_budget: CHUNK_GENERATION_BUDGET,
+            chunk_mesh_budget: CHUNK_MESH_BUDGET,
+            lod_distance: chunk_radius,
+            lod_mesh_budget: LOD_MESH_BUDGET,
         }
     }
     
@@ -79,19 +140,37 @@ impl Engine {
 
             
 
-            self.player.update(&self.entities, &self.terrain, delta_time);
+            let feet = Vector3::new(self.player.position.x.floor() as isize, self.player.position.y.floor() as isize, self.player.position.z.floor() as isize);
+            if self.terrain.is_chunk_loaded_at(feet) && self.terrain.is_chunk_loaded_at(feet - Vector3::new(0, 1, 0)) {
+                self.player.update(&self.entities, &self.terrain, delta_time);
+            }
+            if let Err(error) = self.terrain.load_chunks_around(&self.player.position, self.view_distance, self.chunk_generation_budget) {
+                error!("Failed to save unloaded chunks, keeping them loaded: {}", error);
+            }
+            self.terrain.load_lod_around(&self.player.position, self.lod_distance, self.lod_mesh_budget);
+
+            self.tick_time += delta_time;
+            let mut ticks = 0;
+            while self.tick_time >= TICK_LENGTH && ticks < MAX_TICKS_PER_FRAME {
+                self.terrain.tick();
+                self.tick_time -= TICK_LENGTH;
+                ticks += 1;
+            }
+            self.tick_time = self.tick_time.min(TICK_LENGTH);
             self.elapsed_time += delta_time;
         }
     }
 
     pub fn render(&mut self) {
-        let perspective_matrix: Matrix4<f32> = perspective_matrix(self.dimensions.0, self.dimensions.1);
+        let perspective_matrix: Matrix4<f32> = perspective_matrix(self.dimensions.0, self.dimensions.1, self.far_plane());
         let view_matrix: Matrix4<f32> = self.player.camera.view_matrix();
 
         
         
+        self.chunk_meshes.sync(&mut self.terrain, self.chunk_mesh_budget);
+
         self.renderer.select_rendertexture();
-        self.terrain.render(&view_matrix, &perspective_matrix, self.elapsed_time);
+        self.chunk_meshes.render(&self.player.camera.position, &view_matrix, &perspective_matrix, self.elapsed_time);
         for i in 0..self.entities.len() {
             let entity = &mut self.entities[i];
             entity.draw(&perspective_matrix, &view_matrix, self.elapsed_time);
@@ -101,14 +180,55 @@ impl Engine {
         self.gui.render(&self.player.inventory, &perspective_matrix, self.dimensions);
     }
 
+    /// Distance to the far clipping plane, which moves out to the horizon when LOD tiles are drawn
+    fn far_plane(&self) -> f32 {
+        let horizon = if self.lod_distance > 0 { lod::horizon_distance(self.lod_distance) } else { self.view_distance };
+        DEFAULT_FAR_PLANE.max((horizon * CHUNK_SIZE as isize) as f32)
+    }
+
+    /// Chunks drawn in the last frame, and chunks which have a mesh, visible or not
+    pub fn visible_chunks(&self) -> (usize, usize) {
+        self.chunk_meshes.visible_chunks()
+    }
+
+    /// Pauses the game and saves the world, the app may not come back from a pause
     pub fn pause(&mut self) {
         self.play_state = PlayState::Paused;
+        if let Err(error) = self.terrain.save() {
+            error!("Failed to save world: {}", error);
+        }
     }
 
     pub fn resume(&mut self) {
         self.play_state = PlayState::Running;
     }
 
+    /// Puts the selected hotbar block into the cell in front of the targeted face and takes it
+    /// out of the inventory. Does nothing if that cell holds anything but air or fluid, or if
+    /// a solid block would end up inside the player.
+    fn place_selected_block(&mut self) {
+        let target = match vectormath::dda(&self.terrain, &self.player.camera.position, &self.player.camera.forward, 6.0) {
+            Some((_, world_index, normal)) => world_index + normal,
+            None => return,
+        };
+        let block_id = match self.player.inventory.items[self.player.inventory.selected] {
+            Some((block_id, _)) => block_id,
+            None => return,
+        };
+        let registry = self.terrain.registry();
+        let occupant = &registry[self.terrain.block_at_global_pos(target)];
+        if !self.terrain.is_chunk_loaded_at(target) || (occupant.id != AIR && occupant.fluid.is_none()) {
+            return;
+        }
+        let block_box = Rect3::new(target.cast::<f32>().unwrap(), Vector3::new(1.0, 1.0, 1.0));
+        if registry[block_id].solid && rect_vs_rect(&block_box, &self.player.bounding_box()) {
+            return;
+        }
+        if let Some(block_id) = self.player.inventory.consume_currently_selected() {
+            self.terrain.place_at_global_pos(target, block_id);
+        }
+    }
+
     pub fn player_movement(&mut self, movement: PlayerMovement) {
         if self.play_state == PlayState::Running {
             match movement {
@@ -130,18 +250,21 @@ impl Engine {
                 },
                 PlayerMovement::Interact(left_hand, right_hand) => {
                     if right_hand {
-                        if let Some((_, world_index)) = vectormath::dda(&self.terrain, &self.player.camera.position, &self.player.camera.forward, 6.0) {
+                        if let Some((_, world_index, _)) = vectormath::dda(&self.terrain, &self.player.camera.position, &self.player.camera.forward, 6.0) {
                             let block_id = self.terrain.block_at_global_pos(world_index);
                             self.player.inventory.add_to_inventory(block_id);
                             self.terrain.destroy_at_global_pos(world_index);
                         }
                     }
                     if left_hand {
-                        if let Some((_, world_index)) = vectormath::dda(&self.terrain, &self.player.camera.position, &self.player.camera.forward, 6.0) {
+                        if let Some((_, world_index, _)) = vectormath::dda(&self.terrain, &self.player.camera.position, &self.player.camera.forward, 6.0) {
                             self.terrain.interact_at_global_pos(world_index);
                         }
                     }
                 }
+                PlayerMovement::Place => {
+                    self.place_selected_block();
+                }
             }
         }
     }
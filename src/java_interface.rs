@@ -89,7 +89,7 @@ pub unsafe extern fn Java_org_farriswheel_voxelgame_VoxelEngine_breakBlock(_env:
 #[no_mangle]
 pub unsafe extern fn Java_org_farriswheel_voxelgame_VoxelEngine_placeBlock(_env: JNIEnv, _: JClass, engine_ptr: jlong) {
     let engine = &mut *(engine_ptr as *mut Engine);
-    
+    engine.player_movement(PlayerMovement::Place);
 }
 
 #[no_mangle]
@@ -5,9 +5,8 @@ pub mod entity;
 pub mod player;
 mod renderer;
 mod macros;
-mod world;
+pub mod world;
 
-#[cfg(target_os = "android")]
 #[macro_use] extern crate log;
 #[cfg(target_os = "android")]
 extern crate android_log;
@@ -14,6 +14,6 @@ macro_rules! c_str {
 #[macro_export]
 macro_rules! offset_of {
     ($ty:ty, $field:ident) => {
-        &(*(ptr::null() as *const $ty)).$field as *const _ as usize
+        std::mem::offset_of!($ty, $field)
     }
 }
@@ -33,7 +33,9 @@ pub fn len(vec: &Vector3<f32>) -> f32 {
     len
 }
 
-pub fn dda(world: &World, start: &Vector3<f32>, dir: &Vector3<f32>, max_dist: f32) -> Option<(Vector3<f32>, Vector3<isize>)> {
+/// Steps a ray through the block grid until it hits a block the world collides with.
+/// Returns the point it hit, the block it hit and the normal of the face it entered that block through.
+pub fn dda(world: &World, start: &Vector3<f32>, dir: &Vector3<f32>, max_dist: f32) -> Option<(Vector3<f32>, Vector3<isize>, Vector3<isize>)> {
     let ray_dir = dir.normalize();
 
     let mut ray_unit_step_size = Vector3 {
@@ -85,6 +87,7 @@ pub fn dda(world: &World, start: &Vector3<f32>, dir: &Vector3<f32>, max_dist: f3
     }
 
     let mut dist = 0.0;
+    let mut normal;
     while dist < max_dist {
 
         let mut min_dist = ray_length_1d.x;
@@ -95,20 +98,23 @@ pub fn dda(world: &World, start: &Vector3<f32>, dir: &Vector3<f32>, max_dist: f3
 
         if min_dir == Vec3Direction::X {
             map_check.x += step.x;
+            normal = Vector3 {x: -step.x, y: 0, z: 0};
             dist = ray_length_1d.x;
             ray_length_1d.x += ray_unit_step_size.x;
         } else if min_dir == Vec3Direction::Y {
             map_check.y += step.y;
+            normal = Vector3 {x: 0, y: -step.y, z: 0};
             dist = ray_length_1d.y;
             ray_length_1d.y += ray_unit_step_size.y;
         } else {
             map_check.z += step.z;
+            normal = Vector3 {x: 0, y: 0, z: -step.z};
             dist = ray_length_1d.z;
             ray_length_1d.z += ray_unit_step_size.z;
         }
         if world.collision_at_world_pos(map_check) {
             return Some(
-                (start + ray_dir * dist, Vector3 { x: map_check.x, y: map_check.y, z: map_check.z})
+                (start + ray_dir * dist, Vector3 { x: map_check.x, y: map_check.y, z: map_check.z}, normal)
             );
         }
     }
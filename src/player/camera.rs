@@ -77,11 +77,14 @@ impl Camera {
     }
 }
 
-pub fn perspective_matrix(width: i32, height: i32) -> Matrix4<f32> {
+/// Distance to the far clipping plane when nothing further away needs to be drawn
+pub const DEFAULT_FAR_PLANE: f32 = 128.0;
+
+/// Projection which clips everything further than `zfar` blocks away
+pub fn perspective_matrix(width: i32, height: i32, zfar: f32) -> Matrix4<f32> {
     let aspect_ratio = height as f32 / width as f32;
 
     let fov: f32 = 3.141592 / 2.0;
-    let zfar = 128.0;
     let znear = 0.01;
 
     let f = 1.0 / (fov / 2.0).tan();
@@ -93,3 +96,36 @@ pub fn perspective_matrix(width: i32, height: i32) -> Matrix4<f32> {
         Vector4::new(0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0),
     )
 }
+
+/// The six planes bounding what the camera can see, pointing inwards
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, each as (a, b, c, d) for the plane ax + by + cz + d = 0
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Pulls the planes out of the combined view and projection matrix, so points are inside
+    /// exactly when they'd land inside the clip volume
+    pub fn new(view_matrix: &Matrix4<f32>, perspective_matrix: &Matrix4<f32>) -> Self {
+        let clip = perspective_matrix * view_matrix;
+        let row = |i: usize| Vector4::new(clip.x[i], clip.y[i], clip.z[i], clip.w[i]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Whether any part of the box between `min` and `max` may be visible. Boxes near a corner
+    /// of the frustum can pass without being visible, but visible boxes never fail.
+    pub fn intersects_box(&self, min: Vector3<f32>, max: Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal is the last to leave its inside
+            let corner = Vector3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.x * corner.x + plane.y * corner.y + plane.z * corner.z + plane.w >= 0.0
+        })
+    }
+}
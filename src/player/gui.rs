@@ -1,6 +1,6 @@
 use cgmath::{Matrix4, Vector2, Matrix2, SquareMatrix, Matrix3, Zero, Vector3};
 
-use crate::{renderer::{mesh::{Texture, Mesh2D}, shader::Shader, vertex::Vertex2D}, world::block::{TextureType, BLOCKS}};
+use crate::{renderer::{mesh::{Texture, Mesh2D}, shader::Shader, vertex::Vertex2D}, world::block::TextureType};
 
 use super::inventory::Inventory;
 
@@ -7,7 +7,6 @@ use cgmath::{Vector3, InnerSpace};
 use camera::Camera;
 
 use super::world::World;
-use super::world::block::BLOCKS;
 use crate::entity;
 use crate::entity::gameobject::GameObject;
 use crate::physics::collision::{self, Collider, Rect3};
@@ -77,7 +76,7 @@ impl Player {
         for block_x in (self.position.x.floor() as isize - 1) ..= (self.position.x.floor() as isize + 1) {
             for block_y in (self.position.y.floor() as isize - 1) ..= (self.position.y.floor() as isize + 2) {
                 for block_z in (self.position.z.floor() as isize - 1) ..= (self.position.z.floor() as isize + 1) {
-                    if !BLOCKS[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
+                    if !world.registry()[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
                         continue;
                     }
                     let block_bounding_box = collision::Rect3 {
@@ -107,7 +106,7 @@ impl Player {
         for block_x in (self.position.x.floor() as isize - 1) ..= (self.position.x.floor() as isize + 1) {
             for block_y in (self.position.y.floor() as isize - 1) ..= (self.position.y.floor() as isize + 2) {
                 for block_z in (self.position.z.floor() as isize - 1) ..= (self.position.z.floor() as isize + 1) {
-                    if !BLOCKS[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
+                    if !world.registry()[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
                         continue;
                     }
                     let block_bounding_box = collision::Rect3 {
@@ -150,7 +149,7 @@ impl Player {
         for block_x in (self.position.x.floor() as isize - 1) ..= (self.position.x.floor() as isize + 1) {
             for block_y in (self.position.y.floor() as isize - 1) ..= (self.position.y.floor() as isize + 2) {
                 for block_z in (self.position.z.floor() as isize - 1) ..= (self.position.z.floor() as isize + 1) {
-                    if !BLOCKS[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
+                    if !world.registry()[world.block_at_global_pos(Vector3::new(block_x, block_y, block_z))].solid {
                         continue;
                     }
                     let block_bounding_box = collision::Rect3 {
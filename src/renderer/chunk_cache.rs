@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+use crate::{c_str, player::camera::Frustum, world::{World, CHUNK_SIZE, FACE_NEIGHBOURS, block::RenderLayer, lod::{LOD_LEVELS, LodTile}, mesher::LayeredMesh, visibility::{ChunkVisibility, opposite_face}}};
+
+use super::{mesh::{ChunkMesh, Texture}, shader::Shader, vertex::Vertex3D};
+
+/// How far the camera has to move, in blocks, before a chunk's translucent faces are sorted again
+const RESORT_DISTANCE: f32 = 1.0;
+/// Seconds an LOD tile takes to fade in when it's built, or out once whatever replaces it is built
+const LOD_FADE_TIME: f32 = 0.5;
+
+/// GPU-side meshes of one chunk or LOD tile, `None` for the render layers it has no faces in
+struct ChunkMeshes {
+    opaque: Option<ChunkMesh>,
+    cutout: Option<ChunkMesh>,
+    translucent: Option<ChunkMesh>,
+    /// Lowest corner in world coordinates, and the width of the mesh's unit cube in blocks
+    origin: Vector3<f32>,
+    scale: f32,
+    model_matrix: Matrix4<f32>,
+    /// Camera position, in the mesh's own coordinates, the translucent faces were last sorted for
+    sorted_from: Option<Vector3<f32>>,
+}
+
+impl ChunkMeshes {
+    fn new(mesh: LayeredMesh, origin: Vector3<isize>, scale: usize, texture: Texture, shader: &Shader) -> Self {
+        let origin = origin.cast::<f32>().unwrap();
+        let scale = scale as f32;
+        let LayeredMesh { opaque, cutout, translucent } = mesh;
+        let upload = |vertices: Vec<Vertex3D>| if vertices.is_empty() { None } else { Some(ChunkMesh::new(vertices, texture, shader.clone())) };
+        Self {
+            opaque: upload(opaque),
+            cutout: upload(cutout),
+            translucent: upload(translucent),
+            origin,
+            scale,
+            model_matrix: Matrix4::from_translation(origin) * Matrix4::from_scale(scale),
+            sorted_from: None,
+        }
+    }
+
+    fn layer(&self, layer: RenderLayer) -> Option<&ChunkMesh> {
+        match layer {
+            RenderLayer::Opaque => self.opaque.as_ref(),
+            RenderLayer::Cutout => self.cutout.as_ref(),
+            RenderLayer::Translucent => self.translucent.as_ref(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.opaque.is_none() && self.cutout.is_none() && self.translucent.is_none()
+    }
+
+    /// Sorts the translucent faces back to front again if the camera has moved far enough since the last time
+    fn sort_translucent(&mut self, camera_position: &Vector3<f32>) {
+        let eye = (camera_position - self.origin) / self.scale;
+        let scale = self.scale;
+        if let Some(mesh) = &mut self.translucent {
+            if self.sorted_from.is_none_or(|sorted_from| (eye - sorted_from).magnitude() * scale > RESORT_DISTANCE) {
+                mesh.update_vertices(sort_back_to_front(&mesh.vertices, &eye));
+                self.sorted_from = Some(eye);
+            }
+        }
+    }
+}
+
+/// Meshes of an LOD tile and how far it has faded in
+struct LodMeshes {
+    meshes: ChunkMeshes,
+    /// From 0 when none of the tile is drawn to 1 when all of it is
+    fade: f32,
+    /// Whether the world still picks the tile
+    selected: bool,
+    /// Whether everything covering a tile the world no longer picks has been built, so it can fade out
+    replaced: bool,
+}
+
+/// Something `render` draws
+#[derive(Clone, Copy)]
+enum Drawn {
+    Chunk(Vector3<isize>),
+    Lod(LodTile),
+}
+
+/// GPU-side copies of the chunk and LOD tile meshes produced by `World`
+pub(crate) struct ChunkMeshCache {
+    meshes: HashMap<Vector3<isize>, ChunkMeshes>,
+    /// Which faces of each chunk see each other, kept for chunks without a mesh too,
+    /// since solid chunks have no faces but still hide what's behind them
+    visibility: HashMap<Vector3<isize>, ChunkVisibility>,
+    /// Tiles drawn beyond the LOD distance, along with tiles which are fading out or waiting for their replacements
+    lod_meshes: HashMap<LodTile, LodMeshes>,
+    texture: Texture,
+    shader: Shader,
+    /// Chunks which were drawn the last time the cache was rendered
+    visible_chunks: usize,
+    /// Time of the last render, which fades are advanced from
+    rendered_at: Option<f32>,
+}
+
+impl ChunkMeshCache {
+    pub(crate) fn new(texture: Texture, shader: Shader) -> Self {
+        Self {
+            meshes: HashMap::new(),
+            visibility: HashMap::new(),
+            lod_meshes: HashMap::new(),
+            texture,
+            shader,
+            visible_chunks: 0,
+            rendered_at: None,
+        }
+    }
+
+    /// Uploads the vertex buffers of up to `max_meshes` chunks `world` has remeshed since the last sync,
+    /// and of every LOD tile it has built since then
+    pub(crate) fn sync(&mut self, world: &mut World, max_meshes: usize) {
+        for (chunk_index, mesh) in world.take_dirty_meshes(max_meshes) {
+            match world.chunk_visibility(&chunk_index) {
+                Some(visibility) => self.visibility.insert(chunk_index, visibility),
+                None => self.visibility.remove(&chunk_index),
+            };
+            if mesh.is_empty() {
+                self.meshes.remove(&chunk_index);
+                continue;
+            }
+            let meshes = ChunkMeshes::new(mesh, chunk_index * CHUNK_SIZE as isize, 1, self.texture, &self.shader);
+            self.meshes.insert(chunk_index, meshes);
+        }
+
+        for (tile, mesh) in world.take_lod_meshes() {
+            let meshes = ChunkMeshes::new(mesh, tile.origin(), tile.scale(), self.texture, &self.shader);
+            // A tile picked again while it was still fading out carries on from where it got to
+            let fade = self.lod_meshes.get(&tile).map_or(0.0, |lod| lod.fade);
+            self.lod_meshes.insert(tile, LodMeshes { meshes, fade, selected: true, replaced: false });
+        }
+        let selected = world.lod_tiles();
+        let unbuilt: Vec<LodTile> = selected.iter().filter(|tile| !self.lod_meshes.contains_key(tile)).copied().collect();
+        for (tile, lod) in &mut self.lod_meshes {
+            lod.selected = selected.contains(tile);
+            lod.replaced = !lod.selected && !unbuilt.iter().any(|other| other.overlaps(tile));
+        }
+    }
+
+    /// Draws the chunks which can be seen from `camera_position`, skipping the ones outside the
+    /// view frustum and the ones hidden behind solid chunks, and the LOD tiles in the frustum.
+    /// Chunks are left out where a tile which has fully faded in stands in for them.
+    /// Opaque faces go first, then cutout faces, and last the translucent faces, which are blended
+    /// from the furthest mesh to the closest and from the furthest face to the closest within each mesh.
+    pub(crate) fn render(&mut self, camera_position: &Vector3<f32>, view_matrix: &Matrix4<f32>, perspective_matrix: &Matrix4<f32>, elapsed_time: f32) {
+        let frustum = Frustum::new(view_matrix, perspective_matrix);
+        let visible = self.visible_from(camera_position, &frustum);
+        self.advance_lod_fades(elapsed_time - self.rendered_at.unwrap_or(elapsed_time));
+        self.rendered_at = Some(elapsed_time);
+
+        let distance = |meshes: &ChunkMeshes| {
+            let center = meshes.origin + Vector3::new(1.0, 1.0, 1.0) * (meshes.scale * CHUNK_SIZE as f32 / 2.0);
+            (center - camera_position).magnitude2()
+        };
+        let mut drawn: Vec<(f32, Drawn)> = self.meshes.iter()
+            .filter(|(chunk_index, _)| visible.contains(chunk_index) && !self.covered_by_lod(chunk_index))
+            .map(|(chunk_index, meshes)| (distance(meshes), Drawn::Chunk(*chunk_index)))
+            .collect();
+        self.visible_chunks = drawn.len();
+        drawn.extend(self.lod_meshes.iter()
+            .filter(|(_, lod)| !lod.meshes.is_empty())
+            .filter(|(tile, _)| {
+                let min = tile.origin().cast::<f32>().unwrap();
+                frustum.intersects_box(min, min + Vector3::new(1.0, 1.0, 1.0) * tile.size() as f32)
+            })
+            .map(|(tile, lod)| (distance(&lod.meshes), Drawn::Lod(*tile))));
+        drawn.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, item) in &drawn {
+            let meshes = match item {
+                Drawn::Chunk(chunk_index) => self.meshes.get_mut(chunk_index),
+                Drawn::Lod(tile) => self.lod_meshes.get_mut(tile).map(|lod| &mut lod.meshes),
+            };
+            if let Some(meshes) = meshes {
+                meshes.sort_translucent(camera_position);
+            }
+        }
+
+        unsafe {
+            self.shader.use_program();
+            self.shader.set_mat4(c_str!("perspective_matrix"), perspective_matrix);
+            self.shader.set_mat4(c_str!("view_matrix"), view_matrix);
+            self.shader.set_vec3(c_str!("sunlight_direction"), &Vector3::new(0.0, 2.0_f32.sqrt(), 2.0_f32.sqrt()));
+            self.shader.set_float(c_str!("time"), elapsed_time);
+
+            gl::Disable(gl::BLEND);
+            self.shader.set_float(c_str!("alpha_cutoff"), 0.0);
+            self.draw_layer(drawn.iter(), RenderLayer::Opaque);
+            self.shader.set_float(c_str!("alpha_cutoff"), 0.5);
+            self.draw_layer(drawn.iter(), RenderLayer::Cutout);
+
+            // Translucent faces are tested against the depth of everything else without hiding each other
+            gl::Enable(gl::BLEND);
+            gl::DepthMask(gl::FALSE);
+            self.shader.set_float(c_str!("alpha_cutoff"), 0.0);
+            self.draw_layer(drawn.iter().rev(), RenderLayer::Translucent);
+            gl::DepthMask(gl::TRUE);
+        }
+    }
+
+    /// Draws one render layer of the given chunks and tiles in order
+    fn draw_layer<'a>(&self, items: impl Iterator<Item = &'a (f32, Drawn)>, layer: RenderLayer) {
+        for (_, item) in items {
+            let (meshes, fade) = match item {
+                Drawn::Chunk(chunk_index) => match self.meshes.get(chunk_index) {
+                    Some(meshes) => (meshes, 1.0),
+                    None => continue,
+                },
+                Drawn::Lod(tile) => match self.lod_meshes.get(tile) {
+                    Some(lod) => (&lod.meshes, lod.fade),
+                    None => continue,
+                },
+            };
+            if let Some(mesh) = meshes.layer(layer) {
+                unsafe {
+                    self.shader.set_mat4(c_str!("model_matrix"), &meshes.model_matrix);
+                    self.shader.set_float(c_str!("fade"), fade);
+                }
+                mesh.draw(&self.shader);
+            }
+        }
+    }
+
+    /// Fades picked LOD tiles in and replaced ones out over `delta_time` seconds, dropping the ones which are gone
+    fn advance_lod_fades(&mut self, delta_time: f32) {
+        let step = delta_time.max(0.0) / LOD_FADE_TIME;
+        for lod in self.lod_meshes.values_mut() {
+            if lod.selected {
+                lod.fade = (lod.fade + step).min(1.0);
+            } else if lod.replaced {
+                lod.fade -= step;
+            }
+        }
+        self.lod_meshes.retain(|_, lod| lod.selected || lod.fade > 0.0);
+    }
+
+    /// Whether an LOD tile which has fully faded in stands in for the chunk.
+    /// Chunks keep being drawn under a tile while it fades in, so the tile appears over them.
+    fn covered_by_lod(&self, chunk_index: &Vector3<isize>) -> bool {
+        (1..=LOD_LEVELS).any(|level| {
+            self.lod_meshes.get(&LodTile::containing(chunk_index, level)).is_some_and(|lod| lod.selected && lod.fade >= 1.0)
+        })
+    }
+
+    /// Chunks drawn by the last `render`, and chunks which have a mesh at all
+    pub(crate) fn visible_chunks(&self) -> (usize, usize) {
+        (self.visible_chunks, self.meshes.len())
+    }
+
+    /// Walks outwards from the camera's chunk, only stepping into a neighbour if it's in the frustum
+    /// and the chunk being left can be seen through from the face it was entered by to the face
+    /// leading to the neighbour. Steps never head back towards the camera along an axis the walk
+    /// has already moved away from it on, which keeps it from going round behind solid rock.
+    /// Chunks which haven't been meshed yet are treated as empty, so they never hide anything.
+    fn visible_from(&self, camera_position: &Vector3<f32>, frustum: &Frustum) -> HashSet<Vector3<isize>> {
+        let size = CHUNK_SIZE as f32;
+        let start = Vector3::new(
+            (camera_position.x / size).floor() as isize,
+            (camera_position.y / size).floor() as isize,
+            (camera_position.z / size).floor() as isize,
+        );
+        // Only chunks within this many chunks of the camera can have a mesh, which bounds the walk
+        let reach = self.meshes.keys().chain(self.visibility.keys())
+            .map(|chunk_index| (chunk_index - start).map(isize::abs))
+            .map(|offset| offset.x.max(offset.y).max(offset.z))
+            .max()
+            .unwrap_or(0);
+
+        let mut visible = HashSet::new();
+        visible.insert(start);
+        // Chunk, the face it was entered through and the faces stepped through to reach it
+        let mut queue = VecDeque::new();
+        queue.push_back((start, None, 0u8));
+        while let Some((chunk_index, entered, directions)) = queue.pop_front() {
+            let visibility = self.visibility.get(&chunk_index).copied().unwrap_or(ChunkVisibility::OPEN);
+            for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+                if directions & (1 << opposite_face(face)) != 0 {
+                    continue;
+                }
+                if let Some(entered) = entered {
+                    if !visibility.connects(entered, face) {
+                        continue;
+                    }
+                }
+                let neighbour = chunk_index + offset;
+                let distance = (neighbour - start).map(isize::abs);
+                if distance.x.max(distance.y).max(distance.z) > reach || visible.contains(&neighbour) {
+                    continue;
+                }
+                let min = (neighbour * CHUNK_SIZE as isize).cast::<f32>().unwrap();
+                if !frustum.intersects_box(min, min + Vector3::new(size, size, size)) {
+                    continue;
+                }
+                visible.insert(neighbour);
+                queue.push_back((neighbour, Some(opposite_face(face)), directions | (1 << face)));
+            }
+        }
+        visible
+    }
+}
+
+/// Orders the triangles of `vertices` from the furthest from `eye` to the closest,
+/// so blending them in order lays each one over the ones behind it
+fn sort_back_to_front(vertices: &[Vertex3D], eye: &Vector3<f32>) -> Vec<Vertex3D> {
+    let mut triangles: Vec<(f32, &[Vertex3D])> = vertices.chunks_exact(3)
+        .map(|triangle| {
+            let center = (triangle[0].position + triangle[1].position + triangle[2].position) / 3.0;
+            ((center - eye).magnitude2(), triangle)
+        })
+        .collect();
+    triangles.sort_by(|a, b| b.0.total_cmp(&a.0));
+    triangles.into_iter().flat_map(|(_, triangle)| triangle.iter().copied()).collect()
+}
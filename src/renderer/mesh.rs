@@ -1,5 +1,4 @@
 pub use std::ffi::c_void;
-use std::ptr;
 use std::mem::size_of;
 use crate::{offset_of, c_str};
 use cgmath::{Vector3, Matrix4, Vector2, Matrix2, Matrix3};
@@ -120,6 +119,43 @@ impl ChunkMesh {
             let vertex_type_location = gl::GetAttribLocation(self.shader.id, c_str!("vtype").as_ptr()) as u32;
             gl::EnableVertexAttribArray(vertex_type_location);
             gl::VertexAttribPointer(vertex_type_location, 1, gl::INT, gl::FALSE, stride, offset_of!(Vertex3D, vtype) as *const c_void);
+
+            // atlas tile origin
+            let atlas_tile_location = gl::GetAttribLocation(self.shader.id, c_str!("atlas_tile").as_ptr()) as u32;
+            gl::EnableVertexAttribArray(atlas_tile_location);
+            gl::VertexAttribPointer(atlas_tile_location, 2, gl::FLOAT, gl::FALSE, stride, offset_of!(Vertex3D, atlas_tile) as *const c_void);
+
+            // vertex light
+            let light_location = gl::GetAttribLocation(self.shader.id, c_str!("light").as_ptr()) as u32;
+            gl::EnableVertexAttribArray(light_location);
+            gl::VertexAttribPointer(light_location, 2, gl::FLOAT, gl::FALSE, stride, offset_of!(Vertex3D, light) as *const c_void);
+
+            // vertex ambient occlusion
+            let ao_location = gl::GetAttribLocation(self.shader.id, c_str!("ao").as_ptr()) as u32;
+            gl::EnableVertexAttribArray(ao_location);
+            gl::VertexAttribPointer(ao_location, 1, gl::FLOAT, gl::FALSE, stride, offset_of!(Vertex3D, ao) as *const c_void);
+
+            // vertex biome tint
+            let tint_location = gl::GetAttribLocation(self.shader.id, c_str!("tint").as_ptr()) as u32;
+            gl::EnableVertexAttribArray(tint_location);
+            gl::VertexAttribPointer(tint_location, 3, gl::FLOAT, gl::FALSE, stride, offset_of!(Vertex3D, tint) as *const c_void);
+        }
+    }
+
+    /// Replaces the vertices with the same number of new ones, like the same triangles in another
+    /// order, rewriting the existing buffer instead of setting it up again
+    pub fn update_vertices(&mut self, vertices: Vec<Vertex3D>) {
+        assert_eq!(vertices.len(), self.vertices.len(), "Updated chunk mesh has a different number of vertices");
+        self.vertices = vertices;
+        if self.vbo == 0 {
+            return;
+        }
+        unsafe {
+            let size = (self.vertices.len() * size_of::<Vertex3D>()) as GLsizeiptr;
+            let data = &self.vertices[0] as *const Vertex3D as *const c_void;
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, data);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
 
@@ -127,7 +163,7 @@ impl ChunkMesh {
         if self.vao == 0 || self.vbo == 0 {
             return;
         }
-        
+
         unsafe {
             let texture_map_str = c_str!("texture_map").as_ptr();
             gl::Uniform1i(gl::GetUniformLocation(shader.id, texture_map_str), 0);
@@ -7,185 +7,225 @@ pub(crate) const CUBE_FACES: [[Vertex3D; 6]; 10] = [
     
     // Facing positive-X
     [
-        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },  // Front-bottom-right
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-right
-        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 }, // Front-top-right
+        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-right
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-right
     
-        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 }, // Front-top-right
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-right
-        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },  // Back-top-right
+        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-right
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-right
     ],
 
     // Facing negative-X
     [
-        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 }, // Front-top-left
-        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },  // Back-top-left
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },  // Front-bottom-left
+        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-left
+        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-left
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-left
         
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },  // Front-bottom-left
-        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },  // Back-top-left
-        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-left
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-left
+        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-left
+        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
     ],
 
     // Facing positive-Y
     [
-        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-top-right
-        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
+        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-right
+        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
     
-        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
-        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-top-right
-        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-top-left
+        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
+        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-right
+        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
     ],
     
     // Facing negative-Y
     [
-        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-bottom-right
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-bottom-left
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
+        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-right
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
 
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-bottom-left
-        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-left
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
     ],
 
     // Facing positive-Z
     [
-        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Front-bottom-left
+        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+        Vertex3D { position: Vector3::new(0.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
     
-        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Front-bottom-left
-        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Front-bottom-right
+        Vertex3D { position: Vector3::new( 1.0,  1.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+        Vertex3D { position: Vector3::new(0.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+        Vertex3D { position: Vector3::new( 1.0, 0.0,  1.0), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-right
     ],   
 
     // Facing negative-Z
     [
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-left
-        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+        Vertex3D { position: Vector3::new(0.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
+        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
     
-        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
-        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0  }     // Back-top-right
+        Vertex3D { position: Vector3::new( 1.0, 0.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+        Vertex3D { position: Vector3::new(0.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
+        Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }     // Back-top-right
     ],
 
     // Diagonal (0, 0) -> (1, 1)
     [
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
 
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(-0.701, 0.0, -0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
     ],
 
     // Diagonal (1, 1) -> (0, 0)
     [
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
 
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
     ],
 
     // Diagonal (0, 1) -> (1, 0)
     [
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
 
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.701, 0.0, 0.701), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
     ],
 
     // Diagonal (1, 0) -> (0, 1)
     [
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.0, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
 
-        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },
-        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0 },
+        Vertex3D { position: Vector3::new(0.146446609407, 0.99, 0.853553390593), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.0, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
+        Vertex3D { position: Vector3::new(0.853553390593, 0.99, 0.146446609407), normal: Vector3::new(0.0, 0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },
     ],
 ];
 
 pub(crate) const DEFAULT_CUBE: [Vertex3D; 36] = [
     // Facing positive-X
-    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },  // Front-bottom-right
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 }, // Front-top-right
+    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-right
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-right
 
-    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 }, // Front-top-right
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },  // Back-top-right
+    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-right
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-right
 
     // Facing negative-X
-    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 }, // Front-top-left
-    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },  // Back-top-left
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },  // Front-bottom-left
+    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }, // Front-top-left
+    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-left
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-left
     
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },  // Front-bottom-left
-    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },  // Back-top-left
-    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-left
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Front-bottom-left
+    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },  // Back-top-left
+    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( -1.0,  0.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
 
     // Facing positive-Y
-    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-top-right
-    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
+    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-right
+    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
 
-    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
-    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-top-right
-    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-top-left
+    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
+    Vertex3D { position: Vector3::new( 0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-right
+    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
     
     // Facing negative-Y
-    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-bottom-right
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-bottom-left
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
+    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-right
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
 
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-bottom-left
-    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-left
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  -1.0, 0.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
 
     // Facing positive-Z
-    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Front-top-left
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Front-bottom-left
+    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+    Vertex3D { position: Vector3::new(-0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-left
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
 
-    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0 },   // Front-top-right
-    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Front-bottom-left
-    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Front-bottom-right
+    Vertex3D { position: Vector3::new( 0.5,  0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-top-right
+    Vertex3D { position: Vector3::new(-0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-left
+    Vertex3D { position: Vector3::new( 0.5, -0.5,  0.5), normal: Vector3::new( 0.0,  0.0,  1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Front-bottom-right
 
     // Facing negative-Z
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-left
-    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new(-0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
+    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
 
-    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
-    Vertex3D { position: Vector3::new( 0.5, 0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0  }     // Back-top-right
+    Vertex3D { position: Vector3::new( 0.5, -0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new(-0.5,  0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
+    Vertex3D { position: Vector3::new( 0.5, 0.5, -0.5), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }     // Back-top-right
 
 ];
 
-pub fn push_face(position: &[f32; 3], face: usize, vertices: &mut Vec<Vertex3D>, texmap_offset: &(f32, f32), vertex_type: i32) {
+/// Axes the texture's u and v coordinates run along for each of the six cube faces in `CUBE_FACES`
+pub(crate) const FACE_UV_AXES: [(usize, usize); 6] = [(2, 1), (2, 1), (0, 2), (0, 2), (0, 1), (0, 1)];
+
+/// Ambient occlusion for faces which aren't occluded at all, like crossed planes
+pub const NO_OCCLUSION: [[f32; 2]; 2] = [[1.0; 2]; 2];
+
+pub fn push_face(position: &[f32; 3], face: usize, vertices: &mut Vec<Vertex3D>, texmap_offset: &(f32, f32), vertex_type: i32, light: &Vector2<f32>, ao: &[[f32; 2]; 2]) {
+    push_quad(position, face, &[1.0, 1.0, 1.0], vertices, texmap_offset, vertex_type, light, ao);
+}
+
+/// Pushes one of the six cube faces stretched to `size` blocks along each axis.
+/// The texture repeats once per block, the shader wraps it inside the tile at `texmap_offset`.
+/// `ao` holds the ambient occlusion of the corners indexed by their u and v coordinate, the quad
+/// is split along whichever diagonal keeps the interpolated occlusion symmetric.
+#[allow(clippy::too_many_arguments)]
+pub fn push_quad(position: &[f32; 3], face: usize, size: &[f32; 3], vertices: &mut Vec<Vertex3D>, texmap_offset: &(f32, f32), vertex_type: i32, light: &Vector2<f32>, ao: &[[f32; 2]; 2]) {
+    let (u_axis, v_axis) = if face < 6 { FACE_UV_AXES[face] } else { (0, 1) };
+    // Corners are numbered u * 2 + v, so opposite corners differ in both bits
+    let corner = |vertex: &Vertex3D| (vertex.position[u_axis] > 0.5) as usize * 2 + (vertex.position[v_axis] > 0.5) as usize;
+    let occlusion = |corner: usize| ao[corner / 2][corner % 2];
+
+    let mut quad = CUBE_FACES[face];
+    // The corner each triangle has to itself, the other two are the diagonal both triangles share
+    let first_only = quad[..3].iter().copied().find(|vertex| quad[3..].iter().all(|other| corner(other) != corner(vertex)));
+    let second_only = quad[3..].iter().copied().find(|vertex| quad[..3].iter().all(|other| corner(other) != corner(vertex)));
+    if let (Some(first_only), Some(second_only)) = (first_only, second_only) {
+        let (first_corner, second_corner) = (corner(&first_only), corner(&second_only));
+        if occlusion(first_corner) + occlusion(second_corner) > occlusion(first_corner ^ 1) + occlusion(first_corner ^ 2) {
+            // Each triangle trades a different end of the shared diagonal for the other triangle's
+            // own corner, which moves the split to the brighter diagonal and keeps the winding
+            for vertex in quad[..3].iter_mut().filter(|vertex| corner(vertex) == first_corner ^ 1) {
+                *vertex = second_only;
+            }
+            for vertex in quad[3..].iter_mut().filter(|vertex| corner(vertex) == first_corner ^ 2) {
+                *vertex = first_only;
+            }
+        }
+    }
+
+    for mut vertex in quad {
+        vertex.ao = occlusion(corner(&vertex));
 
-    for v in 0..6 {
-        let mut vertex = CUBE_FACES[face][v];
-        vertex.position.x += position[0];
-        vertex.position.y += position[1];
-        vertex.position.z += position[2];
+        vertex.position.x = vertex.position.x * size[0] + position[0];
+        vertex.position.y = vertex.position.y * size[1] + position[1];
+        vertex.position.z = vertex.position.z * size[2] + position[2];
 
-        vertex.tex_coords.x = vertex.tex_coords.x * 0.0625 + 0.0625 * texmap_offset.0 as f32;
-        vertex.tex_coords.y = vertex.tex_coords.y * 0.0625 + 0.0625 * texmap_offset.1 as f32;
+        vertex.tex_coords.x *= size[u_axis];
+        vertex.tex_coords.y *= size[v_axis];
+        vertex.atlas_tile = Vector2::new(0.0625 * texmap_offset.0, 0.0625 * texmap_offset.1);
 
-        vertex.vtype = vertex_type as i32;
+        vertex.vtype = vertex_type;
+        vertex.light = *light;
 
         vertices.push(vertex);
     }
-}
\ No newline at end of file
+}
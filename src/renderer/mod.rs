@@ -1,6 +1,7 @@
 use self::{postprocess::PostProcessRenderMesh, render_texture::RenderTexture};
 
 
+pub(crate) mod chunk_cache;
 pub(crate) mod mesh;
 pub(crate) mod meshgen;
 pub(crate) mod shader;
@@ -1,5 +1,4 @@
 pub(crate) use std::{mem::size_of, ffi::c_void};
-use std::ptr;
 use cgmath::{Vector3, Vector2};
 use gl::types::GLsizeiptr;
 
@@ -9,13 +8,13 @@ use crate::{offset_of, c_str};
 use super::{shader::Shader, vertex::Vertex3D, mesh::Texture};
 
 pub const POSTPROCESS_VERTICES: [Vertex3D; 6] = [
-    Vertex3D { position: Vector3::new( 1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new(-1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0 },   // Back-bottom-left
-    Vertex3D { position: Vector3::new(-1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
+    Vertex3D { position: Vector3::new( 1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new(-1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-left
+    Vertex3D { position: Vector3::new(-1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
 
-    Vertex3D { position: Vector3::new( 1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0 },   // Back-bottom-right
-    Vertex3D { position: Vector3::new(-1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0 },   // Back-top-left
-    Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0  }     // Back-top-right
+    Vertex3D { position: Vector3::new( 1.0, -1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 0.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-bottom-right
+    Vertex3D { position: Vector3::new(-1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(0.0, 1.0) , vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) },   // Back-top-left
+    Vertex3D { position: Vector3::new( 1.0,  1.0, 0.0), normal: Vector3::new( 0.0,  0.0, -1.0), tex_coords: Vector2::new(1.0, 1.0), vtype: 0, atlas_tile: Vector2::new(0.0, 0.0), light: Vector2::new(0.0, 0.0), ao: 1.0, tint: Vector3::new(1.0, 1.0, 1.0) }     // Back-top-right
 ];
 
 pub(crate) struct PostProcessRenderMesh {
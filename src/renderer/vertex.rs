@@ -8,6 +8,14 @@ pub struct Vertex3D {
     pub normal: Vector3<f32>,
     pub tex_coords: Vector2<f32>,
     pub vtype: i32,
+    /// Origin of the block's tile in the texture atlas, `tex_coords` count whole tiles from it
+    pub atlas_tile: Vector2<f32>,
+    /// Sky and block light reaching the face, from 0 for darkness to 1 for full brightness
+    pub light: Vector2<f32>,
+    /// Ambient occlusion at the vertex, from 0 for a corner boxed in by blocks to 1 for an open one
+    pub ao: f32,
+    /// Colour the texture is multiplied by, white unless the biome tints the block
+    pub tint: Vector3<f32>,
 }
 
 impl Vertex3D {
@@ -26,6 +34,10 @@ impl Default for Vertex3D {
             normal: Vector3::zero(),
             tex_coords: Vector2::zero(),
             vtype: 0,
+            atlas_tile: Vector2::zero(),
+            light: Vector2::zero(),
+            ao: 1.0,
+            tint: Vector3::new(1.0, 1.0, 1.0),
         }
     }
 }
@@ -0,0 +1,134 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::block::BlockRegistry;
+
+/// Shapes of tree terrain generation can grow
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeKind {
+    Oak,
+    /// Oak with a longer trunk and a wider crown
+    TallOak,
+    /// Single log hidden in a clump of leaves
+    Bush,
+    /// Column of cactus up to three blocks tall
+    Cactus,
+}
+
+/// A biome as it's written in a generator preset, with blocks given by their identifier
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeDefinition {
+    pub id: String,
+    pub name: String,
+    /// Climate the biome is found in, both roughly from -1 to 1.
+    /// Each column belongs to the biome whose climate is closest to its own.
+    pub temperature: f64,
+    pub humidity: f64,
+    /// Top block of the terrain
+    pub surface: String,
+    /// Blocks between the surface and the stone
+    pub subsurface: String,
+    /// Added to the preset's base height
+    pub height_offset: f64,
+    /// Multiplies the height of the preset's hills and mountains
+    pub hill_scale: f64,
+    pub mountain_scale: f64,
+    /// Colour grass, plants and leaves are multiplied by
+    pub tint: [f32; 3],
+    /// Chance of a tree growing on any surface block, and how often each kind is picked
+    pub tree_chance: f64,
+    pub trees: Vec<(TreeKind, u32)>,
+    /// Chance of a plant growing on any surface block without a tree, and how often each plant is picked
+    pub ground_cover_chance: f64,
+    pub ground_cover: Vec<(String, u32)>,
+}
+
+impl BiomeDefinition {
+    pub(crate) fn validate(&self, registry: &BlockRegistry) -> Result<(), String> {
+        let ground_cover = self.ground_cover.iter().map(|(identifier, _)| identifier);
+        for identifier in vec![&self.surface, &self.subsurface].into_iter().chain(ground_cover) {
+            if registry.id(identifier).is_none() {
+                return Err(format!("Biome \"{}\" needs \"{}\", which isn't in the block registry", self.id, identifier));
+            }
+        }
+        let chance = 0.0..=1.0;
+        if !chance.contains(&self.tree_chance) || !chance.contains(&self.ground_cover_chance) || self.tree_chance + self.ground_cover_chance > 1.0 {
+            return Err(format!("Biome \"{}\" has tree and ground cover chances which don't add up to between 0 and 1", self.id));
+        }
+        if self.tint.iter().any(|channel| *channel < 0.0) {
+            return Err(format!("Biome \"{}\" has a negative tint", self.id));
+        }
+        Ok(())
+    }
+}
+
+/// A biome whose blocks have been looked up in the block registry
+#[derive(Clone, Debug)]
+pub struct Biome {
+    /// Index of the biome in its preset
+    pub id: usize,
+    pub identifier: String,
+    pub name: String,
+    pub temperature: f64,
+    pub humidity: f64,
+    pub surface: usize,
+    pub subsurface: usize,
+    pub height_offset: f64,
+    pub hill_scale: f64,
+    pub mountain_scale: f64,
+    pub tint: [f32; 3],
+    pub tree_chance: f64,
+    pub trees: Vec<(TreeKind, u32)>,
+    pub ground_cover_chance: f64,
+    pub ground_cover: Vec<(usize, u32)>,
+}
+
+impl Biome {
+    /// Panics if the definition names a block which isn't in the registry, like terrain generation does for its own blocks.
+    /// Presets read by `GeneratorPreset::from_json` have already been checked against the registry they're read with.
+    pub(crate) fn resolve(id: usize, definition: &BiomeDefinition, registry: &BlockRegistry) -> Self {
+        let block = |identifier: &str| registry.id(identifier)
+            .unwrap_or_else(|| panic!("Biome {} needs {}, which isn't in the block registry", definition.id, identifier));
+        Self {
+            id,
+            identifier: definition.id.clone(),
+            name: definition.name.clone(),
+            temperature: definition.temperature,
+            humidity: definition.humidity,
+            surface: block(&definition.surface),
+            subsurface: block(&definition.subsurface),
+            height_offset: definition.height_offset,
+            hill_scale: definition.hill_scale,
+            mountain_scale: definition.mountain_scale,
+            tint: definition.tint,
+            tree_chance: definition.tree_chance,
+            trees: definition.trees.clone(),
+            ground_cover_chance: definition.ground_cover_chance,
+            ground_cover: definition.ground_cover.iter().map(|(identifier, weight)| (block(identifier), *weight)).collect(),
+        }
+    }
+
+    /// Distance between the biome's climate and the given one
+    pub(crate) fn climate_distance(&self, temperature: f64, humidity: f64) -> f64 {
+        let (dt, dh) = (self.temperature - temperature, self.humidity - humidity);
+        (dt * dt + dh * dh).sqrt()
+    }
+}
+
+/// Picks one of `choices` with a chance proportional to its weight, `None` if there's nothing to pick
+pub(crate) fn pick_weighted<T: Copy, R: Rng>(choices: &[(T, u32)], rng: &mut R) -> Option<T> {
+    let total: u32 = choices.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0, total);
+    for (choice, weight) in choices {
+        if roll < *weight {
+            return Some(*choice);
+        }
+        roll -= weight;
+    }
+    None
+}
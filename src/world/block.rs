@@ -1,48 +1,37 @@
-pub const BLOCKS: [Block; 13] = [
-    Block {id: 0, name: "Air", solid: false, transparent: true, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: None },
-    Block {id: 1, name: "Stone", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(1.0, 15.0)) },
-    Block {id: 2, name: "Grass", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::TopSideBottom((0.0, 15.0), (3.0, 15.0), (2.0, 15.0))) },
-    Block {id: 3, name: "Dirt", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(2.0, 15.0)) },
-    Block {id: 4, name: "Rose", solid: false, transparent: true, block_type: BlockType::Grass, mesh_type: MeshType::CrossedPlanes, texture_map: Some(TextureType::Single(12.0, 15.0))},
-    Block {id: 5, name: "Oak Log", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::TopSideBottom((5.0, 14.0), (4.0, 14.0), (5.0, 14.0)))},
-    Block {id: 6, name: "Dandelion", solid: false, transparent: true, block_type: BlockType::Grass, mesh_type: MeshType::CrossedPlanes, texture_map: Some(TextureType::Single(13.0, 15.0))},
-    Block {id: 7, name: "Oak Leaves", solid: true, transparent: true, block_type: BlockType::Leaves, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(4.0, 12.0))},
-    Block {id: 8, name: "Short Grass", solid: false, transparent: true, block_type: BlockType::Grass, mesh_type: MeshType::CrossedPlanes, texture_map: Some(TextureType::Single(7.0, 13.0))},
-    Block {id: 9, name: "Fern", solid: false, transparent: true, block_type: BlockType::Grass, mesh_type: MeshType::CrossedPlanes, texture_map: Some(TextureType::Single(8.0, 12.0))},
-    Block {id: 10, name: "Iron Ore", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(1.0, 13.0))},
-    Block {id: 11, name: "Coal", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(2.0, 13.0))},
-    Block {id: 12, name: "Glass", solid: true, transparent: true, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(1.0, 12.0))},
-    //Block {id: 14, name: "Diamond Ore", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(2.0, 12.0))},
-    //Block {id: 4, name: "Cobblestone", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(0.0, 14.0)) },
-    //Block {id: 5, name: "Oak Plank", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(4.0, 15.0)) },
-    //Block {id: 6, name: "Sand", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::Single(2.0, 14.0)) },
-    //Block {id: 17, name: "Furnace", solid: true, transparent: false, block_type: BlockType::Block, mesh_type: MeshType::Block, texture_map: Some(TextureType::TopSideFrontActivatable((12.0, 13.0),(13.0, 12.0), (13.0, 13.0),(14.0, 12.0)))},
-];
-
-pub fn block_index_by_name(name: &str) -> usize {
-    for i in 0..BLOCKS.len() {
-        if BLOCKS[i].name == name {
-            return i;
-        }
-    }
-    0
-}
+use std::{collections::HashMap, ops::Index, sync::{Arc, OnceLock}};
+
+use serde::Deserialize;
+
+use super::fluid::MAX_SPREAD;
+
+/// Definitions of the blocks which ship with the game
+pub const CORE_BLOCKS: &str = include_str!("../../assets/blocks.json");
+
+/// Numeric id of air, which every registry reserves for itself
+pub const AIR: usize = 0;
+pub const AIR_IDENTIFIER: &str = "core:air";
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BlockType {
     Block,
     Grass,
     Leaves,
+    Fluid,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MeshType {
     Block,
     CrossedPlanes,
+    /// Cube which is lowered to the height of the fluid in it
+    Fluid,
 }
 
 #[allow(unused)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TextureType {
     Single(f32, f32),
     TopAndSide((f32, f32), (f32, f32)),
@@ -50,42 +39,199 @@ pub enum TextureType {
     TopSideFrontActivatable((f32,f32), (f32, f32), (f32, f32), (f32, f32))
 }
 
-#[allow(unused)]
-#[derive(Copy, Clone)]
+/// Which pass the faces of a block are drawn in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderLayer {
+    /// Drawn first, without blending or discarding anything
+    Opaque,
+    /// Drawn wherever the texture's alpha is at least one half, like leaves and glass
+    Cutout,
+    /// Blended with whatever is behind it, so it's drawn last and from back to front, like water
+    Translucent,
+}
+
+/// Which faces of a block are coloured by the biome it's in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tint {
+    #[default]
+    None,
+    /// Only the top face, like grass whose sides are mostly dirt
+    Top,
+    All,
+}
+
+/// How a fluid block flows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluidProperties {
+    /// How many blocks the fluid flows sideways from a source, at most `fluid::MAX_SPREAD`
+    pub spread: u8,
+    /// Ticks between the fluid flowing one block and the next
+    pub tick_interval: u64,
+}
+
+#[derive(Clone, Debug)]
 pub struct Block {
+    /// Numeric id, assigned when the registry is loaded and only stable within one run
     pub id: usize,
-    pub name: &'static str,
+    /// Namespaced identifier like `core:grass`, which is what gets saved
+    pub identifier: String,
+    pub name: String,
     pub transparent: bool,
     pub solid: bool,
     pub block_type: BlockType,
     pub mesh_type: MeshType,
+    pub render_layer: RenderLayer,
     pub texture_map: Option<TextureType>,
-}
-
-impl Block {
-    pub fn _new(id: usize, name: &'static str, solid: bool, transparent: bool, block_type: BlockType, mesh_type: MeshType, texture_map: Option<TextureType>) -> Self {
-        Self {
-            id,
-            name,
-            solid,
-            transparent,
-            block_type,
-            mesh_type,
-            texture_map
-        }
-    }
+    pub tint: Tint,
+    /// Block light given off by the block, from 0 to 15
+    pub light_emission: u8,
+    /// Set for fluid blocks, which keep their level in the chunk's metadata
+    pub fluid: Option<FluidProperties>,
 }
 
 impl Default for Block {
     fn default() -> Self {
         Self {
-            id: 0,
-            name: "Air",
+            id: AIR,
+            identifier: String::from(AIR_IDENTIFIER),
+            name: String::from("Air"),
             solid: false,
             transparent: true,
             block_type: BlockType::Block,
             mesh_type: MeshType::Block,
-            texture_map: None
+            render_layer: RenderLayer::Cutout,
+            texture_map: None,
+            tint: Tint::None,
+            light_emission: 0,
+            fluid: None,
+        }
+    }
+}
+
+/// A block as it's written in the data file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockDefinition {
+    id: String,
+    name: String,
+    solid: bool,
+    transparent: bool,
+    block_type: BlockType,
+    mesh_type: MeshType,
+    /// Cutout for transparent blocks and opaque for the rest if it's left out
+    #[serde(default)]
+    render_layer: Option<RenderLayer>,
+    #[serde(default)]
+    texture: Option<TextureType>,
+    #[serde(default)]
+    tint: Tint,
+    #[serde(default)]
+    light: u8,
+    #[serde(default)]
+    fluid: Option<FluidProperties>,
+}
+
+/// Every block the world can contain, indexed by numeric id
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids: HashMap<String, usize>,
+}
+
+impl BlockRegistry {
+    /// Loads a JSON array of block definitions. Air always gets id 0, the rest are numbered
+    /// in the order they're listed, so ids are only meaningful alongside this registry.
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        let definitions: Vec<BlockDefinition> = serde_json::from_str(source).map_err(|error| format!("Invalid block definitions: {}", error))?;
+
+        let mut registry = Self {
+            blocks: vec![Block::default()],
+            ids: HashMap::new(),
+        };
+        registry.ids.insert(String::from(AIR_IDENTIFIER), AIR);
+
+        for definition in definitions {
+            if !BlockRegistry::is_valid_identifier(&definition.id) {
+                return Err(format!("Block identifier \"{}\" isn't of the form namespace:name", definition.id));
+            }
+            if definition.light > 15 {
+                return Err(format!("Block \"{}\" gives off more than the maximum light of 15", definition.id));
+            }
+            if (definition.block_type == BlockType::Fluid) != definition.fluid.is_some() {
+                return Err(format!("Block \"{}\" needs fluid properties exactly when its block type is fluid", definition.id));
+            }
+            if definition.fluid.is_some_and(|fluid| fluid.spread == 0 || fluid.spread > MAX_SPREAD || fluid.tick_interval == 0) {
+                return Err(format!("Block \"{}\" has to spread between 1 and {} blocks at a non-zero tick interval", definition.id, MAX_SPREAD));
+            }
+            if definition.render_layer == Some(RenderLayer::Translucent) && !definition.transparent {
+                return Err(format!("Block \"{}\" is drawn translucent but hides the faces behind it", definition.id));
+            }
+            if registry.ids.contains_key(&definition.id) {
+                return Err(format!("Block \"{}\" is defined more than once", definition.id));
+            }
+
+            let id = registry.blocks.len();
+            registry.ids.insert(definition.id.clone(), id);
+            registry.blocks.push(Block {
+                id,
+                identifier: definition.id,
+                name: definition.name,
+                solid: definition.solid,
+                transparent: definition.transparent,
+                block_type: definition.block_type,
+                mesh_type: definition.mesh_type,
+                render_layer: definition.render_layer.unwrap_or(if definition.transparent { RenderLayer::Cutout } else { RenderLayer::Opaque }),
+                texture_map: definition.texture,
+                tint: definition.tint,
+                light_emission: definition.light,
+                fluid: definition.fluid,
+            });
         }
+        Ok(registry)
+    }
+
+    /// The registry built from `CORE_BLOCKS`, loaded once and shared
+    pub fn core() -> Arc<BlockRegistry> {
+        static CORE: OnceLock<Arc<BlockRegistry>> = OnceLock::new();
+        CORE.get_or_init(|| Arc::new(BlockRegistry::from_json(CORE_BLOCKS).unwrap())).clone()
+    }
+
+    /// Looks up the numeric id of a block by its namespaced identifier
+    pub fn id(&self, identifier: &str) -> Option<usize> {
+        self.ids.get(identifier).copied()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Block> {
+        self.blocks.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+
+    fn is_valid_identifier(identifier: &str) -> bool {
+        let valid_part = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        match identifier.split_once(':') {
+            Some((namespace, name)) => valid_part(namespace) && valid_part(name),
+            None => false,
+        }
+    }
+}
+
+impl Index<usize> for BlockRegistry {
+    type Output = Block;
+
+    fn index(&self, id: usize) -> &Block {
+        &self.blocks[id]
+    }
+}
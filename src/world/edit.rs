@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use cgmath::Vector3;
+
+use super::{CHUNK_SIZE, NEIGHBOURS, World, feature::Replace, light::Lighting, template::{Template, Transform}};
+
+/// Box of blocks between two corners, both inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Vector3<isize>,
+    pub max: Vector3<isize>,
+}
+
+impl Bounds {
+    /// Smallest box holding both corners, whichever way round they're given
+    pub fn new(a: Vector3<isize>, b: Vector3<isize>) -> Self {
+        Self {
+            min: Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn size(&self) -> Vector3<usize> {
+        (self.max - self.min + Vector3::new(1, 1, 1)).cast::<usize>().unwrap()
+    }
+
+    pub fn contains(&self, world_pos: &Vector3<isize>) -> bool {
+        (0..3).all(|axis| self.min[axis] <= world_pos[axis] && world_pos[axis] <= self.max[axis])
+    }
+
+    /// Every position in the box, x first, then z, then y, the same order templates store their cells in
+    pub fn positions(&self) -> impl Iterator<Item = Vector3<isize>> {
+        let Bounds { min, max } = *self;
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| Vector3::new(x, y, z))))
+    }
+
+    fn include(&mut self, world_pos: &Vector3<isize>) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(world_pos[axis]);
+            self.max[axis] = self.max[axis].max(world_pos[axis]);
+        }
+    }
+}
+
+/// Bulk edits for level design. Each one writes its blocks a chunk at a time and then relights,
+/// remeshes and notifies neighbours once for the whole edit, instead of once per block.
+/// Only loaded chunks are edited, and every edit returns the bounds of the blocks which
+/// actually changed, `None` if nothing did.
+impl World {
+    /// Sets every block in `bounds` to `block_id`
+    pub fn fill(&mut self, bounds: Bounds, block_id: usize) -> Option<Bounds> {
+        let metadata = self.placed_metadata(block_id);
+        self.recorded(|world| world.apply_edits(bounds.positions().map(|world_pos| (world_pos, block_id, metadata)).collect()))
+    }
+
+    /// Turns every `from` block in `bounds` into `to`
+    pub fn replace(&mut self, bounds: Bounds, from: usize, to: usize) -> Option<Bounds> {
+        let metadata = self.placed_metadata(to);
+        let edits = bounds.positions()
+            .filter(|world_pos| self.is_chunk_loaded_at(*world_pos) && self.block_at_global_pos(*world_pos) == from)
+            .map(|world_pos| (world_pos, to, metadata))
+            .collect();
+        self.recorded(|world| world.apply_edits(edits))
+    }
+
+    /// Copies the blocks in `bounds` into a template whose origin is at `origin`, ready to be pasted
+    /// somewhere else. Blocks in chunks which aren't loaded are left out.
+    pub fn copy(&self, bounds: Bounds, origin: Vector3<isize>) -> Template {
+        let cells: Vec<Option<usize>> = bounds.positions()
+            .map(|world_pos| Some(self.block_at_global_pos(world_pos)).filter(|_| self.is_chunk_loaded_at(world_pos)))
+            .collect();
+        Template::from_cells(bounds.size(), origin - bounds.min, &cells)
+    }
+
+    /// Writes `template` with its origin at `world_pos`, turned by `transform`
+    pub fn paste(&mut self, template: &Template, world_pos: Vector3<isize>, transform: Transform) -> Option<Bounds> {
+        let mut blocks = Vec::new();
+        template.place(world_pos, transform, Replace::Always, &mut blocks);
+        let edits = blocks.into_iter().map(|(world_pos, block_id, _)| (world_pos, block_id, self.placed_metadata(block_id))).collect();
+        self.recorded(|world| world.apply_edits(edits))
+    }
+
+    /// Writes each block with its metadata, recording the changes if a transaction is open
+    pub(super) fn apply_edits(&mut self, edits: Vec<(Vector3<isize>, usize, usize)>) -> Option<Bounds> {
+        // Chunks are kept in the order they're first edited, so neighbours are notified in a fixed order
+        let mut chunk_order = Vec::new();
+        let mut by_chunk: HashMap<Vector3<isize>, Vec<_>> = HashMap::new();
+        for (world_pos, block_id, metadata) in edits {
+            let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
+            by_chunk.entry(chunk_index).or_insert_with(|| {
+                chunk_order.push(chunk_index);
+                Vec::new()
+            }).push((world_pos, block_index, block_id, metadata));
+        }
+
+        let mut changed = Vec::new();
+        for chunk_index in chunk_order {
+            let edits = by_chunk.remove(&chunk_index).unwrap_or_default();
+            let mut recorded = Vec::new();
+            let chunk = match self.chunks.get_mut(&chunk_index) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            // Corners of the edited blocks within the chunk, to find which neighbours they touch
+            let mut low = Vector3::new(CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE);
+            let mut high = Vector3::new(0, 0, 0);
+            for (world_pos, block_index, block_id, metadata) in edits {
+                let before = (chunk.block_at_chunk_pos(&block_index), chunk.metadata_at_chunk_pos(&block_index));
+                if before == (block_id, metadata) {
+                    continue;
+                }
+                recorded.push((world_pos, before, (block_id, metadata)));
+                chunk.set_block_at_chunk_pos(&block_index, block_id);
+                chunk.set_metadata_at_chunk_pos(&block_index, metadata);
+                changed.push(world_pos);
+                for axis in 0..3 {
+                    low[axis] = low[axis].min(block_index[axis]);
+                    high[axis] = high[axis].max(block_index[axis]);
+                }
+            }
+            for (world_pos, before, after) in recorded {
+                self.record_change(world_pos, before, after);
+            }
+            if low.x > high.x {
+                continue;
+            }
+            self.unsaved_chunks.insert(chunk_index);
+            self.dirty_chunks.insert(chunk_index);
+            // Neighbours only need remeshing if an edited block lies on the border they share
+            for offset in &NEIGHBOURS {
+                let touches = (0..3).all(|axis| match offset[axis] {
+                    -1 => low[axis] == 0,
+                    1 => high[axis] == CHUNK_SIZE - 1,
+                    _ => true,
+                });
+                if touches && self.chunks.contains_key(&(chunk_index + offset)) {
+                    self.dirty_chunks.insert(chunk_index + offset);
+                }
+            }
+        }
+
+        let first = *changed.first()?;
+        let mut bounds = Bounds::new(first, first);
+        for world_pos in &changed {
+            bounds.include(world_pos);
+        }
+        let mut lighting = Lighting::new(&mut self.chunks, &self.registry);
+        lighting.relight_blocks(&changed);
+        self.dirty_chunks.extend(lighting.changed);
+        self.notify_neighbours_of_all(&changed);
+        Some(bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{block::AIR, template::Rotation};
+
+    #[test]
+    fn bounds_are_ordered() {
+        let bounds = Bounds::new(Vector3::new(3, -1, 2), Vector3::new(1, 4, 2));
+        assert_eq!(bounds.min, Vector3::new(1, -1, 2));
+        assert_eq!(bounds.max, Vector3::new(3, 4, 2));
+        assert_eq!(bounds.size(), Vector3::new(3, 6, 1));
+        assert_eq!(bounds.positions().count(), 18);
+        assert_eq!(bounds.positions().next(), Some(bounds.min));
+        assert_eq!(bounds.positions().last(), Some(bounds.max));
+        assert!(bounds.contains(&Vector3::new(2, 0, 2)) && !bounds.contains(&Vector3::new(2, 0, 3)));
+    }
+
+    #[test]
+    fn fill_returns_what_changed() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        let bounds = Bounds::new(Vector3::new(-3, 2, -3), Vector3::new(3, 5, 3));
+        assert_eq!(world.fill(bounds, stone), Some(bounds));
+        assert!(world.fill(bounds, stone).is_none());
+
+        // Hollowing out the box only changes the inside
+        let inside = Bounds::new(Vector3::new(-2, 3, -2), Vector3::new(2, 4, 2));
+        assert_eq!(world.fill(inside, AIR), Some(inside));
+
+        // Chunks which aren't loaded are left out
+        let reaching_out = Bounds::new(Vector3::new(20, 0, 0), Vector3::new(40, 0, 0));
+        assert_eq!(world.fill(reaching_out, stone), Some(Bounds::new(Vector3::new(20, 0, 0), Vector3::new(31, 0, 0))));
+        assert!(!world.is_chunk_loaded_at(Vector3::new(32, 0, 0)));
+    }
+
+    #[test]
+    fn replace_only_touches_matching_blocks() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        let glass = world.registry().id("core:glass").unwrap();
+        world.fill(Bounds::new(Vector3::new(0, 0, 0), Vector3::new(9, 0, 9)), stone);
+        world.fill(Bounds::new(Vector3::new(2, 0, 3), Vector3::new(4, 0, 3)), glass);
+        world.fill(Bounds::new(Vector3::new(7, 0, 8), Vector3::new(7, 0, 8)), glass);
+
+        let everything = Bounds::new(Vector3::new(-5, -5, -5), Vector3::new(15, 5, 15));
+        assert_eq!(world.replace(everything, glass, AIR), Some(Bounds::new(Vector3::new(2, 0, 3), Vector3::new(7, 0, 8))));
+        assert!(world.replace(everything, glass, AIR).is_none());
+        assert_eq!(world.block_at_global_pos(Vector3::new(3, 0, 3)), AIR);
+        assert_eq!(world.block_at_global_pos(Vector3::new(3, 0, 4)), stone);
+    }
+
+    #[test]
+    fn paste_turns_the_template() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        let glass = world.registry().id("core:glass").unwrap();
+        // An L of stone with glass on top of its corner
+        world.fill(Bounds::new(Vector3::new(0, 0, 0), Vector3::new(2, 0, 0)), stone);
+        world.fill(Bounds::new(Vector3::new(0, 0, 1), Vector3::new(0, 0, 1)), stone);
+        world.fill(Bounds::new(Vector3::new(0, 1, 0), Vector3::new(0, 1, 0)), glass);
+        let template = world.copy(Bounds::new(Vector3::new(0, 0, 0), Vector3::new(2, 1, 1)), Vector3::new(0, 0, 0));
+
+        let turned = Transform { rotation: Rotation::Clockwise90, mirror: false };
+        let pasted = world.paste(&template, Vector3::new(-10, 4, -10), turned).unwrap();
+        // The arm along x now runs along z, and the one along z runs towards -x
+        assert_eq!(pasted, Bounds::new(Vector3::new(-11, 4, -10), Vector3::new(-10, 5, -8)));
+        for z in -10..=-8 {
+            assert_eq!(world.block_at_global_pos(Vector3::new(-10, 4, z)), stone);
+        }
+        assert_eq!(world.block_at_global_pos(Vector3::new(-11, 4, -10)), stone);
+        assert_eq!(world.block_at_global_pos(Vector3::new(-10, 5, -10)), glass);
+        // Air in the template is pasted too, so pasting it again changes nothing
+        assert!(world.paste(&template, Vector3::new(-10, 4, -10), turned).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, fs, io::{self, BufWriter, Write}, path::Path};
+
+use cgmath::Vector3;
+use serde_json::json;
+
+use crate::renderer::vertex::Vertex3D;
+
+use super::{CHUNK_SIZE, Chunk, NEIGHBOURS, World, block::AIR, edit::Bounds, mesher::MeshingMode};
+
+/// Texture atlas the exported UVs point into, written next to OBJ files and embedded in glTF ones
+const TERRAIN_ATLAS: &[u8] = include_bytes!("../../assets/terrain.png");
+const ATLAS_FILE_NAME: &str = "terrain.png";
+/// Width of a tile in the atlas, as a fraction of the whole atlas
+const ATLAS_TILE_SIZE: f32 = 0.0625;
+
+/// Exports regions of the world as textured meshes for other programs, such as Blender.
+/// Faces are culled the same way as in the game, except that blocks just outside the exported
+/// region count as air, so the model is closed where it was cut out of the world.
+/// Positions are relative to the lowest corner of the region, in blocks, with y up.
+/// Lighting isn't baked in, but biome tints are kept as vertex colours.
+impl World {
+    /// Writes the blocks in `bounds` to a Wavefront OBJ file at `path`, along with a material
+    /// library of the same name and the texture atlas in the same directory
+    pub fn export_obj<P: AsRef<Path>>(&self, bounds: Bounds, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let vertices = self.export_mesh(bounds)?;
+        let material_path = path.with_extension("mtl");
+        let material_name = material_path.file_name().and_then(|name| name.to_str()).unwrap_or("terrain.mtl");
+
+        let mut obj = BufWriter::new(fs::File::create(path)?);
+        writeln!(obj, "# {} blocks exported from a voxel world", bounds.size().x * bounds.size().y * bounds.size().z)?;
+        writeln!(obj, "mtllib {}", material_name)?;
+        writeln!(obj, "o terrain")?;
+        writeln!(obj, "usemtl terrain")?;
+        // OBJ indexes positions, texture coordinates and normals separately, so each list is deduplicated on its own.
+        // Positions carry the tint as a vertex colour, which Blender and most other importers understand.
+        let mut positions = HashMap::new();
+        let mut uvs = HashMap::new();
+        let mut normals = HashMap::new();
+        let mut face = Vec::with_capacity(3);
+        for vertex in &vertices {
+            let position = [vertex.position.x, vertex.position.y, vertex.position.z, vertex.tint.x, vertex.tint.y, vertex.tint.z];
+            let position_index = obj_index(&mut positions, position, |[x, y, z, r, g, b]| format!("v {} {} {} {} {} {}", x, y, z, r, g, b), &mut obj)?;
+            let uv_index = obj_index(&mut uvs, [vertex.tex_coords.x, vertex.tex_coords.y], |[u, v]| format!("vt {} {}", u, v), &mut obj)?;
+            let normal_index = obj_index(&mut normals, [vertex.normal.x, vertex.normal.y, vertex.normal.z], |[x, y, z]| format!("vn {} {} {}", x, y, z), &mut obj)?;
+            face.push(format!("{}/{}/{}", position_index, uv_index, normal_index));
+            if face.len() == 3 {
+                writeln!(obj, "f {}", face.join(" "))?;
+                face.clear();
+            }
+        }
+        obj.flush()?;
+
+        // Alpha comes from the atlas as well, so leaves and glass stay see-through
+        let mut mtl = BufWriter::new(fs::File::create(&material_path)?);
+        writeln!(mtl, "newmtl terrain")?;
+        writeln!(mtl, "Ka 1.0 1.0 1.0")?;
+        writeln!(mtl, "Kd 1.0 1.0 1.0")?;
+        writeln!(mtl, "Ks 0.0 0.0 0.0")?;
+        writeln!(mtl, "illum 1")?;
+        writeln!(mtl, "map_Kd {}", ATLAS_FILE_NAME)?;
+        writeln!(mtl, "map_d {}", ATLAS_FILE_NAME)?;
+        mtl.flush()?;
+
+        fs::write(path.with_file_name(ATLAS_FILE_NAME), TERRAIN_ATLAS)
+    }
+
+    /// Writes the blocks in `bounds` to a binary glTF file at `path`, with the texture atlas embedded
+    pub fn export_glb<P: AsRef<Path>>(&self, bounds: Bounds, path: P) -> io::Result<()> {
+        let vertices = self.export_mesh(bounds)?;
+
+        let mut indices = Vec::with_capacity(vertices.len());
+        let mut unique = HashMap::new();
+        let mut attributes: [Vec<f32>; 4] = Default::default();
+        for vertex in &vertices {
+            // glTF puts the origin of texture coordinates at the top of the image, OpenGL at the bottom
+            let values = [
+                vertex.position.x, vertex.position.y, vertex.position.z,
+                vertex.normal.x, vertex.normal.y, vertex.normal.z,
+                vertex.tex_coords.x, 1.0 - vertex.tex_coords.y,
+                vertex.tint.x, vertex.tint.y, vertex.tint.z,
+            ];
+            let next = unique.len() as u32;
+            let index = *unique.entry(values.map(f32::to_bits)).or_insert_with(|| {
+                attributes[0].extend_from_slice(&values[0..3]);
+                attributes[1].extend_from_slice(&values[3..6]);
+                attributes[2].extend_from_slice(&values[6..8]);
+                attributes[3].extend_from_slice(&values[8..11]);
+                next
+            });
+            indices.push(index);
+        }
+        let count = unique.len();
+
+        // Every view starts on a multiple of four bytes, as glTF requires for float and index data
+        let mut buffer = Vec::new();
+        let mut views = Vec::new();
+        let mut add_view = |bytes: &[u8], target: Option<u32>| {
+            let offset = buffer.len();
+            buffer.extend_from_slice(bytes);
+            buffer.resize(buffer.len().div_ceil(4) * 4, 0);
+            let mut view = json!({ "buffer": 0, "byteOffset": offset, "byteLength": bytes.len() });
+            if let Some(target) = target {
+                view["target"] = json!(target);
+            }
+            views.push(view);
+        };
+        for values in &attributes {
+            add_view(&values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>(), Some(GL_ARRAY_BUFFER));
+        }
+        add_view(&indices.iter().flat_map(|index| index.to_le_bytes()).collect::<Vec<u8>>(), Some(GL_ELEMENT_ARRAY_BUFFER));
+        add_view(TERRAIN_ATLAS, None);
+
+        let (min, max) = attributes[0].chunks(3).fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), position| {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+            (min, max)
+        });
+        let document = json!({
+            "asset": { "version": "2.0", "generator": "libvoxel" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "name": "terrain", "mesh": 0 }],
+            "meshes": [{
+                "primitives": [{
+                    "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2, "COLOR_0": 3 },
+                    "indices": 4,
+                    "material": 0,
+                }],
+            }],
+            "materials": [{
+                "name": "terrain",
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 }, "metallicFactor": 0.0, "roughnessFactor": 1.0 },
+                "alphaMode": "MASK",
+                "alphaCutoff": 0.5,
+            }],
+            "textures": [{ "sampler": 0, "source": 0 }],
+            // Nearest filtering keeps the pixel art sharp and stops tiles bleeding into each other
+            "samplers": [{ "magFilter": GL_NEAREST, "minFilter": GL_NEAREST, "wrapS": GL_CLAMP_TO_EDGE, "wrapT": GL_CLAMP_TO_EDGE }],
+            "images": [{ "bufferView": 5, "mimeType": "image/png" }],
+            "buffers": [{ "byteLength": buffer.len() }],
+            "bufferViews": views,
+            "accessors": [
+                { "bufferView": 0, "componentType": GL_FLOAT, "count": count, "type": "VEC3", "min": min, "max": max },
+                { "bufferView": 1, "componentType": GL_FLOAT, "count": count, "type": "VEC3" },
+                { "bufferView": 2, "componentType": GL_FLOAT, "count": count, "type": "VEC2" },
+                { "bufferView": 3, "componentType": GL_FLOAT, "count": count, "type": "VEC3" },
+                { "bufferView": 4, "componentType": GL_UNSIGNED_INT, "count": indices.len(), "type": "SCALAR" },
+            ],
+        });
+
+        // The JSON chunk is padded with spaces and the binary chunk with zeros
+        let mut json = serde_json::to_vec(&document).map_err(io::Error::other)?;
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&buffer);
+        fs::write(path, glb)
+    }
+
+    /// Meshes the loaded chunks overlapping `bounds` one block at a time, so every face's UVs fit
+    /// inside its tile, and points the UVs into the atlas itself instead of into the block's tile
+    fn export_mesh(&self, bounds: Bounds) -> io::Result<Vec<Vertex3D>> {
+        let size = CHUNK_SIZE as isize;
+        let low = bounds.min.map(|value| value.div_euclid(size));
+        let high = bounds.max.map(|value| value.div_euclid(size));
+        let mut vertices = Vec::new();
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                for z in low.z..=high.z {
+                    let chunk_index = Vector3::new(x, y, z);
+                    let mut neighbourhood = match self.neighbourhood(&chunk_index) {
+                        Some(neighbourhood) => neighbourhood,
+                        None => continue,
+                    };
+                    neighbourhood.meshing_mode = MeshingMode::Naive;
+                    clip_chunk(&mut neighbourhood.center, chunk_index, &bounds);
+                    for (offset, neighbour) in NEIGHBOURS.iter().zip(neighbourhood.neighbours.iter_mut()) {
+                        let neighbour = neighbour.get_or_insert_with(|| Chunk::uniform(AIR));
+                        clip_chunk(neighbour, chunk_index + offset, &bounds);
+                    }
+
+                    let origin = (chunk_index * size - bounds.min).cast::<f32>().unwrap();
+                    for mut vertex in neighbourhood.gen_mesh().into_vertices() {
+                        vertex.position += origin;
+                        vertex.tex_coords = vertex.atlas_tile + vertex.tex_coords * ATLAS_TILE_SIZE;
+                        vertices.push(vertex);
+                    }
+                }
+            }
+        }
+        if vertices.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Nothing to export, the region has no visible blocks or isn't loaded"));
+        }
+        Ok(vertices)
+    }
+}
+
+const GL_FLOAT: u32 = 0x1406;
+const GL_UNSIGNED_INT: u32 = 0x1405;
+const GL_ARRAY_BUFFER: u32 = 0x8892;
+const GL_ELEMENT_ARRAY_BUFFER: u32 = 0x8893;
+const GL_NEAREST: u32 = 0x2600;
+const GL_CLAMP_TO_EDGE: u32 = 0x812F;
+
+/// Turns every block of a chunk outside `bounds` into air
+fn clip_chunk(chunk: &mut Chunk, chunk_index: Vector3<isize>, bounds: &Bounds) {
+    let origin = chunk_index * CHUNK_SIZE as isize;
+    let chunk_bounds = Bounds::new(origin, origin + Vector3::new(1, 1, 1) * (CHUNK_SIZE as isize - 1));
+    if bounds.contains(&chunk_bounds.min) && bounds.contains(&chunk_bounds.max) {
+        return;
+    }
+    for world_pos in chunk_bounds.positions() {
+        if !bounds.contains(&world_pos) {
+            chunk.set_block_at_chunk_pos(&(world_pos - origin).cast::<usize>().unwrap(), AIR);
+        }
+    }
+}
+
+/// 1-based index of a value in one of an OBJ file's lists, writing the value out the first time it's seen
+fn obj_index<const N: usize, W: Write>(
+    indices: &mut HashMap<[u32; N], usize>,
+    value: [f32; N],
+    line: impl Fn([f32; N]) -> String,
+    obj: &mut W,
+) -> io::Result<usize> {
+    let next = indices.len() + 1;
+    let index = *indices.entry(value.map(f32::to_bits)).or_insert(next);
+    if index == next {
+        writeln!(obj, "{}", line(value))?;
+    }
+    Ok(index)
+}
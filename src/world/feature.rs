@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use cgmath::Vector3;
+use rand::{Rng, rngs::StdRng};
+
+use super::{FACE_NEIGHBOURS, biome::TreeKind, block::BlockRegistry, generator::SpilledBlocks, template::{Template, TemplateFeature, WELL_TEMPLATE}};
+
+/// Which blocks a feature's block may overwrite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Replace {
+    /// Air, fluids and plants, so features don't cut into the terrain or into each other
+    NonSolid,
+    /// Only the given block, like ore replacing stone
+    Only(usize),
+    Always,
+}
+
+impl Replace {
+    pub fn allows(&self, target: usize, registry: &BlockRegistry) -> bool {
+        match self {
+            Replace::NonSolid => !registry[target].solid,
+            Replace::Only(block_id) => target == *block_id,
+            Replace::Always => true,
+        }
+    }
+}
+
+/// A group of blocks terrain generation places once a chunk's terrain is done, such as a tree,
+/// a boulder or an ore vein. A feature may reach into the chunks touching the one it starts in,
+/// blocks any further out are dropped.
+pub trait Feature: Send + Sync {
+    /// Adds the feature's blocks around `origin`, in world coordinates. `rng` is seeded from the
+    /// chunk, so the same seed always grows the same feature.
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks);
+}
+
+/// Where in a chunk a feature is tried
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// Above the surface block of a random column, unless a cave opened the surface up
+    Surface,
+    /// At a random block between two world heights, bottom inclusive and top exclusive
+    Between(isize, isize),
+}
+
+/// A feature along with where and how often it generates
+pub struct PlacedFeature {
+    pub feature: Box<dyn Feature>,
+    pub placement: Placement,
+    /// Times each chunk tries to place the feature, and the chance of each try succeeding
+    pub attempts: u32,
+    pub chance: f64,
+    /// Identifiers of the biomes the feature generates in, every biome if empty
+    pub biomes: Vec<String>,
+}
+
+/// The features which ship with the game: ore veins, boulders, ruins and wells.
+/// Trees are picked by the biomes instead, see `TreeFeature`.
+pub fn core_features(registry: &BlockRegistry) -> Vec<PlacedFeature> {
+    let mut features = Vec::new();
+    let stone = match registry.id("core:stone") {
+        Some(stone) => stone,
+        None => return features,
+    };
+    // Ore, vein size, veins per chunk and the heights they're found between
+    let ores = [("core:coal", 10, 6, (-128, 8)), ("core:iron_ore", 6, 4, (-128, 0))];
+    for (ore, size, attempts, (bottom, top)) in ores {
+        if let Some(ore) = registry.id(ore) {
+            features.push(PlacedFeature {
+                feature: Box::new(OreVein { ore, replaces: stone, size }),
+                placement: Placement::Between(bottom, top),
+                attempts,
+                chance: 1.0,
+                biomes: Vec::new(),
+            });
+        }
+    }
+    features.push(PlacedFeature {
+        feature: Box::new(Boulder { block: stone, radius: 1.5 }),
+        placement: Placement::Surface,
+        attempts: 1,
+        chance: 0.08,
+        biomes: vec![String::from("core:plains"), String::from("core:snowy_tundra")],
+    });
+    if let Some(floor) = registry.id("core:oak_log") {
+        features.push(PlacedFeature {
+            feature: Box::new(Ruin { walls: stone, floor }),
+            placement: Placement::Surface,
+            attempts: 1,
+            chance: 0.005,
+            biomes: vec![String::from("core:plains"), String::from("core:desert")],
+        });
+    }
+    // Registries without the blocks the well is built from go without it
+    if let Ok(well) = Template::from_json(WELL_TEMPLATE, registry).and_then(|well| TemplateFeature::new(Arc::new(well), Replace::Always)) {
+        features.push(PlacedFeature {
+            feature: Box::new(well),
+            placement: Placement::Surface,
+            attempts: 1,
+            chance: 0.004,
+            biomes: vec![String::from("core:plains"), String::from("core:forest")],
+        });
+    }
+    features
+}
+
+/// One of the tree shapes biomes grow
+pub struct TreeFeature {
+    kind: TreeKind,
+    log: usize,
+    leaves: usize,
+    cactus: usize,
+}
+
+impl TreeFeature {
+    /// Panics if a block the tree is made of isn't in the registry, like terrain generation does for its own blocks
+    pub fn new(kind: TreeKind, registry: &BlockRegistry) -> Self {
+        let id = |identifier: &str| registry.id(identifier)
+            .unwrap_or_else(|| panic!("Trees need {}, which isn't in the block registry", identifier));
+        Self {
+            kind,
+            log: id("core:oak_log"),
+            leaves: id("core:oak_leaves"),
+            cactus: id("core:cactus"),
+        }
+    }
+}
+
+impl Feature for TreeFeature {
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks) {
+        let mut block = |offset: Vector3<isize>, block_id: usize| blocks.push((origin + offset, block_id, Replace::NonSolid));
+        match self.kind {
+            TreeKind::Oak => {
+                for y in 0..5 {
+                    block(Vector3::new(0, y, 0), self.log);
+                }
+
+                for x in -1..=1 {
+                    for z in -1..=1 {
+                        for y in 3..=5 {
+                            if (x == -1 && z == -1 && y == 5) || (x == 1 && z == 1 && y == 5) || (x == -1 && z == 1 && y == 5) || (x == 1 && z == -1 && y == 5) || (x == 0 && z == 0 && y == 3) {
+                                continue;
+                            }
+                            block(Vector3::new(x, y, z), self.leaves);
+                        }
+                    }
+                }
+            }
+            TreeKind::TallOak => {
+                for y in 0..7 {
+                    block(Vector3::new(0, y, 0), self.log);
+                }
+                // Wide layers with their corners cut off, topped by a narrow one
+                for y in 4..=7 {
+                    let radius: isize = if y == 7 { 1 } else { 2 };
+                    for x in -radius..=radius {
+                        for z in -radius..=radius {
+                            let corner = x.abs() == radius && z.abs() == radius;
+                            let trunk = x == 0 && z == 0 && y < 7;
+                            if (corner && radius > 1) || trunk {
+                                continue;
+                            }
+                            block(Vector3::new(x, y, z), self.leaves);
+                        }
+                    }
+                }
+            }
+            TreeKind::Bush => {
+                block(Vector3::new(0, 0, 0), self.log);
+                for x in -1..=1 {
+                    for z in -1..=1 {
+                        for y in 0..=1 {
+                            if (x == 0 && z == 0 && y == 0) || (y == 1 && x != 0 && z != 0) {
+                                continue;
+                            }
+                            block(Vector3::new(x, y, z), self.leaves);
+                        }
+                    }
+                }
+            }
+            TreeKind::Cactus => {
+                for y in 0..rng.gen_range(1, 4) {
+                    block(Vector3::new(0, y, 0), self.cactus);
+                }
+            }
+        }
+    }
+}
+
+/// A blob of ore grown by walking from block to block, only replacing `replaces`
+pub struct OreVein {
+    pub ore: usize,
+    pub replaces: usize,
+    /// Steps of the walk, the vein has at most this many blocks
+    pub size: u32,
+}
+
+impl Feature for OreVein {
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks) {
+        let mut position = origin;
+        for _ in 0..self.size {
+            blocks.push((position, self.ore, Replace::Only(self.replaces)));
+            position += FACE_NEIGHBOURS[rng.gen_range(0, FACE_NEIGHBOURS.len())];
+        }
+    }
+}
+
+/// A lumpy ball of `block` half sunk into the ground
+pub struct Boulder {
+    pub block: usize,
+    pub radius: f64,
+}
+
+impl Feature for Boulder {
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks) {
+        let reach = self.radius.ceil() as isize;
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    let distance = ((x * x + y * y + z * z) as f64).sqrt();
+                    // Blocks near the edge are left out at random so it isn't a perfect sphere
+                    if distance <= self.radius - 0.5 || (distance <= self.radius + 0.5 && rng.gen_bool(0.5)) {
+                        blocks.push((origin + Vector3::new(x, y - 1, z), self.block, Replace::NonSolid));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Crumbling walls of a small hut, on a floor which levels out the ground beneath it
+pub struct Ruin {
+    pub walls: usize,
+    pub floor: usize,
+}
+
+impl Ruin {
+    const HALF_WIDTH: isize = 2;
+    const WALL_HEIGHT: isize = 3;
+}
+
+impl Feature for Ruin {
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks) {
+        let reach = Ruin::HALF_WIDTH;
+        // Doorway in the middle of a random wall
+        let door = match rng.gen_range(0, 4) {
+            0 => Vector3::new(reach, 0, 0),
+            1 => Vector3::new(-reach, 0, 0),
+            2 => Vector3::new(0, 0, reach),
+            _ => Vector3::new(0, 0, -reach),
+        };
+        for x in -reach..=reach {
+            for z in -reach..=reach {
+                blocks.push((origin + Vector3::new(x, -1, z), self.floor, Replace::Always));
+                let wall = x.abs() == reach || z.abs() == reach;
+                let corner = x.abs() == reach && z.abs() == reach;
+                if !wall || (x == door.x && z == door.z) {
+                    continue;
+                }
+                // Corners stand to full height, the rest has crumbled to a random height
+                let height = if corner { Ruin::WALL_HEIGHT } else { rng.gen_range(0, Ruin::WALL_HEIGHT + 1) };
+                for y in 0..height {
+                    blocks.push((origin + Vector3::new(x, y, z), self.walls, Replace::NonSolid));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use cgmath::Vector3;
+
+use super::{CHUNK_SIZE, FACE_NEIGHBOURS, World, block::{AIR, BlockRegistry, FluidProperties}};
+
+/// Furthest any fluid can flow sideways from a source, limited by the bits its level is kept in
+pub const MAX_SPREAD: u8 = 7;
+/// Metadata bit of fluid fed from above, which spreads like a source once it lands
+pub const FALLING: usize = 0x8;
+const LEVEL_MASK: usize = 0x7;
+
+/// Height of the surface of a source block, a little below the top so pools look filled
+const SOURCE_HEIGHT: f32 = 0.875;
+/// Indices into `FACE_NEIGHBOURS` of the four sides fluid spreads to
+const SIDES: [usize; 4] = [0, 1, 4, 5];
+const ABOVE: usize = 2;
+const BELOW: usize = 3;
+
+/// Blocks away from its source a fluid block is, 0 for sources and falling fluid
+pub fn level(metadata: usize) -> u8 {
+    (metadata & LEVEL_MASK) as u8
+}
+
+pub fn is_source(metadata: usize) -> bool {
+    metadata == 0
+}
+
+/// Height of the fluid's surface within its block. Fluid with more of the same fluid on top
+/// fills the whole block so columns don't show gaps.
+pub fn surface_height(fluid: &FluidProperties, metadata: usize, covered: bool) -> f32 {
+    if covered {
+        1.0
+    } else {
+        SOURCE_HEIGHT * (1.0 - level(metadata) as f32 / (fluid.spread as f32 + 1.0))
+    }
+}
+
+/// A fluid which hardens where it touches another fluid
+struct Reaction {
+    fluid: usize,
+    touching: usize,
+    /// What sources turn into, and what the other fluid turns into when this one flows into it
+    source_becomes: usize,
+    flowing_becomes: usize,
+}
+
+/// Where a fluid could flow to
+enum FlowTarget {
+    /// Air, or a block the fluid washes away
+    Open,
+    /// More of the same fluid, with its metadata
+    Same(usize),
+    /// A fluid it reacts with, which turns into the given block
+    Reacts(usize),
+    Blocked,
+}
+
+/// Fluid behaviour which isn't tied to a single kind of fluid
+pub(crate) struct FluidSimulation {
+    reactions: Vec<Reaction>,
+}
+
+impl FluidSimulation {
+    pub(crate) fn new(registry: &BlockRegistry) -> Self {
+        // Lava sources touching water harden into stone, and so does water which lava flows into
+        let mut reactions = Vec::new();
+        if let (Some(fluid), Some(touching), Some(stone)) = (registry.id("core:lava"), registry.id("core:water"), registry.id("core:stone")) {
+            reactions.push(Reaction { fluid, touching, source_becomes: stone, flowing_becomes: stone });
+        }
+
+        Self { reactions }
+    }
+}
+
+impl World {
+    /// Schedules the fluid in a chunk which was just loaded, and the fluid in its neighbours which may flow into it
+    pub(super) fn schedule_chunk_fluids(&mut self, chunk_index: &Vector3<isize>) {
+        let size = CHUNK_SIZE as isize;
+        let origin = chunk_index * size;
+        let registry = self.registry.clone();
+        let has_fluid = |chunk_index: &Vector3<isize>| self.chunks.get(chunk_index)
+            .is_some_and(|chunk| chunk.may_contain(|block_id| registry[block_id].fluid.is_some()));
+
+        let mut positions = Vec::new();
+        if has_fluid(chunk_index) {
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        positions.push(origin + Vector3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+            if !has_fluid(&(chunk_index + offset)) {
+                continue;
+            }
+            let axis = face / 2;
+            for u in 0..size {
+                for v in 0..size {
+                    let mut cell = [0; 3];
+                    cell[axis] = if offset[axis] > 0 { size } else { -1 };
+                    cell[(axis + 1) % 3] = u;
+                    cell[(axis + 2) % 3] = v;
+                    positions.push(origin + Vector3::from(cell));
+                }
+            }
+        }
+        for position in positions {
+            self.schedule_fluid(position);
+        }
+    }
+
+    /// Lets the fluid at `world_pos` flow once its tick interval has passed
+    pub(super) fn schedule_fluid(&mut self, world_pos: Vector3<isize>) {
+        if let Some(fluid) = self.registry[self.block_at_global_pos(world_pos)].fluid {
+            self.schedule_tick(world_pos, fluid.tick_interval);
+        }
+    }
+
+    /// Hardens the fluid at `world_pos` if it touches a fluid it reacts with, recomputes its level
+    /// if it's flowing, then lets it flow down or, if it can't, out to the sides
+    pub(super) fn update_fluid(&mut self, world_pos: Vector3<isize>) {
+        let block_id = self.block_at_global_pos(world_pos);
+        let fluid = match self.registry[block_id].fluid {
+            Some(fluid) => fluid,
+            None => return,
+        };
+        let mut metadata = self.metadata_at_global_pos(world_pos);
+
+        let hardened = self.fluids.reactions.iter()
+            .filter(|reaction| reaction.fluid == block_id)
+            .find(|reaction| FACE_NEIGHBOURS.iter().enumerate()
+                .any(|(face, offset)| face != BELOW && self.block_at_global_pos(world_pos + offset) == reaction.touching))
+            .map(|reaction| if is_source(metadata) { reaction.source_becomes } else { reaction.flowing_becomes });
+        if let Some(hardened) = hardened {
+            self.set_block_at_global_pos(world_pos, hardened, 0);
+            return;
+        }
+
+        if !is_source(metadata) {
+            match self.expected_flow(world_pos, block_id, &fluid) {
+                None => {
+                    self.set_block_at_global_pos(world_pos, AIR, 0);
+                    return;
+                }
+                Some(expected) if expected != metadata => {
+                    self.set_block_at_global_pos(world_pos, block_id, expected);
+                    metadata = expected;
+                }
+                Some(_) => {}
+            }
+        }
+
+        let below = world_pos + FACE_NEIGHBOURS[BELOW];
+        match self.flow_target(below, block_id) {
+            FlowTarget::Open => {
+                self.set_block_at_global_pos(below, block_id, FALLING);
+                return;
+            }
+            FlowTarget::Reacts(becomes) => {
+                self.set_block_at_global_pos(below, becomes, 0);
+                return;
+            }
+            FlowTarget::Same(below_metadata) if !is_source(below_metadata) => {
+                if below_metadata != FALLING {
+                    self.set_block_at_global_pos(below, block_id, FALLING);
+                }
+                return;
+            }
+            // Fluid resting on a source spreads over it like it would over the ground
+            FlowTarget::Same(_) | FlowTarget::Blocked => {}
+        }
+
+        let spread_level = level(metadata) + 1;
+        if spread_level > fluid.spread {
+            return;
+        }
+        for face in SIDES {
+            let side = world_pos + FACE_NEIGHBOURS[face];
+            match self.flow_target(side, block_id) {
+                FlowTarget::Open => {
+                    self.set_block_at_global_pos(side, block_id, spread_level as usize);
+                }
+                FlowTarget::Reacts(becomes) => {
+                    self.set_block_at_global_pos(side, becomes, 0);
+                }
+                FlowTarget::Same(side_metadata) if !is_source(side_metadata) && side_metadata & FALLING == 0 && level(side_metadata) > spread_level => {
+                    self.set_block_at_global_pos(side, block_id, spread_level as usize);
+                }
+                FlowTarget::Same(_) | FlowTarget::Blocked => {}
+            }
+        }
+    }
+
+    /// Metadata flowing fluid at `world_pos` should have given the fluid around it, `None` if nothing feeds it anymore
+    fn expected_flow(&self, world_pos: Vector3<isize>, block_id: usize, fluid: &FluidProperties) -> Option<usize> {
+        if self.block_at_global_pos(world_pos + FACE_NEIGHBOURS[ABOVE]) == block_id {
+            return Some(FALLING);
+        }
+        // Only fluid which can't flow down spreads sideways
+        let closest = SIDES.iter()
+            .map(|face| world_pos + FACE_NEIGHBOURS[*face])
+            .filter(|side| self.block_at_global_pos(*side) == block_id && !self.flows_down(*side, block_id))
+            .map(|side| level(self.metadata_at_global_pos(side)) + 1)
+            .min()?;
+        if closest <= fluid.spread {
+            Some(closest as usize)
+        } else {
+            None
+        }
+    }
+
+    fn flows_down(&self, world_pos: Vector3<isize>, block_id: usize) -> bool {
+        match self.flow_target(world_pos + FACE_NEIGHBOURS[BELOW], block_id) {
+            FlowTarget::Open | FlowTarget::Reacts(_) => true,
+            FlowTarget::Same(metadata) => !is_source(metadata),
+            FlowTarget::Blocked => false,
+        }
+    }
+
+    fn flow_target(&self, world_pos: Vector3<isize>, block_id: usize) -> FlowTarget {
+        if !self.is_chunk_loaded_at(world_pos) {
+            return FlowTarget::Blocked;
+        }
+        let target_id = self.block_at_global_pos(world_pos);
+        let target = &self.registry[target_id];
+        if target_id == block_id {
+            FlowTarget::Same(self.metadata_at_global_pos(world_pos))
+        } else if let Some(reaction) = self.fluids.reactions.iter().find(|reaction| reaction.fluid == block_id && reaction.touching == target_id) {
+            FlowTarget::Reacts(reaction.source_becomes)
+        } else if !target.solid && target.fluid.is_none() {
+            FlowTarget::Open
+        } else {
+            FlowTarget::Blocked
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Chunk;
+
+    /// World of a single chunk of air on a stone floor at y = 0, fluid can't leave it
+    fn floored_world() -> World {
+        let mut world = World::new(0);
+        let stone = world.registry.id("core:stone").unwrap();
+        let mut chunk = Chunk::uniform(AIR);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block_at_chunk_pos(&Vector3::new(x, 0, z), stone);
+            }
+        }
+        world.insert_chunk(Vector3::new(0, 0, 0), chunk);
+        world
+    }
+
+    fn tick_for(world: &mut World, ticks: usize) {
+        for _ in 0..ticks {
+            world.tick();
+        }
+    }
+
+    #[test]
+    fn levels_fall_off_from_a_source() {
+        let mut world = floored_world();
+        let water = world.registry.id("core:water").unwrap();
+        world.place_at_global_pos(Vector3::new(8, 1, 8), water);
+        tick_for(&mut world, 200);
+
+        assert!(is_source(world.metadata_at_global_pos(Vector3::new(8, 1, 8))));
+        for distance in 1..=MAX_SPREAD as isize {
+            let position = Vector3::new(8, 1, 8 + distance);
+            assert_eq!(world.block_at_global_pos(position), water);
+            assert_eq!(level(world.metadata_at_global_pos(position)), distance as u8);
+        }
+        assert_eq!(level(world.metadata_at_global_pos(Vector3::new(10, 1, 11))), 5);
+        assert_eq!(world.block_at_global_pos(Vector3::new(0, 1, 8)), AIR);
+        assert_eq!(world.block_at_global_pos(Vector3::new(8, 2, 8)), AIR);
+
+        // Without its source the flowing water drains away
+        world.destroy_at_global_pos(Vector3::new(8, 1, 8));
+        tick_for(&mut world, 200);
+        for x in 0..CHUNK_SIZE as isize {
+            for z in 0..CHUNK_SIZE as isize {
+                assert_eq!(world.block_at_global_pos(Vector3::new(x, 1, z)), AIR);
+            }
+        }
+    }
+
+    #[test]
+    fn fluid_falls_in_columns() {
+        let mut world = floored_world();
+        let water = world.registry.id("core:water").unwrap();
+        world.place_at_global_pos(Vector3::new(8, 12, 8), water);
+        tick_for(&mut world, 200);
+
+        for y in 1..12 {
+            assert_eq!(world.block_at_global_pos(Vector3::new(8, y, 8)), water);
+            assert_eq!(world.metadata_at_global_pos(Vector3::new(8, y, 8)), FALLING);
+        }
+        assert_eq!(world.block_at_global_pos(Vector3::new(9, 5, 8)), AIR);
+        // Once it lands it spreads like a source
+        assert_eq!(level(world.metadata_at_global_pos(Vector3::new(9, 1, 8))), 1);
+        assert_eq!(level(world.metadata_at_global_pos(Vector3::new(8, 1, 15))), 7);
+
+        let properties = world.registry[water].fluid.unwrap();
+        assert_eq!(surface_height(&properties, FALLING, true), 1.0);
+        assert!(surface_height(&properties, 0, false) > surface_height(&properties, 1, false));
+    }
+
+    #[test]
+    fn lava_meeting_water_turns_to_stone() {
+        let mut world = floored_world();
+        let water = world.registry.id("core:water").unwrap();
+        let lava = world.registry.id("core:lava").unwrap();
+        let stone = world.registry.id("core:stone").unwrap();
+        let interval = world.registry[lava].fluid.unwrap().tick_interval as usize;
+
+        // A lava source touching water hardens on its next tick
+        world.place_at_global_pos(Vector3::new(3, 1, 3), lava);
+        world.place_at_global_pos(Vector3::new(4, 1, 3), water);
+        tick_for(&mut world, interval - 1);
+        assert_eq!(world.block_at_global_pos(Vector3::new(3, 1, 3)), lava);
+        tick_for(&mut world, 1);
+        assert_eq!(world.block_at_global_pos(Vector3::new(3, 1, 3)), stone);
+
+        // Lava flowing into water turns the water into stone
+        let mut world = floored_world();
+        world.place_at_global_pos(Vector3::new(8, 1, 8), water);
+        world.place_at_global_pos(Vector3::new(8, 4, 8), lava);
+        tick_for(&mut world, 10 * interval);
+        assert_eq!(world.block_at_global_pos(Vector3::new(8, 1, 8)), stone);
+    }
+}
@@ -0,0 +1,474 @@
+use std::{collections::HashMap, sync::Arc};
+
+use cgmath::{Vector2, Vector3};
+use noise::{Perlin, Seedable};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use super::{Chunk, CHUNK_SIZE, World, biome::{self, Biome, TreeKind}, block::{AIR, BlockRegistry}, feature::{self, Feature, PlacedFeature, Placement, Replace, TreeFeature}, preset::GeneratorPreset};
+
+/// Independent random streams so that tweaking one generation step doesn't reshuffle the others
+const FOLIAGE_RNG_STREAM: u64 = 2;
+const FEATURE_RNG_STREAM: u64 = 3;
+
+/// Offsets between the areas of the noise each layer samples, so layers sharing the same
+/// Perlin noise don't line up with each other
+const WARP_X_OFFSET: [f64; 2] = [-7_331.0, 1_409.0];
+const WARP_Z_OFFSET: [f64; 2] = [2_843.0, -5_227.0];
+const MOUNTAIN_OFFSET: [f64; 2] = [9_173.0, 3_719.0];
+const MOUNTAIN_MASK_OFFSET: [f64; 2] = [-4_513.0, -8_641.0];
+const TEMPERATURE_OFFSET: [f64; 2] = [6_247.0, -2_953.0];
+const HUMIDITY_OFFSET: [f64; 2] = [-1_871.0, 7_723.0];
+/// How far above `mountain_threshold` the mask has to be for mountains to reach their full height
+const MOUNTAIN_BLEND: f64 = 0.2;
+
+/// Blocks placed by a chunk's generation in world coordinates, which may lie outside of the chunk itself,
+/// along with the blocks they may overwrite
+pub type SpilledBlocks = Vec<(Vector3<isize>, usize, Replace)>;
+
+/// Height of the surface and the biome of a column
+#[derive(Clone, Copy, Debug, Default)]
+struct Column {
+    height: f64,
+    biome: usize,
+}
+
+/// Ids of the blocks terrain generation places regardless of the biome, looked up once from the registry
+struct TerrainBlocks {
+    stone: usize,
+}
+
+impl TerrainBlocks {
+    fn resolve(registry: &BlockRegistry) -> Self {
+        let id = |identifier: &str| registry.id(identifier)
+            .unwrap_or_else(|| panic!("Terrain generation needs {}, which isn't in the block registry", identifier));
+        Self {
+            stone: id("core:stone"),
+        }
+    }
+}
+
+/// Generates chunks as a pure function of the seed and chunk coordinate.
+/// Holds no world state so it can be shared between worker threads.
+pub struct TerrainGenerator {
+    seed: u32,
+    noise_offset: Vector2<f64>,
+    perlin: Perlin,
+    preset: GeneratorPreset,
+    biomes: Vec<Biome>,
+    blocks: TerrainBlocks,
+    trees: HashMap<TreeKind, TreeFeature>,
+    features: Vec<PlacedFeature>,
+    registry: Arc<BlockRegistry>,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u32, registry: &Arc<BlockRegistry>) -> Self {
+        TerrainGenerator::with_preset(seed, registry, GeneratorPreset::default())
+    }
+
+    /// Generator shaped by `preset`, which places the core features
+    pub fn with_preset(seed: u32, registry: &Arc<BlockRegistry>, preset: GeneratorPreset) -> Self {
+        let mut seed_rng = StdRng::seed_from_u64(seed as u64);
+        let noise_offset = Vector2::new(
+            1_000_000.0 * seed_rng.gen::<f64>() + 3_141_592.0,
+            1_000_000.0 * seed_rng.gen::<f64>() + 3_141_592.0,
+        );
+        let perlin = Perlin::new().set_seed(seed);
+
+        Self {
+            seed,
+            noise_offset,
+            perlin,
+            biomes: preset.biomes.iter().enumerate().map(|(id, definition)| Biome::resolve(id, definition, registry)).collect(),
+            preset,
+            blocks: TerrainBlocks::resolve(registry),
+            trees: [TreeKind::Oak, TreeKind::TallOak, TreeKind::Bush, TreeKind::Cactus].iter()
+                .map(|kind| (*kind, TreeFeature::new(*kind, registry)))
+                .collect(),
+            features: feature::core_features(registry),
+            registry: registry.clone(),
+        }
+    }
+
+    /// Places `feature` in every chunk generated from now on, after the features added before it
+    pub fn add_feature(&mut self, feature: PlacedFeature) {
+        self.features.push(feature);
+    }
+
+    /// Stops placing every feature, including the core ones
+    pub fn clear_features(&mut self) {
+        self.features.clear();
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn registry(&self) -> &Arc<BlockRegistry> {
+        &self.registry
+    }
+
+    pub fn preset(&self) -> &GeneratorPreset {
+        &self.preset
+    }
+
+    /// Biome of the column at the given world coordinates
+    pub fn biome_at(&self, global_x: isize, global_z: isize) -> &Biome {
+        let point = [global_x as f64 + self.noise_offset.x, global_z as f64 + self.noise_offset.y];
+        let distances = self.climate_distances(self.warp(point));
+        &self.biomes[closest(&distances).0]
+    }
+
+    /// Generates the chunk at `chunk_index`. Foliage and features reaching into neighbouring chunks, such as
+    /// the top of a tree, are kept in the chunk's spilled blocks for the world to write once it's inserted.
+    pub fn generate_chunk(&self, chunk_index: &Vector3<isize>) -> Chunk {
+        let mut chunk = Chunk::uniform(0);
+        let mut foliage = Vec::new();
+
+        let columns = self.columns(chunk_index);
+        self.gen_terrain(chunk_index, &columns, &mut chunk);
+        self.gen_caves(chunk_index, &mut chunk);
+        // Placed only once all of it is known, so foliage can't stop other foliage from growing
+        self.gen_foliage(chunk_index, &columns, &chunk, &mut foliage);
+        self.gen_features(chunk_index, &columns, &chunk, &mut foliage);
+        for (world_pos, block_id, replace) in foliage {
+            let (foliage_chunk, block_index) = World::chunk_and_block_index(&world_pos);
+            let offset = foliage_chunk - chunk_index;
+            if offset == Vector3::new(0, 0, 0) {
+                if replace.allows(chunk.block_at_chunk_pos(&block_index), &self.registry) {
+                    chunk.set_block_at_chunk_pos(&block_index, block_id);
+                }
+            } else if offset.x.abs() <= 1 && offset.y.abs() <= 1 && offset.z.abs() <= 1 {
+                // Only neighbours take spilled blocks, features are kept within a chunk of where
+                // they're placed, see `TemplateFeature::new`
+                chunk.spilled.push((world_pos, block_id, replace));
+            }
+        }
+        // Caves may have carved away every block of a kind
+        chunk.compact();
+
+        chunk
+    }
+
+    /// Returns an RNG which only depends on the world seed, the chunk coordinate and `stream`,
+    /// so a chunk generates identically regardless of the order chunks are loaded in
+    fn chunk_rng(&self, chunk_index: &Vector3<isize>, stream: u64) -> StdRng {
+        // SplitMix64 finalizer, mixes each coordinate into the hash
+        fn mix(mut z: u64) -> u64 {
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+        let mut hash = mix(self.seed as u64 ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        for coord in &[chunk_index.x, chunk_index.y, chunk_index.z] {
+            hash = mix(hash ^ *coord as u64);
+        }
+        StdRng::seed_from_u64(hash)
+    }
+
+    fn gen_terrain(&self, chunk_index: &Vector3<isize>, columns: &[[Column; CHUNK_SIZE]; CHUNK_SIZE], chunk: &mut Chunk) {
+        for (block_x, row) in columns.iter().enumerate() {
+            for (block_z, column) in row.iter().enumerate() {
+                for block_y in 0..CHUNK_SIZE {
+                    let global_y = block_y as isize + (chunk_index.y * CHUNK_SIZE as isize);
+                    let block_id = self.terrain_block(column, global_y);
+                    if block_id != AIR {
+                        chunk.set_block_at_chunk_pos(&Vector3::new(block_x, block_y, block_z), block_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Block the terrain step places at `global_y` in `column`, before caves are carved out of it
+    fn terrain_block(&self, column: &Column, global_y: isize) -> usize {
+        let biome = &self.biomes[column.biome];
+        let surface_y = column.height;
+        if (global_y as f64) >= surface_y {
+            AIR
+        } else if global_y == surface_y.floor() as isize {
+            biome.surface
+        } else if (global_y as f64) < (self.preset.stone_depth * surface_y).floor() {
+            self.blocks.stone
+        } else {
+            biome.subsurface
+        }
+    }
+
+    /// Terrain of the column at the given world coordinates downsampled into `cells` cells of `scale` blocks,
+    /// stacked upwards from `min_y`. A cell is filled if at least half of its blocks are, with the biome's
+    /// surface block if the surface is inside it so the ground keeps its colour from afar, and otherwise
+    /// with whichever block fills most of it. Caves, foliage and features are left out.
+    pub fn lod_column(&self, global_x: isize, global_z: isize, min_y: isize, scale: usize, cells: usize) -> Vec<usize> {
+        let column = self.column(global_x as f64, global_z as f64);
+        let surface_y = column.height.floor() as isize;
+        let mut counts: Vec<(usize, usize)> = Vec::new();
+        (0..cells).map(|cell| {
+            let bottom = min_y + (cell * scale) as isize;
+            counts.clear();
+            for global_y in bottom..bottom + scale as isize {
+                let block_id = self.terrain_block(&column, global_y);
+                if block_id == AIR {
+                    continue;
+                }
+                match counts.iter_mut().find(|(counted, _)| *counted == block_id) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((block_id, 1)),
+                }
+            }
+            let filled: usize = counts.iter().map(|(_, count)| count).sum();
+            if filled * 2 < scale {
+                AIR
+            } else if (bottom..bottom + scale as isize).contains(&surface_y) {
+                self.biomes[column.biome].surface
+            } else {
+                counts.iter().max_by_key(|(_, count)| *count).map_or(AIR, |(block_id, _)| *block_id)
+            }
+        }).collect()
+    }
+
+    fn gen_foliage(&self, chunk_index: &Vector3<isize>, columns: &[[Column; CHUNK_SIZE]; CHUNK_SIZE], chunk: &Chunk, spilled: &mut SpilledBlocks) {
+        let mut rng = self.chunk_rng(chunk_index, FOLIAGE_RNG_STREAM);
+        for (block_x, row) in columns.iter().enumerate() {
+            for (block_z, column) in row.iter().enumerate() {
+                let biome = &self.biomes[column.biome];
+                let global_x = block_x as isize + (chunk_index.x * CHUNK_SIZE as isize);
+                let global_y = column.height.floor() as isize;
+                let global_z = block_z as isize + (chunk_index.z * CHUNK_SIZE as isize);
+                let (surface_chunk, surface_index) = World::chunk_and_block_index(&Vector3::new(global_x, global_y, global_z));
+                // Only the chunk holding the surface grows foliage on it, and not where a cave opened it up
+                if surface_chunk != *chunk_index || chunk.block_at_chunk_pos(&surface_index) != biome.surface {
+                    continue;
+                }
+
+                let above = Vector3::new(global_x, global_y + 1, global_z);
+                let roll: f64 = rng.gen();
+                if roll < biome.tree_chance {
+                    if let Some(kind) = biome::pick_weighted(&biome.trees, &mut rng) {
+                        self.trees[&kind].place(above, &mut rng, spilled);
+                    }
+                } else if roll < biome.tree_chance + biome.ground_cover_chance {
+                    if let Some(block_id) = biome::pick_weighted(&biome.ground_cover, &mut rng) {
+                        spilled.push((above, block_id, Replace::NonSolid));
+                    }
+                }
+            }
+        }
+    }
+
+    fn gen_caves(&self, chunk_index: &Vector3<isize>, chunk: &mut Chunk) {
+        for block_x in 0..CHUNK_SIZE {
+            for block_y in 0..CHUNK_SIZE {
+                for block_z in 0..CHUNK_SIZE {
+                    let global_x = (block_x as isize + (chunk_index.x * CHUNK_SIZE as isize)) as f64;
+                    let global_y = (block_y as isize + (chunk_index.y * CHUNK_SIZE as isize)) as f64;
+                    let global_z = (block_z as isize + (chunk_index.z * CHUNK_SIZE as isize)) as f64;
+                    let noise = self.preset.caves.sample(&self.perlin, [global_x, global_y, global_z]);
+                    if noise > self.preset.cave_cutoff {
+                        chunk.set_block_at_chunk_pos(&Vector3::new(block_x, block_y, block_z), 0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tries each feature as often as it asks for, at random spots of the chunk which match its placement
+    fn gen_features(&self, chunk_index: &Vector3<isize>, columns: &[[Column; CHUNK_SIZE]; CHUNK_SIZE], chunk: &Chunk, spilled: &mut SpilledBlocks) {
+        let mut rng = self.chunk_rng(chunk_index, FEATURE_RNG_STREAM);
+        let origin = chunk_index * CHUNK_SIZE as isize;
+        for placed in &self.features {
+            for _ in 0..placed.attempts {
+                // Every try rolls the same numbers whether or not it succeeds, so adding a feature
+                // doesn't move the ones after it around in chunks where it never generates
+                let roll: f64 = rng.gen();
+                let block_index = Vector3::new(rng.gen_range(0, CHUNK_SIZE), rng.gen_range(0, CHUNK_SIZE), rng.gen_range(0, CHUNK_SIZE));
+                let mut feature_rng = StdRng::seed_from_u64(rng.gen());
+                if roll >= placed.chance {
+                    continue;
+                }
+
+                let column = &columns[block_index.x][block_index.z];
+                let biome = &self.biomes[column.biome];
+                if !placed.biomes.is_empty() && !placed.biomes.contains(&biome.identifier) {
+                    continue;
+                }
+                let position = match placed.placement {
+                    Placement::Surface => {
+                        let surface = Vector3::new(origin.x + block_index.x as isize, column.height.floor() as isize, origin.z + block_index.z as isize);
+                        let (surface_chunk, surface_index) = World::chunk_and_block_index(&surface);
+                        if surface_chunk != *chunk_index || chunk.block_at_chunk_pos(&surface_index) != biome.surface {
+                            continue;
+                        }
+                        surface + Vector3::new(0, 1, 0)
+                    }
+                    Placement::Between(bottom, top) => {
+                        let position = origin + block_index.cast::<isize>().unwrap();
+                        if position.y < bottom || position.y >= top {
+                            continue;
+                        }
+                        position
+                    }
+                };
+                placed.feature.place(position, &mut feature_rng, spilled);
+            }
+        }
+    }
+
+    /// Surface height and biome of every column of a chunk
+    fn columns(&self, chunk_index: &Vector3<isize>) -> [[Column; CHUNK_SIZE]; CHUNK_SIZE] {
+        let mut columns = [[Column::default(); CHUNK_SIZE]; CHUNK_SIZE];
+        for (block_x, row) in columns.iter_mut().enumerate() {
+            for (block_z, column) in row.iter_mut().enumerate() {
+                let global_x = block_x as isize + (chunk_index.x * CHUNK_SIZE as isize);
+                let global_z = block_z as isize + (chunk_index.z * CHUNK_SIZE as isize);
+                *column = self.column(global_x as f64, global_z as f64);
+            }
+        }
+        columns
+    }
+
+    /// Hills everywhere, plus ridged mountains where the mountain mask is high enough, both scaled
+    /// by the biomes close to the column's climate. Everything is sampled at a domain warped point
+    /// so the outlines of hills, mountains and biomes wander.
+    fn column(&self, global_x: f64, global_z: f64) -> Column {
+        let preset = &self.preset;
+        let point = [global_x + self.noise_offset.x, global_z + self.noise_offset.y];
+        let warped = self.warp(point);
+
+        // Every biome within `biome_blend` of the closest one gets a say in the height, fading
+        // out linearly, so the height is continuous where the closest biome changes
+        let distances = self.climate_distances(warped);
+        let (biome, closest) = closest(&distances);
+        let (mut height_offset, mut hill_scale, mut mountain_scale, mut total_weight) = (0.0, 0.0, 0.0, 0.0);
+        for (other, distance) in self.biomes.iter().zip(&distances) {
+            let weight = (1.0 - (distance - closest) / preset.biome_blend).max(0.0);
+            height_offset += weight * other.height_offset;
+            hill_scale += weight * other.hill_scale;
+            mountain_scale += weight * other.mountain_scale;
+            total_weight += weight;
+        }
+
+        let hills = preset.hill_height * preset.hills.sample(&self.perlin, warped);
+
+        let mask = preset.mountain_mask.sample(&self.perlin, offset(point, MOUNTAIN_MASK_OFFSET));
+        let rise = ((mask - preset.mountain_threshold) / MOUNTAIN_BLEND).clamp(0.0, 1.0);
+        let mountains = if rise > 0.0 {
+            let ridges = preset.mountains.sample_ridged(&self.perlin, offset(warped, MOUNTAIN_OFFSET));
+            // Smoothstep so the foothills blend into the hills
+            preset.mountain_height * ridges * rise * rise * (3.0 - 2.0 * rise)
+        } else {
+            0.0
+        };
+
+        Column {
+            height: preset.base_height + (height_offset + hill_scale * hills + mountain_scale * mountains) / total_weight,
+            biome,
+        }
+    }
+
+    fn warp(&self, point: [f64; 2]) -> [f64; 2] {
+        let preset = &self.preset;
+        [
+            point[0] + preset.warp_strength * preset.warp.sample(&self.perlin, offset(point, WARP_X_OFFSET)),
+            point[1] + preset.warp_strength * preset.warp.sample(&self.perlin, offset(point, WARP_Z_OFFSET)),
+        ]
+    }
+
+    /// Distance from the climate at a warped point to the climate of each biome
+    fn climate_distances(&self, warped: [f64; 2]) -> Vec<f64> {
+        let temperature = self.preset.temperature.sample(&self.perlin, offset(warped, TEMPERATURE_OFFSET));
+        let humidity = self.preset.humidity.sample(&self.perlin, offset(warped, HUMIDITY_OFFSET));
+        self.biomes.iter().map(|biome| biome.climate_distance(temperature, humidity)).collect()
+    }
+}
+
+fn offset(point: [f64; 2], offset: [f64; 2]) -> [f64; 2] {
+    [point[0] + offset[0], point[1] + offset[1]]
+}
+
+/// Index and distance of the closest biome
+fn closest(distances: &[f64]) -> (usize, f64) {
+    distances.iter().copied().enumerate()
+        .fold((0, f64::MAX), |closest, (i, distance)| if distance < closest.1 { (i, distance) } else { closest })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn blocks(chunk: &Chunk) -> Vec<usize> {
+        let mut blocks = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    blocks.push(chunk.block_at_chunk_pos(&Vector3::new(x, y, z)));
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Number of blocks of each kind in a chunk, by identifier
+    fn block_counts(chunk: &Chunk, registry: &BlockRegistry) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for block_id in blocks(chunk) {
+            *counts.entry(registry[block_id].identifier.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn chunks_only_depend_on_seed_and_position() {
+        let registry = BlockRegistry::core();
+        let mut chunk_indices = Vec::new();
+        for x in -1..=1 {
+            for y in -2..=1 {
+                for z in -1..=1 {
+                    chunk_indices.push(Vector3::new(x, y, z));
+                }
+            }
+        }
+
+        let forwards = TerrainGenerator::new(1234, &registry);
+        let generated: Vec<Chunk> = chunk_indices.iter().map(|chunk_index| forwards.generate_chunk(chunk_index)).collect();
+        let backwards = TerrainGenerator::new(1234, &registry);
+        for (chunk_index, expected) in chunk_indices.iter().zip(&generated).rev() {
+            let chunk = backwards.generate_chunk(chunk_index);
+            assert_eq!(blocks(&chunk), blocks(expected));
+            assert_eq!(chunk.spilled, expected.spilled);
+        }
+
+        // A world loaded from somewhere else first ends up with the same chunks once the blocks
+        // spilled into them are written, here the chunk at the origin which is surrounded in both
+        let mut direct = World::new(1234);
+        direct.load_chunks_around(&Vector3::new(8.0, 8.0, 8.0), 1, usize::MAX).unwrap();
+        let mut detour = World::new(1234);
+        detour.load_chunks_around(&Vector3::new(40.0, -8.0, 8.0), 1, usize::MAX).unwrap();
+        detour.load_chunks_around(&Vector3::new(8.0, 8.0, 8.0), 1, usize::MAX).unwrap();
+        let origin = Vector3::new(0, 0, 0);
+        assert_eq!(blocks(&detour.chunks[&origin]), blocks(&direct.chunks[&origin]));
+    }
+
+    #[test]
+    fn fixed_seed_generates_known_terrain() {
+        let registry = BlockRegistry::core();
+        let generator = TerrainGenerator::new(1234, &registry);
+        let counts = |pairs: &[(&str, usize)]| pairs.iter().map(|(identifier, count)| (identifier.to_string(), *count)).collect::<BTreeMap<_, _>>();
+
+        assert_eq!(generator.biome_at(0, 0).identifier, "core:desert");
+        let surface = generator.generate_chunk(&Vector3::new(0, 0, 0));
+        assert_eq!(registry[surface.block_at_chunk_pos(&Vector3::new(0, 9, 0))].identifier, "core:sand");
+        assert_eq!(surface.block_at_chunk_pos(&Vector3::new(0, 10, 0)), AIR);
+        assert_eq!(block_counts(&surface, &registry), counts(&[
+            ("core:air", 1655), ("core:cactus", 1), ("core:coal", 12), ("core:dead_bush", 2), ("core:sand", 534), ("core:stone", 1892),
+        ]));
+        assert_eq!(surface.spilled.len(), 5);
+
+        let underground = generator.generate_chunk(&Vector3::new(0, -1, 0));
+        assert_eq!(block_counts(&underground, &registry), counts(&[
+            ("core:air", 132), ("core:coal", 46), ("core:iron_ore", 20), ("core:stone", 3898),
+        ]));
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+
+use cgmath::Vector3;
+
+use super::{World, edit::Bounds};
+
+/// Memory the edit history keeps by default before it forgets the oldest edits, in bytes
+pub const DEFAULT_HISTORY_BUDGET: usize = 16 * 1024 * 1024;
+
+/// One block changed by an edit, with its block and metadata before and after
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BlockChange {
+    world_pos: Vector3<isize>,
+    before: (usize, usize),
+    after: (usize, usize),
+}
+
+/// Block changes which are undone and redone together
+type Transaction = Vec<BlockChange>;
+
+/// Journal of the blocks changed by edits, so they can be undone and redone.
+///
+/// Only edits made through the public editing methods are recorded, along with whatever they
+/// change right away, like a plant which drops when the block under it is removed. Changes the
+/// simulation makes on later ticks, such as flowing water, aren't recorded, even while a
+/// transaction is open.
+pub(crate) struct EditHistory {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    /// Transaction being recorded, and how many transactions are nested inside each other
+    open: Transaction,
+    depth: usize,
+    /// Set while the world ticks, nothing is recorded then
+    suspended: bool,
+    /// Bytes of block changes the undo and redo stacks can hold, and how many they hold now
+    budget: usize,
+    used: usize,
+}
+
+impl EditHistory {
+    pub(crate) fn new(budget: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            open: Vec::new(),
+            depth: 0,
+            suspended: false,
+            budget,
+            used: 0,
+        }
+    }
+
+    fn begin(&mut self) {
+        self.depth += 1;
+    }
+
+    /// Closes the innermost transaction, the outermost one is pushed onto the undo stack
+    fn commit(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth == 0 && !self.open.is_empty() {
+            let transaction = std::mem::take(&mut self.open);
+            self.used -= self.redo.drain(..).map(|transaction| EditHistory::cost(&transaction)).sum::<usize>();
+            self.used += EditHistory::cost(&transaction);
+            self.undo.push_back(transaction);
+            self.trim();
+        }
+    }
+
+    /// Stops or resumes recording changes into the open transaction
+    pub(super) fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    fn record(&mut self, change: BlockChange) {
+        if self.depth > 0 && !self.suspended {
+            self.open.push(change);
+        }
+    }
+
+    /// Forgets the oldest transactions until the stacks fit in the budget. A transaction which
+    /// is bigger than the whole budget can't be undone at all.
+    fn trim(&mut self) {
+        while self.used > self.budget {
+            let oldest = match self.undo.pop_front() {
+                Some(transaction) => transaction,
+                None => match self.redo.pop() {
+                    Some(transaction) => transaction,
+                    None => break,
+                },
+            };
+            self.used -= EditHistory::cost(&oldest);
+        }
+    }
+
+    fn cost(transaction: &Transaction) -> usize {
+        transaction.len() * std::mem::size_of::<BlockChange>()
+    }
+}
+
+impl World {
+    /// Groups every edit until the matching `commit_transaction` into one step of the history,
+    /// so a single `undo` reverts all of them. Transactions can be nested, only the outermost
+    /// one counts.
+    pub fn begin_transaction(&mut self) {
+        self.history.begin();
+    }
+
+    pub fn commit_transaction(&mut self) {
+        self.history.commit();
+    }
+
+    /// Reverts the most recent transaction, returning the bounds of the blocks which changed back.
+    /// Blocks in chunks which have been unloaded since are left as they are. Does nothing while a
+    /// transaction is open.
+    pub fn undo(&mut self) -> Option<Bounds> {
+        if self.history.depth > 0 {
+            return None;
+        }
+        let transaction = self.history.undo.pop_back()?;
+        let edits = transaction.iter().rev().map(|change| (change.world_pos, change.before.0, change.before.1)).collect();
+        let bounds = self.apply_edits(edits);
+        self.history.redo.push(transaction);
+        bounds
+    }
+
+    /// Makes the most recently undone transaction again, as long as nothing was edited since
+    pub fn redo(&mut self) -> Option<Bounds> {
+        if self.history.depth > 0 {
+            return None;
+        }
+        let transaction = self.history.redo.pop()?;
+        let edits = transaction.iter().map(|change| (change.world_pos, change.after.0, change.after.1)).collect();
+        let bounds = self.apply_edits(edits);
+        self.history.undo.push_back(transaction);
+        bounds
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Sets how many bytes of block changes the history keeps, forgetting the oldest edits if it holds more
+    pub fn set_history_budget(&mut self, budget: usize) {
+        self.history.budget = budget;
+        self.history.trim();
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.undo.clear();
+        self.history.redo.clear();
+        self.history.used = 0;
+    }
+
+    /// Runs `edit` as its own transaction, unless it's part of one which is already open
+    pub(super) fn recorded<R, F: FnOnce(&mut World) -> R>(&mut self, edit: F) -> R {
+        self.history.begin();
+        let result = edit(self);
+        self.history.commit();
+        result
+    }
+
+    /// Notes that the block at `world_pos` changed, if a transaction is being recorded
+    pub(super) fn record_change(&mut self, world_pos: Vector3<isize>, before: (usize, usize), after: (usize, usize)) {
+        if before != after {
+            self.history.record(BlockChange { world_pos, before, after });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::block::AIR;
+
+    /// Every block in `bounds`
+    fn snapshot(world: &World, bounds: Bounds) -> Vec<usize> {
+        bounds.positions().map(|world_pos| world.block_at_global_pos(world_pos)).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        let glass = world.registry().id("core:glass").unwrap();
+        let area = Bounds::new(Vector3::new(-8, -8, -8), Vector3::new(8, 8, 8));
+        let box_bounds = Bounds::new(Vector3::new(-3, -3, -3), Vector3::new(3, 3, 3));
+        let original = snapshot(&world, area);
+        assert!(!world.can_undo());
+
+        world.fill(box_bounds, stone);
+        let filled = snapshot(&world, area);
+        // Everything in a transaction is undone in one step
+        world.begin_transaction();
+        world.place_at_global_pos(Vector3::new(0, 5, 0), glass);
+        world.fill(Bounds::new(Vector3::new(0, 6, 0), Vector3::new(0, 7, 0)), glass);
+        world.destroy_at_global_pos(Vector3::new(0, 3, 0));
+        // Nothing is undone while the transaction is still open
+        assert!(world.undo().is_none());
+        world.commit_transaction();
+        let edited = snapshot(&world, area);
+
+        assert_eq!(world.undo(), Some(Bounds::new(Vector3::new(0, 3, 0), Vector3::new(0, 7, 0))));
+        assert_eq!(snapshot(&world, area), filled);
+        assert_eq!(world.undo(), Some(box_bounds));
+        assert_eq!(snapshot(&world, area), original);
+        assert!(world.undo().is_none());
+
+        world.redo();
+        world.redo();
+        assert_eq!(snapshot(&world, area), edited);
+        assert!(!world.can_redo());
+
+        // A new edit forgets what was undone
+        world.undo();
+        world.destroy_at_global_pos(Vector3::new(3, 3, 3));
+        assert!(!world.can_redo());
+        assert!(world.redo().is_none());
+    }
+
+    #[test]
+    fn budget_forgets_the_oldest_edits() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        world.set_history_budget(10 * std::mem::size_of::<BlockChange>());
+        world.fill(Bounds::new(Vector3::new(0, 0, 0), Vector3::new(5, 0, 0)), stone);
+        world.fill(Bounds::new(Vector3::new(0, 1, 0), Vector3::new(5, 1, 0)), stone);
+        assert_eq!(world.history.undo.len(), 1);
+        assert_eq!(world.undo(), Some(Bounds::new(Vector3::new(0, 1, 0), Vector3::new(5, 1, 0))));
+        assert!(!world.can_undo());
+
+        // An edit bigger than the whole budget can't be undone
+        world.fill(Bounds::new(Vector3::new(0, 2, 0), Vector3::new(10, 2, 0)), stone);
+        assert!(!world.can_undo());
+    }
+
+    #[test]
+    fn ticks_are_not_recorded() {
+        let mut world = World::empty(1);
+        let water = world.registry().id("core:water").unwrap();
+        let source = Vector3::new(0, 5, 0);
+        world.begin_transaction();
+        world.place_at_global_pos(source, water);
+        for _ in 0..20 {
+            world.tick();
+        }
+        world.commit_transaction();
+        // The water has flowed, but only placing its source was recorded
+        assert_eq!(world.block_at_global_pos(source - Vector3::new(0, 1, 0)), water);
+        assert_eq!(world.history.undo.back().map(|transaction| transaction.len()), Some(1));
+        world.undo();
+        assert_eq!(world.block_at_global_pos(source), AIR);
+    }
+}
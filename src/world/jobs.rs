@@ -0,0 +1,194 @@
+use std::{collections::HashMap, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, Sender}}, thread::{self, JoinHandle}};
+
+use cgmath::Vector3;
+
+use super::{Chunk, generator::TerrainGenerator, lod::{self, LodTile}, mesher::{ChunkNeighbourhood, LayeredMesh, MeshingMode}, visibility::ChunkVisibility};
+
+pub(crate) enum Job {
+    Generate(Vector3<isize>),
+    Mesh(Vector3<isize>, Box<ChunkNeighbourhood>),
+    /// Generates the cells of an LOD tile and meshes them, which the world never stores
+    MeshLod(LodTile, MeshingMode),
+}
+
+pub(crate) enum JobOutput {
+    Generated(Vector3<isize>, Box<Chunk>),
+    Meshed(Vector3<isize>, LayeredMesh, ChunkVisibility),
+    LodMeshed(LodTile, LayeredMesh),
+}
+
+impl Job {
+    fn run(self, generator: &TerrainGenerator) -> JobOutput {
+        match self {
+            Job::Generate(chunk_index) => JobOutput::Generated(chunk_index, Box::new(generator.generate_chunk(&chunk_index))),
+            Job::Mesh(chunk_index, neighbourhood) => {
+                let visibility = ChunkVisibility::new(&neighbourhood.center, &neighbourhood.registry);
+                JobOutput::Meshed(chunk_index, neighbourhood.gen_mesh(), visibility)
+            }
+            Job::MeshLod(tile, meshing_mode) => JobOutput::LodMeshed(tile, lod::tile_neighbourhood(generator, &tile, meshing_mode).gen_mesh()),
+        }
+    }
+}
+
+/// A job together with the flag used to cancel it once it's stale
+struct Ticket {
+    job: Job,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Runs chunk generation and meshing on a pool of worker threads.
+/// With zero workers jobs run inline when submitted, which keeps headless use deterministic.
+pub(crate) struct JobQueue {
+    job_sender: Option<Sender<Ticket>>,
+    result_sender: Sender<(JobOutput, Arc<AtomicBool>)>,
+    result_receiver: Receiver<(JobOutput, Arc<AtomicBool>)>,
+    workers: Vec<JoinHandle<()>>,
+    generator: Arc<TerrainGenerator>,
+
+    pending_generation: HashMap<Vector3<isize>, Arc<AtomicBool>>,
+    pending_meshes: HashMap<Vector3<isize>, Arc<AtomicBool>>,
+    pending_lod_meshes: HashMap<LodTile, Arc<AtomicBool>>,
+}
+
+impl JobQueue {
+    pub(crate) fn new(generator: Arc<TerrainGenerator>, worker_threads: usize) -> Self {
+        let (result_sender, result_receiver) = mpsc::channel();
+        let mut job_sender = None;
+        let mut workers = Vec::new();
+
+        if worker_threads > 0 {
+            let (sender, receiver) = mpsc::channel::<Ticket>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..worker_threads {
+                let receiver = receiver.clone();
+                let results = result_sender.clone();
+                let generator = generator.clone();
+                workers.push(thread::spawn(move || loop {
+                    // Only hold the lock while waiting, so other workers can pick up jobs in the meantime
+                    let ticket = match receiver.lock().unwrap().recv() {
+                        Ok(ticket) => ticket,
+                        Err(_) => break,
+                    };
+                    if ticket.cancelled.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let output = ticket.job.run(&generator);
+                    if results.send((output, ticket.cancelled)).is_err() {
+                        break;
+                    }
+                }));
+            }
+            job_sender = Some(sender);
+        }
+
+        Self {
+            job_sender,
+            result_sender,
+            result_receiver,
+            workers,
+            generator,
+            pending_generation: HashMap::new(),
+            pending_meshes: HashMap::new(),
+            pending_lod_meshes: HashMap::new(),
+        }
+    }
+
+    fn submit(&mut self, job: Job) -> Arc<AtomicBool> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        match &self.job_sender {
+            Some(sender) => {
+                let _ = sender.send(Ticket { job, cancelled: cancelled.clone() });
+            }
+            None => {
+                let output = job.run(&self.generator);
+                let _ = self.result_sender.send((output, cancelled.clone()));
+            }
+        }
+        cancelled
+    }
+
+    pub(crate) fn submit_generation(&mut self, chunk_index: Vector3<isize>) {
+        if self.pending_generation.contains_key(&chunk_index) {
+            return;
+        }
+        let cancelled = self.submit(Job::Generate(chunk_index));
+        self.pending_generation.insert(chunk_index, cancelled);
+    }
+
+    /// Queues a remesh of the chunk, superseding any mesh job still pending for it
+    pub(crate) fn submit_mesh(&mut self, chunk_index: Vector3<isize>, neighbourhood: ChunkNeighbourhood) {
+        self.cancel_mesh(&chunk_index);
+        let cancelled = self.submit(Job::Mesh(chunk_index, Box::new(neighbourhood)));
+        self.pending_meshes.insert(chunk_index, cancelled);
+    }
+
+    pub(crate) fn submit_lod_mesh(&mut self, tile: LodTile, meshing_mode: MeshingMode) {
+        if self.pending_lod_meshes.contains_key(&tile) {
+            return;
+        }
+        let cancelled = self.submit(Job::MeshLod(tile, meshing_mode));
+        self.pending_lod_meshes.insert(tile, cancelled);
+    }
+
+    pub(crate) fn is_generating(&self, chunk_index: &Vector3<isize>) -> bool {
+        self.pending_generation.contains_key(chunk_index)
+    }
+
+    pub(crate) fn cancel_mesh(&mut self, chunk_index: &Vector3<isize>) {
+        if let Some(cancelled) = self.pending_meshes.remove(chunk_index) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Cancels the pending generation of every chunk for which `keep` returns false
+    pub(crate) fn retain_generation<F: Fn(&Vector3<isize>) -> bool>(&mut self, keep: F) {
+        self.pending_generation.retain(|chunk_index, cancelled| {
+            if keep(chunk_index) {
+                return true;
+            }
+            cancelled.store(true, Ordering::Relaxed);
+            false
+        });
+    }
+
+    /// Cancels the pending mesh of every LOD tile for which `keep` returns false
+    pub(crate) fn retain_lod_meshes<F: Fn(&LodTile) -> bool>(&mut self, keep: F) {
+        self.pending_lod_meshes.retain(|tile, cancelled| {
+            if keep(tile) {
+                return true;
+            }
+            cancelled.store(true, Ordering::Relaxed);
+            false
+        });
+    }
+
+    /// Returns the output of every job which finished since the last call and is still wanted
+    pub(crate) fn finished(&mut self) -> Vec<JobOutput> {
+        let mut outputs = Vec::new();
+        while let Ok((output, cancelled)) = self.result_receiver.try_recv() {
+            if cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+            match &output {
+                JobOutput::Generated(chunk_index, _) => self.pending_generation.remove(chunk_index),
+                JobOutput::Meshed(chunk_index, ..) => self.pending_meshes.remove(chunk_index),
+                JobOutput::LodMeshed(tile, _) => self.pending_lod_meshes.remove(tile),
+            };
+            outputs.push(output);
+        }
+        outputs
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        for cancelled in self.pending_generation.values().chain(self.pending_meshes.values()).chain(self.pending_lod_meshes.values()) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+        // Closing the channel makes every worker's `recv` fail once the queue is drained
+        self.job_sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
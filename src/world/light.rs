@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use cgmath::Vector3;
+
+use super::{Chunk, CHUNK_SIZE, FACE_NEIGHBOURS, World, block::BlockRegistry};
+
+/// Brightest light level, which is what the sky gives wherever it can be seen
+pub const MAX_LIGHT: u8 = 15;
+
+/// Index into `FACE_NEIGHBOURS` of the block below
+const DOWN: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light from the sky, which travels straight down without dimming
+    Sky,
+    /// Light given off by blocks
+    Block,
+}
+
+/// Flood fills light through the loaded chunks, dimming by one level per block travelled.
+///
+/// Light only passes through transparent blocks. A column of chunks is lit from the top as if
+/// there was open sky above the highest loaded chunk, which is corrected when the chunk above
+/// is loaded. Removing light clears everything the old level could have reached and fills it
+/// in again from whatever light is left around the edges.
+pub(crate) struct Lighting<'a> {
+    chunks: &'a mut HashMap<Vector3<isize>, Chunk>,
+    registry: &'a BlockRegistry,
+    /// Loaded chunks whose mesh needs rebuilding because light on or next to them changed
+    pub(crate) changed: HashSet<Vector3<isize>>,
+}
+
+impl<'a> Lighting<'a> {
+    pub(crate) fn new(chunks: &'a mut HashMap<Vector3<isize>, Chunk>, registry: &'a BlockRegistry) -> Self {
+        Self {
+            chunks,
+            registry,
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Lights a chunk which was just inserted, letting light from its neighbours flow in
+    /// and its own light flow out
+    pub(crate) fn light_chunk(&mut self, chunk_index: &Vector3<isize>) {
+        let size = CHUNK_SIZE as isize;
+        let origin = chunk_index * size;
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+
+        // The sky reaches a column if nothing is loaded above or it reaches the bottom of the chunk above
+        let mut sky_above = [[false; CHUNK_SIZE]; CHUNK_SIZE];
+        for (x, row) in sky_above.iter_mut().enumerate() {
+            for (z, open) in row.iter_mut().enumerate() {
+                let above = origin + Vector3::new(x as isize, size, z as isize);
+                *open = self.light(&above, LightChannel::Sky).is_none_or(|level| level == MAX_LIGHT);
+            }
+        }
+
+        // The chunk's own light is seeded directly, without going through the world lookups
+        let registry = self.registry;
+        let chunk = match self.chunks.get_mut(chunk_index) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        // Lowest block of each column the sky reaches, `CHUNK_SIZE` if it doesn't reach the column at all
+        let mut sky_floor = [[CHUNK_SIZE; CHUNK_SIZE]; CHUNK_SIZE];
+        for (x, row) in sky_floor.iter_mut().enumerate() {
+            for (z, floor) in row.iter_mut().enumerate() {
+                if sky_above[x][z] {
+                    while *floor > 0 && registry[chunk.block_at_chunk_pos(&Vector3::new(x, *floor - 1, z))].transparent {
+                        *floor -= 1;
+                    }
+                }
+            }
+        }
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    let block_index = Vector3::new(x, y, z);
+                    let position = origin + block_index.cast::<isize>().unwrap();
+                    if y >= sky_floor[x][z] {
+                        chunk.set_light_at_chunk_pos(&block_index, LightChannel::Sky, MAX_LIGHT);
+                        // Only sunlit blocks next to shade or another chunk have anywhere to spread to
+                        let border = x == 0 || x == CHUNK_SIZE - 1 || z == 0 || z == CHUNK_SIZE - 1 || y == 0;
+                        let shaded = sky_floor[x.saturating_sub(1)][z] > y || sky_floor[(x + 1).min(CHUNK_SIZE - 1)][z] > y
+                            || sky_floor[x][z.saturating_sub(1)] > y || sky_floor[x][(z + 1).min(CHUNK_SIZE - 1)] > y;
+                        if border || shaded {
+                            sky.push_back(position);
+                        }
+                    }
+                    let emission = registry[chunk.block_at_chunk_pos(&block_index)].light_emission;
+                    if emission > 0 {
+                        chunk.set_light_at_chunk_pos(&block_index, LightChannel::Block, emission);
+                        block.push_back(position);
+                    }
+                }
+            }
+        }
+        self.changed.insert(*chunk_index);
+        for offset in &FACE_NEIGHBOURS {
+            if self.chunks.contains_key(&(chunk_index + offset)) {
+                self.changed.insert(chunk_index + offset);
+            }
+        }
+
+        // Light already in the neighbours flows in across the shared faces
+        for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+            if !self.chunks.contains_key(&(chunk_index + offset)) {
+                continue;
+            }
+            let axis = face / 2;
+            for u in 0..size {
+                for v in 0..size {
+                    let mut cell = [0; 3];
+                    cell[axis] = if offset[axis] > 0 { size } else { -1 };
+                    cell[(axis + 1) % 3] = u;
+                    cell[(axis + 2) % 3] = v;
+                    let position = origin + Vector3::from(cell);
+                    if self.light(&position, LightChannel::Sky).is_some_and(|level| level > 0) {
+                        sky.push_back(position);
+                    }
+                    if self.light(&position, LightChannel::Block).is_some_and(|level| level > 0) {
+                        block.push_back(position);
+                    }
+                }
+            }
+        }
+        self.spread(sky, LightChannel::Sky);
+        self.spread(block, LightChannel::Block);
+
+        // The chunk below was lit as if it had open sky above it, which this chunk may now block
+        let mut blocked = VecDeque::new();
+        for x in 0..size {
+            for z in 0..size {
+                let below = origin + Vector3::new(x, -1, z);
+                if self.light(&below, LightChannel::Sky) == Some(MAX_LIGHT)
+                    && self.light(&(origin + Vector3::new(x, 0, z)), LightChannel::Sky) != Some(MAX_LIGHT) {
+                    self.set_light(&below, LightChannel::Sky, 0);
+                    blocked.push_back((below, MAX_LIGHT));
+                }
+            }
+        }
+        self.remove(blocked, LightChannel::Sky);
+    }
+
+    /// Updates the light around a block which was just placed, removed or replaced
+    pub(crate) fn relight_block(&mut self, position: &Vector3<isize>) {
+        self.relight_blocks(std::slice::from_ref(position));
+    }
+
+    /// Updates the light around many edited blocks at once, which only floods each area once
+    /// instead of once per block
+    pub(crate) fn relight_blocks(&mut self, positions: &[Vector3<isize>]) {
+        for channel in [LightChannel::Sky, LightChannel::Block] {
+            let mut removed = VecDeque::new();
+            for position in positions {
+                if let Some(level) = self.light(position, channel).filter(|level| *level > 0) {
+                    self.set_light(position, channel, 0);
+                    removed.push_back((*position, level));
+                }
+            }
+            self.remove(removed, channel);
+
+            // Light flows back in from the neighbours, or starts at the blocks themselves
+            let mut queue = VecDeque::new();
+            for position in positions {
+                queue.extend(FACE_NEIGHBOURS.iter().map(|offset| position + offset));
+                if let Some(level) = self.source_level(position, channel) {
+                    self.set_light(position, channel, level);
+                    queue.push_back(*position);
+                }
+            }
+            self.spread(queue, channel);
+        }
+    }
+
+    /// Spreads light outwards from every position in `queue`
+    fn spread(&mut self, mut queue: VecDeque<Vector3<isize>>, channel: LightChannel) {
+        while let Some(position) = queue.pop_front() {
+            let level = match self.light(&position, channel) {
+                Some(level) if level > 1 => level,
+                _ => continue,
+            };
+            for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+                let neighbour = position + offset;
+                let neighbour_level = if channel == LightChannel::Sky && face == DOWN && level == MAX_LIGHT {
+                    MAX_LIGHT
+                } else {
+                    level - 1
+                };
+                // Spreading visits every lit block, so the neighbour's chunk is only looked up once
+                let (chunk_index, block_index) = World::chunk_and_block_index(&neighbour);
+                let chunk = match self.chunks.get_mut(&chunk_index) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+                if self.registry[chunk.block_at_chunk_pos(&block_index)].transparent
+                    && chunk.light_at_chunk_pos(&block_index, channel) < neighbour_level {
+                    chunk.set_light_at_chunk_pos(&block_index, channel, neighbour_level);
+                    self.mark_changed(&chunk_index, &block_index);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Clears the light which came from the positions in `queue`, given with the level they had
+    /// before they were set to 0, then fills the cleared area from the light around it
+    fn remove(&mut self, mut queue: VecDeque<(Vector3<isize>, u8)>, channel: LightChannel) {
+        let mut refill = VecDeque::new();
+        while let Some((position, old_level)) = queue.pop_front() {
+            for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+                let neighbour = position + offset;
+                let level = match self.light(&neighbour, channel) {
+                    Some(level) if level > 0 => level,
+                    _ => continue,
+                };
+                let lit_by_removed = level < old_level
+                    || (channel == LightChannel::Sky && face == DOWN && old_level == MAX_LIGHT);
+                if lit_by_removed {
+                    self.set_light(&neighbour, channel, 0);
+                    queue.push_back((neighbour, level));
+                    if let Some(source_level) = self.source_level(&neighbour, channel) {
+                        self.set_light(&neighbour, channel, source_level);
+                        refill.push_back(neighbour);
+                    }
+                } else {
+                    refill.push_back(neighbour);
+                }
+            }
+        }
+        self.spread(refill, channel);
+    }
+
+    /// Light a block has regardless of its surroundings: emitted block light, or sky light
+    /// for transparent blocks at the top of the loaded chunks
+    fn source_level(&self, position: &Vector3<isize>, channel: LightChannel) -> Option<u8> {
+        match channel {
+            LightChannel::Sky => {
+                let open_above = self.light(&(position + FACE_NEIGHBOURS[2]), channel).is_none();
+                if open_above && self.transmits_light(position) == Some(true) {
+                    Some(MAX_LIGHT)
+                } else {
+                    None
+                }
+            }
+            LightChannel::Block => Some(self.emission(position)).filter(|level| *level > 0),
+        }
+    }
+
+    fn light(&self, position: &Vector3<isize>, channel: LightChannel) -> Option<u8> {
+        let (chunk_index, block_index) = World::chunk_and_block_index(position);
+        self.chunks.get(&chunk_index).map(|chunk| chunk.light_at_chunk_pos(&block_index, channel))
+    }
+
+    fn set_light(&mut self, position: &Vector3<isize>, channel: LightChannel, level: u8) {
+        let (chunk_index, block_index) = World::chunk_and_block_index(position);
+        if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
+            chunk.set_light_at_chunk_pos(&block_index, channel, level);
+            self.mark_changed(&chunk_index, &block_index);
+        }
+    }
+
+    /// Marks the chunk holding a block whose light changed, along with any neighbouring chunk
+    /// with faces touching the block
+    fn mark_changed(&mut self, chunk_index: &Vector3<isize>, block_index: &Vector3<usize>) {
+        self.changed.insert(*chunk_index);
+        for axis in 0..3 {
+            let mut offset = Vector3::new(0, 0, 0);
+            if block_index[axis] == 0 {
+                offset[axis] = -1;
+            } else if block_index[axis] == CHUNK_SIZE - 1 {
+                offset[axis] = 1;
+            } else {
+                continue;
+            }
+            if self.chunks.contains_key(&(chunk_index + offset)) {
+                self.changed.insert(chunk_index + offset);
+            }
+        }
+    }
+
+    /// Whether light can pass through a block, `None` if its chunk isn't loaded
+    fn transmits_light(&self, position: &Vector3<isize>) -> Option<bool> {
+        let (chunk_index, block_index) = World::chunk_and_block_index(position);
+        let chunk = self.chunks.get(&chunk_index)?;
+        Some(self.registry[chunk.block_at_chunk_pos(&block_index)].transparent)
+    }
+
+    fn emission(&self, position: &Vector3<isize>) -> u8 {
+        let (chunk_index, block_index) = World::chunk_and_block_index(position);
+        self.chunks.get(&chunk_index).map_or(0, |chunk| self.registry[chunk.block_at_chunk_pos(&block_index)].light_emission)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::block::AIR;
+
+    /// Chunk of air under a stone roof at its top layer
+    fn roofed_chunk(registry: &BlockRegistry) -> Chunk {
+        let mut chunk = Chunk::uniform(AIR);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block_at_chunk_pos(&Vector3::new(x, CHUNK_SIZE - 1, z), registry.id("core:stone").unwrap());
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn sky_light_crosses_chunk_borders() {
+        // Light flows into a chunk inserted next to a lit one, and out of one inserted next to a dark one
+        for roofed_first in [false, true] {
+            let mut world = World::new(0);
+            let roofed = roofed_chunk(&world.registry);
+            if roofed_first {
+                world.insert_chunk(Vector3::new(0, 0, 0), roofed);
+                world.insert_chunk(Vector3::new(1, 0, 0), Chunk::uniform(AIR));
+            } else {
+                world.insert_chunk(Vector3::new(1, 0, 0), Chunk::uniform(AIR));
+                world.insert_chunk(Vector3::new(0, 0, 0), roofed);
+            }
+
+            assert_eq!(world.light_at_global_pos(Vector3::new(16, 5, 5), LightChannel::Sky), MAX_LIGHT);
+            assert_eq!(world.light_at_global_pos(Vector3::new(15, 5, 5), LightChannel::Sky), 14);
+            assert_eq!(world.light_at_global_pos(Vector3::new(10, 5, 5), LightChannel::Sky), 9);
+            assert_eq!(world.light_at_global_pos(Vector3::new(0, 5, 5), LightChannel::Sky), 0);
+            assert_eq!(world.light_at_global_pos(Vector3::new(10, 15, 5), LightChannel::Sky), 0);
+        }
+    }
+
+    #[test]
+    fn block_light_falls_off_from_sources() {
+        let mut world = World::empty(1);
+        let lava = world.registry.id("core:lava").unwrap();
+        world.place_at_global_pos(Vector3::new(0, 0, 0), lava);
+
+        let block_light = |world: &World, x, y, z| world.light_at_global_pos(Vector3::new(x, y, z), LightChannel::Block);
+        assert_eq!(block_light(&world, 0, 0, 0), 15);
+        assert_eq!(block_light(&world, 3, 0, 0), 12);
+        assert_eq!(block_light(&world, -1, 0, 0), 14);
+        assert_eq!(block_light(&world, 5, 5, 0), 5);
+        assert_eq!(block_light(&world, 5, 5, 5), 0);
+        assert_eq!(block_light(&world, 20, 0, 0), 0);
+
+        // Light goes around opaque blocks rather than through them
+        world.place_at_global_pos(Vector3::new(1, 0, 0), world.registry.id("core:stone").unwrap());
+        assert_eq!(block_light(&world, 1, 0, 0), 0);
+        assert_eq!(block_light(&world, 2, 0, 0), 11);
+    }
+
+    #[test]
+    fn light_is_recomputed_after_edits() {
+        let mut world = World::empty(1);
+        let stone = world.registry.id("core:stone").unwrap();
+        let lava = world.registry.id("core:lava").unwrap();
+        let sky_light = |world: &World, x, y, z| world.light_at_global_pos(Vector3::new(x, y, z), LightChannel::Sky);
+        let block_light = |world: &World, x, y, z| world.light_at_global_pos(Vector3::new(x, y, z), LightChannel::Block);
+        assert_eq!(sky_light(&world, 5, 0, 5), MAX_LIGHT);
+
+        // Blocks cast a shadow which the light around it fills in
+        world.place_at_global_pos(Vector3::new(5, 10, 5), stone);
+        assert_eq!(sky_light(&world, 5, 10, 5), 0);
+        assert_eq!(sky_light(&world, 5, 9, 5), 14);
+        assert_eq!(sky_light(&world, 5, -10, 5), 14);
+        world.destroy_at_global_pos(Vector3::new(5, 10, 5));
+        assert_eq!(sky_light(&world, 5, 9, 5), MAX_LIGHT);
+        assert_eq!(sky_light(&world, 5, -10, 5), MAX_LIGHT);
+
+        world.place_at_global_pos(Vector3::new(5, 5, 5), lava);
+        assert_eq!(block_light(&world, 5, 5, 8), 12);
+        world.place_at_global_pos(Vector3::new(5, 5, 5), stone);
+        assert_eq!(block_light(&world, 5, 5, 5), 0);
+        assert_eq!(block_light(&world, 5, 5, 8), 0);
+        world.destroy_at_global_pos(Vector3::new(5, 5, 5));
+        assert_eq!(block_light(&world, 5, 5, 6), 0);
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use cgmath::Vector3;
+
+use super::{CHUNK_SIZE, Chunk, NEIGHBOURS, World, block::AIR, generator::TerrainGenerator, light::{LightChannel, MAX_LIGHT}, mesher::{ChunkNeighbourhood, LayeredMesh, MeshingMode}};
+
+/// Number of coarser levels drawn beyond the LOD distance, each downsampling twice as much as the last
+pub const LOD_LEVELS: usize = 3;
+/// How many cells below the surface the sides of a tile are kept open along its border.
+/// A neighbouring tile one level coarser can be up to a cell of its own, two of the finer tile's, off.
+const SKIRT_CELLS: usize = 2;
+
+/// Cube of `CHUNK_SIZE` cells, each `2^level` blocks wide, which stands in for the `2^level` chunks
+/// along each axis it covers. Tiles at a level are indexed like chunks, so the tile at index `i`
+/// starts at chunk `i * 2^level`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LodTile {
+    pub level: usize,
+    pub index: Vector3<isize>,
+}
+
+impl LodTile {
+    /// Tile at `level` covering the chunk at `chunk_index`
+    pub fn containing(chunk_index: &Vector3<isize>, level: usize) -> Self {
+        let chunks = 1 << level;
+        LodTile {
+            level,
+            index: chunk_index.map(|coordinate| coordinate.div_euclid(chunks)),
+        }
+    }
+
+    /// Width of a cell in blocks
+    pub fn scale(&self) -> usize {
+        1 << self.level
+    }
+
+    /// Lowest chunk the tile covers
+    pub fn first_chunk(&self) -> Vector3<isize> {
+        self.index * (1 << self.level)
+    }
+
+    /// Lowest corner of the tile in world coordinates
+    pub fn origin(&self) -> Vector3<isize> {
+        self.first_chunk() * CHUNK_SIZE as isize
+    }
+
+    /// Width of the tile in blocks
+    pub fn size(&self) -> usize {
+        CHUNK_SIZE * self.scale()
+    }
+
+    /// Chebyshev distance, in chunks, from `chunk_index` to the closest chunk the tile covers
+    pub fn chunk_distance(&self, chunk_index: &Vector3<isize>) -> isize {
+        let first = self.first_chunk();
+        let last = first + Vector3::new(1, 1, 1) * ((1 << self.level) - 1);
+        let distance = |coordinate: usize| (first[coordinate] - chunk_index[coordinate]).max(chunk_index[coordinate] - last[coordinate]).max(0);
+        distance(0).max(distance(1)).max(distance(2))
+    }
+
+    /// Whether the two tiles cover any chunk in common, which for tiles of different levels means one lies inside the other
+    pub fn overlaps(&self, other: &LodTile) -> bool {
+        let level = self.level.max(other.level);
+        LodTile::containing(&self.first_chunk(), level) == LodTile::containing(&other.first_chunk(), level)
+    }
+
+    /// The eight tiles one level finer which make up this one
+    fn children(&self) -> impl Iterator<Item = LodTile> + '_ {
+        (0..8).map(move |child| LodTile {
+            level: self.level - 1,
+            index: self.index * 2 + Vector3::new(child & 1, (child >> 1) & 1, (child >> 2) & 1),
+        })
+    }
+}
+
+/// Distance, in chunks, from the camera to where the coarsest tiles end
+pub fn horizon_distance(lod_distance: isize) -> isize {
+    lod_distance << LOD_LEVELS
+}
+
+/// Tiles covering every chunk from `lod_distance` chunks around `center` out to the horizon, without overlapping.
+/// Starting from the coarsest level, a tile is split into its finer children while it's closer than
+/// `lod_distance` doubled for each level above the first, so detail halves every time the distance doubles.
+/// Splitting a tile of the first level leaves full chunks, which aren't part of the result.
+pub fn select_tiles(center: &Vector3<isize>, lod_distance: isize) -> HashSet<LodTile> {
+    let mut tiles = HashSet::new();
+    if lod_distance <= 0 {
+        return tiles;
+    }
+    let horizon = horizon_distance(lod_distance);
+    let lowest = LodTile::containing(&center.map(|coordinate| coordinate - horizon), LOD_LEVELS).index;
+    let highest = LodTile::containing(&center.map(|coordinate| coordinate + horizon), LOD_LEVELS).index;
+    let mut stack = Vec::new();
+    for x in lowest.x..=highest.x {
+        for y in lowest.y..=highest.y {
+            for z in lowest.z..=highest.z {
+                let tile = LodTile { level: LOD_LEVELS, index: Vector3::new(x, y, z) };
+                if tile.chunk_distance(center) <= horizon {
+                    stack.push(tile);
+                }
+            }
+        }
+    }
+    while let Some(tile) = stack.pop() {
+        if tile.chunk_distance(center) >= lod_distance << (tile.level - 1) {
+            tiles.insert(tile);
+        } else if tile.level > 1 {
+            stack.extend(tile.children());
+        }
+    }
+    tiles
+}
+
+/// Builds the cells of `tile` and the cells around it the mesher looks at, from the generator's terrain.
+/// Every empty cell is fully lit by the sky.
+///
+/// Cells a few cells below the surface along the tile's sides are left out of its neighbours,
+/// which keeps the tile's side faces there. Where a neighbouring tile is at another level its
+/// surface doesn't line up with this one's, and those faces hang down over the crack between them.
+pub(crate) fn tile_neighbourhood(generator: &TerrainGenerator, tile: &LodTile, meshing_mode: MeshingMode) -> ChunkNeighbourhood {
+    let scale = tile.scale();
+    let origin = tile.origin();
+    let size = CHUNK_SIZE as isize;
+    // Columns reach a cell past the tile on every side, and high enough to find the surface above the skirts
+    let cells = CHUNK_SIZE + 2 + SKIRT_CELLS;
+
+    let mut center = Chunk::uniform(AIR);
+    let mut neighbours: [Option<Chunk>; 26] = std::array::from_fn(|_| Some(Chunk::uniform(AIR)));
+    let mut tints = [[[1.0; 3]; CHUNK_SIZE]; CHUNK_SIZE];
+    for x in -1..=size {
+        for z in -1..=size {
+            // Each column of cells is sampled through its middle
+            let global_x = origin.x + x * scale as isize + scale as isize / 2;
+            let global_z = origin.z + z * scale as isize + scale as isize / 2;
+            let column = generator.lod_column(global_x, global_z, origin.y - scale as isize, scale, cells);
+            let inside_column = (0..size).contains(&x) && (0..size).contains(&z);
+            if inside_column {
+                tints[x as usize][z as usize] = generator.biome_at(global_x, global_z).tint;
+            }
+            for y in -1..=size {
+                let mut block_id = column[(y + 1) as usize];
+                let position = Vector3::new(x, y, z);
+                let offset = position.map(|coordinate| coordinate.div_euclid(size));
+                let chunk = if offset == Vector3::new(0, 0, 0) {
+                    &mut center
+                } else {
+                    let neighbour = NEIGHBOURS.iter().position(|neighbour| *neighbour == offset).unwrap();
+                    // Skirts only hang down the sides, the cells are still there above and below the tile
+                    let skirt = !inside_column && (0..size).contains(&y);
+                    if skirt && column[(y + 2) as usize..(y + 2) as usize + SKIRT_CELLS].contains(&AIR) {
+                        block_id = AIR;
+                    }
+                    neighbours[neighbour].as_mut().unwrap()
+                };
+                let cell = position.map(|coordinate| coordinate.rem_euclid(size) as usize);
+                if block_id == AIR {
+                    chunk.set_light_at_chunk_pos(&cell, LightChannel::Sky, MAX_LIGHT);
+                } else {
+                    chunk.set_block_at_chunk_pos(&cell, block_id);
+                }
+            }
+        }
+    }
+
+    ChunkNeighbourhood {
+        center,
+        neighbours,
+        meshing_mode,
+        registry: generator.registry().clone(),
+        tints,
+    }
+}
+
+/// LOD tiles the world has picked around the player and the meshes built for them
+#[derive(Default)]
+pub(crate) struct LodTiles {
+    /// Tiles covering everything between the LOD distance and the horizon
+    selected: HashSet<LodTile>,
+    /// Chunk and LOD distance `selected` was picked for
+    picked_for: Option<(Vector3<isize>, isize)>,
+    /// Selected tiles whose mesh has been built or is being built
+    queued: HashSet<LodTile>,
+    /// Meshes which haven't been handed out yet
+    finished: Vec<(LodTile, LayeredMesh)>,
+}
+
+impl LodTiles {
+    /// Keeps a mesh built by the job queue, unless its tile has been dropped since
+    pub(super) fn mesh_built(&mut self, tile: LodTile, mesh: LayeredMesh) {
+        if self.selected.contains(&tile) {
+            self.finished.push((tile, mesh));
+        }
+    }
+}
+
+impl World {
+    /// Picks the LOD tiles covering everything from `lod_distance` chunks around `position` out to the
+    /// horizon, see `select_tiles`, and queues up to `mesh_budget` of the new ones for meshing, closest first.
+    /// Tiles which are no longer picked are dropped. A distance of 0 turns LOD off.
+    /// Returns the number of tiles which were queued.
+    pub fn load_lod_around(&mut self, position: &Vector3<f32>, lod_distance: isize, mesh_budget: usize) -> usize {
+        let (center, _) = World::chunk_and_block_index(&position.map(|coordinate| coordinate.floor() as isize));
+        if self.lod.picked_for != Some((center, lod_distance)) {
+            let selected = select_tiles(&center, lod_distance);
+            self.lod.queued.retain(|tile| selected.contains(tile));
+            self.lod.finished.retain(|(tile, _)| selected.contains(tile));
+            self.jobs.retain_lod_meshes(|tile| selected.contains(tile));
+            self.lod.selected = selected;
+            self.lod.picked_for = Some((center, lod_distance));
+        }
+
+        let mut missing: Vec<LodTile> = self.lod.selected.iter().filter(|tile| !self.lod.queued.contains(tile)).copied().collect();
+        missing.sort_by_key(|tile| (tile.chunk_distance(&center), tile.level));
+        missing.truncate(mesh_budget);
+        for tile in &missing {
+            self.jobs.submit_lod_mesh(*tile, self.meshing_mode);
+            self.lod.queued.insert(*tile);
+        }
+        self.poll_jobs();
+        missing.len()
+    }
+
+    /// Tiles picked by the last `load_lod_around`, whether or not their mesh has been built yet
+    pub fn lod_tiles(&self) -> &HashSet<LodTile> {
+        &self.lod.selected
+    }
+
+    /// Returns the mesh of every picked LOD tile which has been built since the last call, in cells
+    /// rather than blocks. A tile with nothing to draw yields an empty mesh.
+    pub fn take_lod_meshes(&mut self) -> Vec<(LodTile, LayeredMesh)> {
+        self.poll_jobs();
+        std::mem::take(&mut self.lod.finished)
+    }
+}
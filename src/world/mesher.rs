@@ -0,0 +1,646 @@
+use std::sync::Arc;
+
+use cgmath::{Vector2, Vector3};
+
+use crate::renderer::{vertex::Vertex3D, meshgen};
+
+use super::{Chunk, CHUNK_SIZE, FACE_NEIGHBOURS, NEIGHBOURS, block::{self, Block, BlockRegistry, MeshType, RenderLayer, Tint}, fluid, light::{LightChannel, MAX_LIGHT}};
+
+/// Atlas tile, vertex type, sky and block light, corner occlusion, tint and render layer of a face,
+/// greedy meshing merges faces with equal keys
+type FaceKey = ((f32, f32), i32, (u8, u8), [[u8; 2]; 2], [u32; 3], RenderLayer);
+
+/// Vertices of a chunk, split by the render pass they're drawn in
+#[derive(Clone, Debug, Default)]
+pub struct LayeredMesh {
+    pub opaque: Vec<Vertex3D>,
+    pub cutout: Vec<Vertex3D>,
+    pub translucent: Vec<Vertex3D>,
+}
+
+impl LayeredMesh {
+    pub fn layer(&self, layer: RenderLayer) -> &Vec<Vertex3D> {
+        match layer {
+            RenderLayer::Opaque => &self.opaque,
+            RenderLayer::Cutout => &self.cutout,
+            RenderLayer::Translucent => &self.translucent,
+        }
+    }
+
+    pub fn layer_mut(&mut self, layer: RenderLayer) -> &mut Vec<Vertex3D> {
+        match layer {
+            RenderLayer::Opaque => &mut self.opaque,
+            RenderLayer::Cutout => &mut self.cutout,
+            RenderLayer::Translucent => &mut self.translucent,
+        }
+    }
+
+    /// Number of vertices in all layers
+    pub fn len(&self) -> usize {
+        self.opaque.len() + self.cutout.len() + self.translucent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every vertex of the chunk in one buffer, opaque layer first
+    pub fn into_vertices(self) -> Vec<Vertex3D> {
+        let mut vertices = self.opaque;
+        vertices.extend(self.cutout);
+        vertices.extend(self.translucent);
+        vertices
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// Two triangles for every exposed face
+    Naive,
+    /// Merges coplanar faces with the same texture into larger quads
+    #[default]
+    Greedy,
+}
+
+/// Copy of a chunk and the 26 chunks touching it, which is all the mesher needs.
+/// Owning the data lets chunks be meshed on a worker thread while the world keeps changing.
+#[derive(Clone)]
+pub struct ChunkNeighbourhood {
+    pub center: Chunk,
+    /// Indexed in the same order as `NEIGHBOURS`, so the first six share a face with the center.
+    /// `None` if that chunk isn't loaded.
+    pub neighbours: [Option<Chunk>; 26],
+    pub meshing_mode: MeshingMode,
+    pub registry: Arc<BlockRegistry>,
+    /// Biome tint of each column of the center chunk, indexed by x and z
+    pub tints: [[[f32; 3]; CHUNK_SIZE]; CHUNK_SIZE],
+}
+
+impl ChunkNeighbourhood {
+    /// Builds the vertex buffers for the center chunk, one for each render layer.
+    /// Faces bordering a chunk which hasn't been generated yet are skipped.
+    pub fn gen_mesh(&self) -> LayeredMesh {
+        match self.meshing_mode {
+            MeshingMode::Naive => self.gen_naive_mesh(),
+            MeshingMode::Greedy => self.gen_greedy_mesh(),
+        }
+    }
+
+    fn gen_naive_mesh(&self) -> LayeredMesh {
+        let mut mesh = LayeredMesh::default();
+
+        let current_chunk = &self.center;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let i = current_chunk.block_at_chunk_pos(&Vector3::new(x, y, z));
+                    if i == 0 {
+                        continue;
+                    }
+                    let cur = &self.registry[i];
+                    let block_vertices = mesh.layer_mut(cur.render_layer);
+                    let tex_coords = face_textures(cur, current_chunk.metadata_at_chunk_pos(&Vector3::new(x, y, z)));
+
+                    let position = [x as f32, y as f32, z as f32];
+                    let block_position = Vector3::new(x as isize, y as isize, z as isize);
+                    let vertex_type = cur.block_type as i32;
+                    let first_vertex = block_vertices.len();
+                    match cur.mesh_type {
+                        MeshType::Block => {
+                            let x_right_adjacent = if x < 15 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x+1, y, z))])
+                            } else if let Some(chunk) = &self.neighbours[0] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(0, y, z))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = x_right_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 0, block_vertices, &tex_coords[0], vertex_type, &self.face_light(&block_position, 0), &occlusion_values(self.face_occlusion(&block_position, 0)));
+                                }
+                            }
+
+                            let x_left_adjacent = if x > 0 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x-1, y, z))])
+                            } else if let Some(chunk) = &self.neighbours[1] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(CHUNK_SIZE-1, y, z))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = x_left_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 1, block_vertices, &tex_coords[1], vertex_type, &self.face_light(&block_position, 1), &occlusion_values(self.face_occlusion(&block_position, 1)));
+                                }
+                            }
+
+    
+                            let y_top_adjacent = if y < 15 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x, y+1, z))])
+                            } else if let Some(chunk) = &self.neighbours[2] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(x,0, z))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = y_top_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 2, block_vertices, &tex_coords[2], vertex_type, &self.face_light(&block_position, 2), &occlusion_values(self.face_occlusion(&block_position, 2)));
+                                }
+                            }
+    
+                            let y_bottom_adjacent = if y > 0 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x, y-1, z))])
+                            } else if let Some(chunk) = &self.neighbours[3] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(x,CHUNK_SIZE-1, z))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = y_bottom_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 3, block_vertices, &tex_coords[3], vertex_type, &self.face_light(&block_position, 3), &occlusion_values(self.face_occlusion(&block_position, 3)));
+                                }
+                            }
+
+                            let z_back_adjacent = if z < 15 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x, y, z+1))])
+                            } else if let Some(chunk) = &self.neighbours[4] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(x, y, 0))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = z_back_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 4, block_vertices, &tex_coords[4], vertex_type, &self.face_light(&block_position, 4), &occlusion_values(self.face_occlusion(&block_position, 4)));
+                                }
+                            }
+
+
+                            let z_front_adjacent = if z > 0 {
+                                Some(&self.registry[current_chunk.block_at_chunk_pos(&Vector3::new(x, y, z-1))])
+                            } else if let Some(chunk) = &self.neighbours[5] {
+                                Some(&self.registry[chunk.block_at_chunk_pos(&Vector3::new(x, y, CHUNK_SIZE-1))])
+                            } else {
+                                None
+                            };
+                            if let Some(adjacent_block) = z_front_adjacent {
+                                if adjacent_block.transparent {
+                                    meshgen::push_face(&position, 5, block_vertices, &tex_coords[5], vertex_type, &self.face_light(&block_position, 5), &occlusion_values(self.face_occlusion(&block_position, 5)));
+                                }
+                            }
+                        }
+                        MeshType::CrossedPlanes => {
+                            let light = self.light_at(&block_position).map_or(Vector2::new(0.0, 0.0), light_vector);
+                            meshgen::push_face(&position, 6, block_vertices, &tex_coords[0], vertex_type, &light, &meshgen::NO_OCCLUSION);
+                            meshgen::push_face(&position, 7, block_vertices, &tex_coords[0], vertex_type, &light, &meshgen::NO_OCCLUSION);
+                            meshgen::push_face(&position, 8, block_vertices, &tex_coords[0], vertex_type, &light, &meshgen::NO_OCCLUSION);
+                            meshgen::push_face(&position, 9, block_vertices, &tex_coords[0], vertex_type, &light, &meshgen::NO_OCCLUSION);
+                        }
+                        MeshType::Fluid => self.push_fluid(&block_position, cur, block_vertices),
+                    }
+                    self.tint_block(cur, (x, z), &mut block_vertices[first_vertex..]);
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Sweeps a slice through the chunk for each face direction and grows every exposed face
+    /// into the largest rectangle of faces sharing its texture, first along u and then along v.
+    fn gen_greedy_mesh(&self) -> LayeredMesh {
+        let mut mesh = LayeredMesh::default();
+
+        for face in 0..6 {
+            let axis = face / 2;
+            let (u_axis, v_axis) = meshgen::FACE_UV_AXES[face];
+
+            for layer in 0..CHUNK_SIZE {
+                let mut mask = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+                for (u, row) in mask.iter_mut().enumerate() {
+                    for (v, cell) in row.iter_mut().enumerate() {
+                        let mut position = [0; 3];
+                        position[axis] = layer;
+                        position[u_axis] = u;
+                        position[v_axis] = v;
+                        *cell = self.exposed_face(&position, face);
+                    }
+                }
+
+                for v in 0..CHUNK_SIZE {
+                    let mut u = 0;
+                    while u < CHUNK_SIZE {
+                        let key = match mask[u][v] {
+                            Some(key) => key,
+                            None => {
+                                u += 1;
+                                continue;
+                            }
+                        };
+
+                        let mut width = 1;
+                        while u + width < CHUNK_SIZE && mask[u + width][v] == Some(key) {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        while v + height < CHUNK_SIZE && (u..u + width).all(|row| mask[row][v + height] == Some(key)) {
+                            height += 1;
+                        }
+                        for row in mask.iter_mut().skip(u).take(width) {
+                            for cell in row.iter_mut().skip(v).take(height) {
+                                *cell = None;
+                            }
+                        }
+
+                        let mut position = [0.0; 3];
+                        position[axis] = layer as f32;
+                        position[u_axis] = u as f32;
+                        position[v_axis] = v as f32;
+                        let mut size = [1.0; 3];
+                        size[u_axis] = width as f32;
+                        size[v_axis] = height as f32;
+                        let (texmap_offset, vertex_type, light, occlusion, tint, render_layer) = key;
+                        let block_vertices = mesh.layer_mut(render_layer);
+                        let first_vertex = block_vertices.len();
+                        meshgen::push_quad(&position, face, &size, block_vertices, &texmap_offset, vertex_type, &light_vector(light), &occlusion_values(occlusion));
+                        let [r, g, b] = tint.map(f32::from_bits);
+                        for vertex in &mut block_vertices[first_vertex..] {
+                            vertex.tint = Vector3::new(r, g, b);
+                        }
+
+                        u += width;
+                    }
+                }
+            }
+        }
+
+        // Crossed planes and fluids can't be merged with anything, so they're meshed one block at a time
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let cur = &self.registry[self.center.block_at_chunk_pos(&Vector3::new(x, y, z))];
+                    let block_vertices = mesh.layer_mut(cur.render_layer);
+                    let first_vertex = block_vertices.len();
+                    match cur.mesh_type {
+                        MeshType::CrossedPlanes => {
+                            let tex_coords = face_textures(cur, self.center.metadata_at_chunk_pos(&Vector3::new(x, y, z)));
+                            let position = [x as f32, y as f32, z as f32];
+                            let light = self.light_at(&Vector3::new(x as isize, y as isize, z as isize)).map_or(Vector2::new(0.0, 0.0), light_vector);
+                            for face in 6..10 {
+                                meshgen::push_face(&position, face, block_vertices, &tex_coords[0], cur.block_type as i32, &light, &meshgen::NO_OCCLUSION);
+                            }
+                        }
+                        MeshType::Fluid => self.push_fluid(&Vector3::new(x as isize, y as isize, z as isize), cur, block_vertices),
+                        MeshType::Block => {}
+                    }
+                    self.tint_block(cur, (x, z), &mut block_vertices[first_vertex..]);
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Pushes the faces of a fluid block which don't touch the same fluid or an opaque block,
+    /// lowered to the fluid's surface. The top is left open when the surface is below the block above.
+    fn push_fluid(&self, position: &Vector3<isize>, cur: &Block, vertices: &mut Vec<Vertex3D>) {
+        let fluid = match &cur.fluid {
+            Some(fluid) => fluid,
+            None => return,
+        };
+        let metadata = self.center.metadata_at_chunk_pos(&position.cast::<usize>().unwrap());
+        let covered = self.block_at(&(position + FACE_NEIGHBOURS[2])) == Some(cur.id);
+        let height = fluid::surface_height(fluid, metadata, covered);
+        let tex_coords = face_textures(cur, metadata);
+        // Fluid doesn't block light, so its faces are lit by the fluid itself
+        let light = self.light_at(position).map_or(Vector2::new(0.0, 0.0), light_vector);
+
+        for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+            let adjacent = match self.block_at(&(position + offset)) {
+                Some(adjacent) => adjacent,
+                None => continue,
+            };
+            let lowered_top = face == 2 && height < 1.0;
+            if adjacent == cur.id || (!self.registry[adjacent].transparent && !lowered_top) {
+                continue;
+            }
+            let origin = [position.x as f32, position.y as f32, position.z as f32];
+            meshgen::push_quad(&origin, face, &[1.0, height, 1.0], vertices, &tex_coords[face], cur.block_type as i32, &light, &meshgen::NO_OCCLUSION);
+        }
+    }
+
+    /// Colour a face of `cur` in the given column of the center chunk is tinted with,
+    /// white unless the block takes on its biome's tint on that face
+    fn face_tint(&self, cur: &Block, column: (usize, usize), top: bool) -> [f32; 3] {
+        match cur.tint {
+            Tint::All => self.tints[column.0][column.1],
+            Tint::Top if top => self.tints[column.0][column.1],
+            _ => [1.0; 3],
+        }
+    }
+
+    /// Tints the vertices just pushed for a block, telling its top faces apart by their normal
+    fn tint_block(&self, cur: &Block, column: (usize, usize), vertices: &mut [Vertex3D]) {
+        for vertex in vertices {
+            let [r, g, b] = self.face_tint(cur, column, vertex.normal.y > 0.5);
+            vertex.tint = Vector3::new(r, g, b);
+        }
+    }
+
+    /// Returns the texture, vertex type, light, occlusion and tint of the center chunk's block `face` at `position`,
+    /// or `None` if that face is hidden or isn't part of a full cube
+    fn exposed_face(&self, position: &[usize; 3], face: usize) -> Option<FaceKey> {
+        let [x, y, z] = *position;
+        let i = self.center.block_at_chunk_pos(&Vector3::new(x, y, z));
+        let cur = &self.registry[i];
+        if i == 0 || !matches!(cur.mesh_type, MeshType::Block) {
+            return None;
+        }
+
+        let adjacent = Vector3::new(x as isize, y as isize, z as isize) + FACE_NEIGHBOURS[face];
+        if !self.registry[self.block_at(&adjacent)?].transparent {
+            return None;
+        }
+        let texture = face_textures(cur, self.center.metadata_at_chunk_pos(&Vector3::new(x, y, z)))[face];
+        let position = Vector3::new(x as isize, y as isize, z as isize);
+        let tint = self.face_tint(cur, (x, z), face == 2).map(f32::to_bits);
+        Some((texture, cur.block_type as i32, self.light_at(&adjacent)?, self.face_occlusion(&position, face), tint, cur.render_layer))
+    }
+
+    /// Ambient occlusion of the corners of a face, indexed by their u and v coordinate.
+    /// Each corner is darkened by the opaque blocks among the two edges and the corner next to it
+    /// in front of the face, from 3 for none to 0 when both edges are blocked.
+    fn face_occlusion(&self, position: &Vector3<isize>, face: usize) -> [[u8; 2]; 2] {
+        let (u_axis, v_axis) = meshgen::FACE_UV_AXES[face];
+        let front = position + FACE_NEIGHBOURS[face];
+        let mut u_step = Vector3::new(0, 0, 0);
+        u_step[u_axis] = 1;
+        let mut v_step = Vector3::new(0, 0, 0);
+        v_step[v_axis] = 1;
+        // Blocks in unloaded chunks don't occlude anything
+        let opaque = |position: Vector3<isize>| self.block_at(&position).is_some_and(|block_id| !self.registry[block_id].transparent);
+
+        let mut occlusion = [[3; 2]; 2];
+        for (u, row) in occlusion.iter_mut().enumerate() {
+            for (v, corner) in row.iter_mut().enumerate() {
+                let u_offset = if u == 0 { -u_step } else { u_step };
+                let v_offset = if v == 0 { -v_step } else { v_step };
+                let u_side = opaque(front + u_offset);
+                let v_side = opaque(front + v_offset);
+                *corner = if u_side && v_side {
+                    0
+                } else {
+                    3 - u_side as u8 - v_side as u8 - opaque(front + u_offset + v_offset) as u8
+                };
+            }
+        }
+        occlusion
+    }
+
+    /// Light falling on a face, which is the light of the block in front of it
+    fn face_light(&self, position: &Vector3<isize>, face: usize) -> Vector2<f32> {
+        self.light_at(&(position + FACE_NEIGHBOURS[face])).map_or(Vector2::new(0.0, 0.0), light_vector)
+    }
+
+    /// Sky and block light relative to the center chunk, `None` if it lies in a neighbour which isn't loaded
+    fn light_at(&self, position: &Vector3<isize>) -> Option<(u8, u8)> {
+        let (chunk, block_index) = self.chunk_at(position)?;
+        Some((chunk.light_at_chunk_pos(&block_index, LightChannel::Sky), chunk.light_at_chunk_pos(&block_index, LightChannel::Block)))
+    }
+
+    /// Looks up a block relative to the center chunk, reaching at most one chunk past it.
+    /// Returns `None` if the block lies in a neighbour which isn't loaded.
+    fn block_at(&self, position: &Vector3<isize>) -> Option<usize> {
+        let (chunk, block_index) = self.chunk_at(position)?;
+        Some(chunk.block_at_chunk_pos(&block_index))
+    }
+
+    /// Finds the chunk holding a position relative to the center chunk and the position within it
+    fn chunk_at(&self, position: &Vector3<isize>) -> Option<(&Chunk, Vector3<usize>)> {
+        let size = CHUNK_SIZE as isize;
+        let offset = Vector3::new(position.x.div_euclid(size), position.y.div_euclid(size), position.z.div_euclid(size));
+        let chunk = if offset == Vector3::new(0, 0, 0) {
+            Some(&self.center)
+        } else {
+            self.neighbours[NEIGHBOURS.iter().position(|neighbour| *neighbour == offset)?].as_ref()
+        }?;
+        Some((chunk, Vector3::new(
+            position.x.rem_euclid(size) as usize,
+            position.y.rem_euclid(size) as usize,
+            position.z.rem_euclid(size) as usize,
+        )))
+    }
+}
+
+/// Scales corner occlusion levels to the 0 to 1 range the shader expects
+fn occlusion_values(occlusion: [[u8; 2]; 2]) -> [[f32; 2]; 2] {
+    occlusion.map(|row| row.map(|level| level as f32 / 3.0))
+}
+
+/// Scales sky and block light levels to the 0 to 1 range the shader expects
+fn light_vector((sky, block): (u8, u8)) -> Vector2<f32> {
+    Vector2::new(sky as f32 / MAX_LIGHT as f32, block as f32 / MAX_LIGHT as f32)
+}
+
+/// Atlas tile of each of the six cube faces of a block, in the same order as `CUBE_FACES`
+fn face_textures(cur: &Block, metadata: usize) -> [(f32, f32); 6] {
+    if let Some(texture_type) = &cur.texture_map {
+        let mut coords = [(0.0f32, 0.0f32); 6];
+        match texture_type {
+            block::TextureType::Single(x, y) => {
+                coords = [(*x, *y); 6];
+            },
+            block::TextureType::TopAndSide((x_top, y_top), (x_side, y_side)) => {
+                coords[0] = (*x_side, *y_side);
+                coords[1] = (*x_side, *y_side);
+                coords[2] = (*x_top, *y_top);
+                coords[3] = (*x_side, *y_side);
+                coords[4] = (*x_side, *y_side);
+                coords[5] = (*x_side, *y_side);
+            },
+            block::TextureType::TopSideBottom((x_top, y_top), (x_side, y_side), (x_bottom, y_bottom)) => {
+                coords[0] = (*x_side, *y_side);
+                coords[1] = (*x_side, *y_side);
+                coords[2] = (*x_top, *y_top);
+                coords[3] = (*x_bottom, *y_bottom);
+                coords[4] = (*x_side, *y_side);
+                coords[5] = (*x_side, *y_side);
+            },
+            block::TextureType::TopSideFrontActivatable(
+                (x_front_inactive, y_front_inactive),
+                (x_front_active, y_front_active),
+                (x_side, y_side),
+                (x_top, y_top)
+            ) => {
+                coords[0] = (*x_side, *y_side);
+                coords[1] = (*x_side, *y_side);
+                coords[2] = (*x_top, *y_top);
+                coords[3] = (*x_top, *y_top);
+                coords[4] = (*x_side, *y_side);
+                let active = metadata == 1;
+                coords[5] = if active {
+                    (*x_front_active, *y_front_active)
+                 } else {
+                     (*x_front_inactive, *y_front_inactive)
+                 };
+            }
+        }
+        coords
+    } else {
+        [(0.0, 0.0); 6]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use super::super::{World, block::AIR};
+
+    /// Neighbourhood of `center` surrounded by dark air, with no biome tint
+    fn neighbourhood(center: Chunk) -> ChunkNeighbourhood {
+        ChunkNeighbourhood {
+            center,
+            neighbours: NEIGHBOURS.map(|_| Some(Chunk::uniform(AIR))),
+            meshing_mode: MeshingMode::Greedy,
+            registry: BlockRegistry::core(),
+            tints: [[[1.0; 3]; CHUNK_SIZE]; CHUNK_SIZE],
+        }
+    }
+
+    /// Two triangles for every quad
+    fn quads(mesh: &LayeredMesh) -> Vec<Vec<Vertex3D>> {
+        mesh.clone().into_vertices().chunks_exact(6).map(|quad| quad.to_vec()).collect()
+    }
+
+    /// Number of quads facing along `normal`
+    fn quads_facing(mesh: &LayeredMesh, normal: Vector3<f32>) -> usize {
+        quads(mesh).iter().filter(|quad| quad[0].normal == normal).count()
+    }
+
+    /// Every block face a mesh covers, with the texture, light and tint it's drawn with.
+    /// Merged quads are split back into one face per block, quads which aren't axis aligned are kept whole.
+    fn covered_faces(mesh: &LayeredMesh) -> BTreeSet<([i32; 3], [i32; 3], [i32; 7])> {
+        let fixed = |value: f32| (value * 16.0).round() as i32;
+        let mut faces = BTreeSet::new();
+        for quad in quads(mesh) {
+            let vertex = quad[0];
+            let normal = [fixed(vertex.normal.x), fixed(vertex.normal.y), fixed(vertex.normal.z)];
+            let looks = [
+                fixed(vertex.atlas_tile.x), fixed(vertex.atlas_tile.y), fixed(vertex.light.x), fixed(vertex.light.y),
+                fixed(vertex.tint.x), fixed(vertex.tint.y), fixed(vertex.tint.z),
+            ];
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for vertex in &quad {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(vertex.position[axis]);
+                    max[axis] = max[axis].max(vertex.position[axis]);
+                }
+            }
+            match (0..3).find(|axis| min[*axis] == max[*axis]) {
+                Some(axis) => {
+                    let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+                    for u in min[u_axis] as i32..max[u_axis].ceil() as i32 {
+                        for v in min[v_axis] as i32..max[v_axis].ceil() as i32 {
+                            let mut cell = [0; 3];
+                            cell[axis] = min[axis] as i32;
+                            cell[u_axis] = u;
+                            cell[v_axis] = v;
+                            faces.insert((normal, cell, looks));
+                        }
+                    }
+                }
+                None => {
+                    faces.insert((normal, [fixed(min[0]), fixed(min[1]), fixed(min[2])], looks));
+                }
+            }
+        }
+        faces
+    }
+
+    #[test]
+    fn slabs_merge_into_one_quad_per_side() {
+        let registry = BlockRegistry::core();
+        let mut center = Chunk::uniform(AIR);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                center.set_block_at_chunk_pos(&Vector3::new(x, 4, z), registry.id("core:stone").unwrap());
+            }
+        }
+        let mut neighbourhood = neighbourhood(center);
+
+        let mesh = neighbourhood.gen_mesh();
+        assert_eq!(quads(&mesh).len(), 6);
+        for face in FACE_NEIGHBOURS.iter() {
+            assert_eq!(quads_facing(&mesh, face.cast().unwrap()), 1);
+        }
+
+        neighbourhood.meshing_mode = MeshingMode::Naive;
+        let naive = neighbourhood.gen_mesh();
+        assert_eq!(quads(&naive).len(), 2 * CHUNK_SIZE * CHUNK_SIZE + 4 * CHUNK_SIZE);
+        assert_eq!(covered_faces(&naive), covered_faces(&mesh));
+    }
+
+    #[test]
+    fn differing_faces_are_not_merged() {
+        let registry = BlockRegistry::core();
+        let stone = registry.id("core:stone").unwrap();
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let (first, second) = (Vector3::new(4, 4, 4), Vector3::new(5, 4, 4));
+        let pair = |first_block: usize, second_block: usize| {
+            let mut center = Chunk::uniform(AIR);
+            center.set_block_at_chunk_pos(&first, first_block);
+            center.set_block_at_chunk_pos(&second, second_block);
+            center
+        };
+
+        // Two blocks of the same kind share their top
+        assert_eq!(quads_facing(&neighbourhood(pair(stone, stone)).gen_mesh(), up), 1);
+
+        // Texture
+        let dirt = registry.id("core:dirt").unwrap();
+        assert_eq!(quads_facing(&neighbourhood(pair(stone, dirt)).gen_mesh(), up), 2);
+
+        // Light
+        let mut center = pair(stone, stone);
+        center.set_light_at_chunk_pos(&(first + Vector3::new(0, 1, 0)), LightChannel::Block, 10);
+        let mesh = neighbourhood(center).gen_mesh();
+        assert_eq!(quads_facing(&mesh, up), 2);
+        assert_eq!(quads_facing(&mesh, -up), 1);
+
+        // Ambient occlusion, a block touching a corner of only the first top
+        let mut center = pair(stone, stone);
+        center.set_block_at_chunk_pos(&Vector3::new(3, 5, 5), stone);
+        let mesh = neighbourhood(center).gen_mesh();
+        let tops_below = quads(&mesh).iter().filter(|quad| quad[0].normal == up && quad[0].position.y == 5.0).count();
+        assert_eq!(tops_below, 2);
+
+        // Tint, grass only takes on the biome colour on its top
+        let grass = registry.id("core:grass").unwrap();
+        let mut neighbourhood = neighbourhood(pair(grass, grass));
+        neighbourhood.tints[5][4] = [0.5, 0.8, 0.3];
+        let mesh = neighbourhood.gen_mesh();
+        assert_eq!(quads_facing(&mesh, up), 2);
+        assert_eq!(quads_facing(&mesh, Vector3::new(0.0, 0.0, 1.0)), 1);
+    }
+
+    #[test]
+    fn greedy_and_naive_meshes_cover_the_same_faces() {
+        let mut world = World::new(1234);
+        world.load_chunks_around(&Vector3::new(8.0, 8.0, 8.0), 1, usize::MAX).unwrap();
+        // Blocks with other textures, tints, render layers and mesh types on top of the generated ground
+        for (x, identifier) in ["core:glass", "core:water", "core:grass", "core:oak_leaves", "core:short_grass", "core:oak_log"].iter().enumerate() {
+            let block_id = world.registry().id(identifier).unwrap();
+            world.place_at_global_pos(Vector3::new(2 + x as isize, 12, 7), block_id);
+            world.place_at_global_pos(Vector3::new(2 + x as isize, 12, 8), block_id);
+        }
+
+        let mut neighbourhood = world.neighbourhood(&Vector3::new(0, 0, 0)).unwrap();
+        neighbourhood.meshing_mode = MeshingMode::Greedy;
+        let greedy = neighbourhood.gen_mesh();
+        neighbourhood.meshing_mode = MeshingMode::Naive;
+        let naive = neighbourhood.gen_mesh();
+        assert!(quads(&greedy).len() < quads(&naive).len());
+        assert_eq!(covered_faces(&greedy), covered_faces(&naive));
+    }
+}
@@ -1,310 +1,600 @@
+pub mod biome;
 pub mod block;
-
-use std::collections::{HashMap, LinkedList};
-
-use cgmath::{Matrix4, Vector3, Vector2};
-use crate::{c_str, renderer::{mesh::{ChunkMesh, Texture}, shader::Shader, vertex::Vertex3D, meshgen}};
-
-use noise::{Perlin, NoiseFn, Seedable};
-
-use self::block::{BLOCKS, MeshType, block_index_by_name};
+pub mod edit;
+pub mod export;
+pub mod feature;
+pub mod fluid;
+pub mod generator;
+pub mod history;
+pub mod light;
+pub mod lod;
+pub mod mesher;
+pub mod preset;
+pub mod region;
+pub mod storage;
+pub mod template;
+pub mod tick;
+pub mod visibility;
+pub mod vox;
+mod jobs;
+mod nbt;
+
+use std::{collections::{HashMap, HashSet}, io, path::Path, sync::Arc};
+
+use cgmath::Vector3;
+use rand::{SeedableRng, rngs::StdRng};
+
+use self::{biome::Biome, block::{AIR, BlockRegistry, BlockType, TextureType}, feature::Replace, fluid::FluidSimulation, generator::TerrainGenerator, history::{DEFAULT_HISTORY_BUDGET, EditHistory}, light::{LightChannel, Lighting}, jobs::{JobOutput, JobQueue}, lod::LodTiles, mesher::{ChunkNeighbourhood, LayeredMesh, MeshingMode}, preset::GeneratorPreset, region::RegionStore, storage::PaletteStorage, tick::{PERSISTENT_LEAVES, ScheduledTicks, TickHandlers}, visibility::ChunkVisibility};
 
 #[cfg(target_os = "android")]
 extern crate android_log;
 
 pub const CHUNK_SIZE: usize = 16;
 
+/// Offsets to the six chunks sharing a face with a chunk
+pub(crate) const FACE_NEIGHBOURS: [Vector3<isize>; 6] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+/// Offsets to all 26 chunks touching a chunk, starting with the ones in `FACE_NEIGHBOURS`
+const NEIGHBOURS: [Vector3<isize>; 26] = neighbour_offsets();
+
+const fn neighbour_offsets() -> [Vector3<isize>; 26] {
+    let mut offsets = [Vector3::new(0, 0, 0); 26];
+    let mut i = 0;
+    while i < FACE_NEIGHBOURS.len() {
+        offsets[i] = FACE_NEIGHBOURS[i];
+        i += 1;
+    }
+    // Chunks sharing only an edge or a corner have more than one non-zero coordinate
+    let mut x = -1;
+    while x <= 1 {
+        let mut y = -1;
+        while y <= 1 {
+            let mut z = -1;
+            while z <= 1 {
+                if (x != 0) as usize + (y != 0) as usize + (z != 0) as usize > 1 {
+                    offsets[i] = Vector3::new(x, y, z);
+                    i += 1;
+                }
+                z += 1;
+            }
+            y += 1;
+        }
+        x += 1;
+    }
+    offsets
+}
+
+#[derive(Clone)]
 pub struct Chunk {
-    blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-    metadata: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-    block_mesh: Box<Option<ChunkMesh>>,
-    model_matrix: Matrix4<f32>,
+    blocks: PaletteStorage,
+    metadata: PaletteStorage,
+    /// Sky light in the high nibble and block light in the low nibble, recomputed whenever the chunk is loaded
+    light: PaletteStorage,
+    /// Blocks this chunk's generation placed in neighbouring chunks, in world coordinates
+    spilled: generator::SpilledBlocks,
+    /// Bit per neighbouring chunk whose spilled blocks have already been written into this one
+    spill_sources: u32,
 }
 
 impl Chunk {
-    pub fn from_blocks(blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], position: Vector3<isize>) -> Self {
+    pub fn from_blocks(blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Self {
+        let mut chunk = Chunk::uniform(blocks[0][0][0]);
+        for (x, plane) in blocks.iter().enumerate() {
+            for (y, column) in plane.iter().enumerate() {
+                for (z, block_id) in column.iter().enumerate() {
+                    chunk.blocks.set(x, y, z, *block_id);
+                }
+            }
+        }
+        chunk
+    }
+
+    /// Chunk filled entirely with `block_id`
+    pub fn uniform(block_id: usize) -> Self {
         Self {
-            blocks,
-            metadata: [[[0usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-            block_mesh: Box::new(None),
-            model_matrix: Matrix4::from_translation(Vector3::new(position.x as f32, position.y as f32, position.z as f32)),
+            blocks: PaletteStorage::uniform(block_id),
+            metadata: PaletteStorage::uniform(0),
+            light: PaletteStorage::uniform(0),
+            spilled: Vec::new(),
+            spill_sources: 0,
         }
     }
 
     pub fn block_at_chunk_pos(&self, chunk_index: &Vector3<usize>) -> usize {
-        self.blocks[chunk_index.x][chunk_index.y][chunk_index.z]
-    }
-
-    pub fn update(&mut self) {
-        for z in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-                    let block = self.blocks[x][y][z];
-                    if block == 0 {
-                        continue;
-                    } else if block == 13 || block == 12 || block == 10 || block == 7 {
-                        if y > 0 {
-                            let lower_block = self.blocks[x][y-1][z];
-                            if lower_block != 2 && lower_block != 3 {
-                                self.blocks[x][y][z] = 0;
-                            }
-                        }
-                    }
-                }
-            }
+        self.blocks.get(chunk_index.x, chunk_index.y, chunk_index.z)
+    }
+
+    pub fn set_block_at_chunk_pos(&mut self, chunk_index: &Vector3<usize>, block_id: usize) {
+        self.blocks.set(chunk_index.x, chunk_index.y, chunk_index.z, block_id);
+    }
+
+    pub fn metadata_at_chunk_pos(&self, chunk_index: &Vector3<usize>) -> usize {
+        self.metadata.get(chunk_index.x, chunk_index.y, chunk_index.z)
+    }
+
+    pub fn set_metadata_at_chunk_pos(&mut self, chunk_index: &Vector3<usize>, metadata: usize) {
+        self.metadata.set(chunk_index.x, chunk_index.y, chunk_index.z, metadata);
+    }
+
+    pub fn light_at_chunk_pos(&self, chunk_index: &Vector3<usize>, channel: LightChannel) -> u8 {
+        let light = self.light.get(chunk_index.x, chunk_index.y, chunk_index.z) as u8;
+        match channel {
+            LightChannel::Sky => light >> 4,
+            LightChannel::Block => light & 0xF,
+        }
+    }
+
+    pub fn set_light_at_chunk_pos(&mut self, chunk_index: &Vector3<usize>, channel: LightChannel, level: u8) {
+        let light = self.light.get(chunk_index.x, chunk_index.y, chunk_index.z) as u8;
+        let light = match channel {
+            LightChannel::Sky => (level << 4) | (light & 0xF),
+            LightChannel::Block => (light & 0xF0) | level,
+        };
+        self.light.set(chunk_index.x, chunk_index.y, chunk_index.z, light as usize);
+    }
+
+    /// Whether any block id in the chunk's palette matches `predicate`. The palette can still
+    /// hold ids which are no longer used, so a match doesn't guarantee such a block exists.
+    pub fn may_contain<F: Fn(usize) -> bool>(&self, predicate: F) -> bool {
+        self.blocks.palette().iter().any(|block_id| predicate(*block_id))
+    }
+
+    /// The block filling the whole chunk, if it only contains one kind
+    pub fn uniform_block(&self) -> Option<usize> {
+        self.blocks.uniform_value()
+    }
+
+    /// Drops palette entries no block uses anymore, see `PaletteStorage::compact`
+    pub fn compact(&mut self) {
+        self.blocks.compact();
+        self.metadata.compact();
+    }
+
+    /// Bit in `spill_sources` for the chunk at `offset` from this one, `None` if it isn't adjacent
+    fn spill_source_bit(offset: &Vector3<isize>) -> Option<u32> {
+        if offset.x.abs() > 1 || offset.y.abs() > 1 || offset.z.abs() > 1 {
+            return None;
         }
+        Some(((offset.x + 1) * 9 + (offset.y + 1) * 3 + (offset.z + 1)) as u32)
     }
 
-    pub fn update_mesh(&mut self, vertices: Vec<Vertex3D>, texture: &Texture, shader: &Shader) {
-        self.block_mesh = Box::new(Some(ChunkMesh::new(vertices, texture.clone(), shader.clone())));
+    fn has_spill_from(&self, offset: &Vector3<isize>) -> bool {
+        Chunk::spill_source_bit(offset).is_some_and(|bit| self.spill_sources & (1 << bit) != 0)
     }
+
+    fn mark_spill_from(&mut self, offset: &Vector3<isize>) {
+        if let Some(bit) = Chunk::spill_source_bit(offset) {
+            self.spill_sources |= 1 << bit;
+        }
+    }
+
 }
 
+/// Spilled blocks waiting for their chunk to load, along with the blocks they may overwrite and the chunk which spilled them
+pub type QueuedBlocks = Vec<(Vector3<usize>, usize, Replace, Vector3<isize>)>;
+
 pub struct World {
-    _seed: u32,
     pub chunks: HashMap<Vector3<isize>, Chunk>,
-    pub generation_queue: HashMap<Vector3<isize>, LinkedList<(Vector3<usize>, usize)>>,
-    noise_offset: Vector2<f64>,
-    noise_scale: f64,
-    perlin: Perlin,
-
-
-    pub texture: Texture,
-    pub(crate) terrain_shader: Shader,
+    pub generation_queue: HashMap<Vector3<isize>, QueuedBlocks>,
+    registry: Arc<BlockRegistry>,
+    generator: Arc<TerrainGenerator>,
+    jobs: JobQueue,
+
+    /// Chunks whose mesh no longer matches their blocks
+    dirty_chunks: HashSet<Vector3<isize>>,
+    /// Meshes built by the job queue which haven't been handed out yet
+    finished_meshes: Vec<(Vector3<isize>, LayeredMesh)>,
+    /// Which faces of each meshed chunk can see each other, worked out along with its mesh
+    visibility: HashMap<Vector3<isize>, ChunkVisibility>,
+    /// Chunk the world was last loaded around, meshes closest to it are built first
+    load_center: Vector3<isize>,
+    /// How chunk meshes are built, greedy unless comparing against the naive mesher
+    pub meshing_mode: MeshingMode,
+    /// Coarser stand-ins for the chunks beyond the LOD distance
+    lod: LodTiles,
+
+    /// Where chunks are saved to and loaded from, if the world is persisted at all
+    regions: Option<RegionStore>,
+    /// Chunks edited since they were last saved
+    unsaved_chunks: HashSet<Vector3<isize>>,
+    /// Load center at which saving edited chunks before unloading them last failed, they're
+    /// kept loaded and only retried once the world is loaded around another chunk
+    failed_unload_at: Option<Vector3<isize>>,
+
+    /// Number of times `tick` has run
+    current_tick: u64,
+    scheduled_ticks: ScheduledTicks,
+    tick_handlers: TickHandlers,
+    /// Picks the blocks random ticks land on
+    tick_rng: StdRng,
+    fluids: FluidSimulation,
+
+    /// Edits which can be undone and redone
+    history: EditHistory,
 }
 
 impl World {
-    pub fn new(texture: Texture, world_shader: Shader, seed: u32, chunk_radius: isize) -> Self {
-        let noise_scale = 0.02;
-        let noise_offset = Vector2::new(
-            1_000_000.0 * rand::random::<f64>() + 3_141_592.0,
-            1_000_000.0 * rand::random::<f64>() + 3_141_592.0,
-        );
-        let perlin = Perlin::new();
-        perlin.set_seed(seed);
-
-        let mut world = Self {
-            _seed: seed,
+    /// Creates an empty world, chunks are generated by `load_chunks_around`.
+    /// Generation and meshing run inline on the calling thread.
+    pub fn new(seed: u32) -> Self {
+        World::with_worker_threads(seed, 0)
+    }
+
+    /// Creates an empty world which generates and meshes chunks on `worker_threads` background threads
+    pub fn with_worker_threads(seed: u32, worker_threads: usize) -> Self {
+        World::with_registry(seed, worker_threads, BlockRegistry::core())
+    }
+
+    /// Creates an empty world made of the blocks in `registry` instead of the core blocks
+    pub fn with_registry(seed: u32, worker_threads: usize, registry: Arc<BlockRegistry>) -> Self {
+        World::with_preset(seed, worker_threads, registry, GeneratorPreset::default())
+    }
+
+    /// Creates an empty world whose terrain is shaped by `preset` instead of the default preset
+    pub fn with_preset(seed: u32, worker_threads: usize, registry: Arc<BlockRegistry>, preset: GeneratorPreset) -> Self {
+        let generator = TerrainGenerator::with_preset(seed, &registry, preset);
+        World::with_generator(worker_threads, generator)
+    }
+
+    /// Creates an empty world generated by `generator`, for example one with extra features added,
+    /// made of the blocks in the generator's registry
+    pub fn with_generator(worker_threads: usize, generator: TerrainGenerator) -> Self {
+        let seed = generator.seed();
+        let registry = generator.registry().clone();
+        let generator = Arc::new(generator);
+        let jobs = JobQueue::new(generator.clone(), worker_threads);
+        let fluids = FluidSimulation::new(&registry);
+        let tick_handlers = TickHandlers::core(&registry);
+
+        Self {
             chunks: HashMap::new(),
             generation_queue: HashMap::new(),
-            noise_offset,
-            noise_scale,
-            perlin,
-            texture,
-            terrain_shader: world_shader,
-        };
-        
-        for chunk_x in -chunk_radius..chunk_radius {
-            for chunk_y in 0..chunk_radius {
-                for chunk_z in -chunk_radius..chunk_radius {
-                    let chunk_index = Vector3::new(chunk_x, chunk_y, chunk_z);
-                    let chunk_data: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-
-                    let mut cur_chunk = Chunk::from_blocks(chunk_data, 16 * chunk_index);
-                    
-                    world.gen_terrain(&chunk_index, &mut cur_chunk);
-                    world.gen_caves(&chunk_index, &mut cur_chunk);
-                    world.gen_foliage(&chunk_index, &mut cur_chunk);
-                    world.chunks.insert(chunk_index, cur_chunk);
-                }
-            }
+            registry,
+            generator,
+            jobs,
+            dirty_chunks: HashSet::new(),
+            finished_meshes: Vec::new(),
+            visibility: HashMap::new(),
+            load_center: Vector3::new(0, 0, 0),
+            meshing_mode: MeshingMode::default(),
+            lod: LodTiles::default(),
+            regions: None,
+            unsaved_chunks: HashSet::new(),
+            failed_unload_at: None,
+            current_tick: 0,
+            scheduled_ticks: ScheduledTicks::default(),
+            tick_handlers,
+            tick_rng: StdRng::seed_from_u64(seed as u64),
+            fluids,
+            history: EditHistory::new(DEFAULT_HISTORY_BUDGET),
         }
+    }
 
-        world.place_enqueued();
+    /// Persists the world to region files in `directory`. Chunks saved there are loaded
+    /// instead of being generated, which should happen before any chunks are loaded.
+    pub fn open_save_directory<P: AsRef<Path>>(&mut self, directory: P) -> io::Result<()> {
+        self.regions = Some(RegionStore::open(directory, self.registry.clone())?);
+        Ok(())
+    }
 
-        let chunks = &mut world.chunks;
-        world.generation_queue.retain( |key, blocks_queue| {
-            if let Some(chunk) = chunks.get_mut(key) {
-                for (index, block_id) in blocks_queue {
-                    chunk.blocks[index.x][index.y][index.z] = *block_id;
-                }
-                return false;
+    /// Writes every chunk edited since it was last saved, returns how many were written.
+    /// Does nothing if no save directory has been opened.
+    pub fn save(&mut self) -> io::Result<usize> {
+        let regions = match &mut self.regions {
+            Some(regions) => regions,
+            None => return Ok(0),
+        };
+        for chunk_index in &self.unsaved_chunks {
+            if let Some(chunk) = self.chunks.get_mut(chunk_index) {
+                chunk.compact();
             }
-            true
-        });
-
-        let mut positions = Vec::new();
-        for (position, _chunk) in &world.chunks {
-            positions.push(position.clone());
-        }
-        for position in positions {
-            world.gen_chunk_mesh(&position);
         }
+        let chunks = &self.chunks;
+        let unsaved: Vec<_> = self.unsaved_chunks.iter()
+            .filter_map(|chunk_index| chunks.get(chunk_index).map(|chunk| (*chunk_index, chunk)))
+            .collect();
+        regions.save_chunks(unsaved.iter().cloned())?;
+        self.unsaved_chunks.clear();
+        Ok(unsaved.len())
+    }
 
-        world
+    pub fn seed(&self) -> u32 {
+        self.generator.seed()
+    }
+
+    pub fn registry(&self) -> &BlockRegistry {
+        &self.registry
     }
 
-    fn gen_terrain(&mut self, chunk_index: &Vector3<isize>, chunk: &mut Chunk) {
-        for block_x in 0..CHUNK_SIZE {
-            for block_y in 0..CHUNK_SIZE {
-                for block_z in 0..CHUNK_SIZE {
-                    let global_x = block_x as isize + (chunk_index.x * CHUNK_SIZE as isize);
-                    let global_y = block_y as isize + (chunk_index.y * CHUNK_SIZE as isize);
-                    let global_z = block_z as isize + (chunk_index.z * CHUNK_SIZE as isize);
-                    let surface_y = self.surface_noise(global_x as f64, global_z as f64);
-                    if (global_y as f64) < surface_y {
-                        if global_y == surface_y.floor() as isize {
-                            chunk.blocks[block_x][block_y][block_z] = block_index_by_name("Grass");
-                        } else if (global_y as f64) < (7.0 * surface_y/8.0).floor() {
-                            match rand::random::<usize>()%100 {
-                                0 => chunk.blocks[block_x][block_y][block_z] = block_index_by_name("Iron Ore"),
-                                1..=3 => chunk.blocks[block_x][block_y][block_z] = block_index_by_name("Coal"),
-                                _ => chunk.blocks[block_x][block_y][block_z] = block_index_by_name("Stone"),
-                            }                            
-                        } else {
-                            chunk.blocks[block_x][block_y][block_z] = block_index_by_name("Dirt");
+    /// Biome of the column at `world_pos`, which only depends on its x and z
+    pub fn biome_at(&self, world_pos: Vector3<isize>) -> &Biome {
+        self.generator.biome_at(world_pos.x, world_pos.z)
+    }
+
+    /// Unloads chunks which are further than `view_distance` chunks from `position` and queues
+    /// up to `generation_budget` of the missing chunks within it for generation, closest first.
+    /// Returns the number of chunks which were queued.
+    ///
+    /// Edited chunks are saved before they're unloaded. If that fails they stay loaded and unsaved,
+    /// the rest of the chunks are still loaded and unloaded, and the error is returned. Saving them
+    /// is tried again once `position` is in another chunk.
+    pub fn load_chunks_around(&mut self, position: &Vector3<f32>, view_distance: isize, generation_budget: usize) -> io::Result<usize> {
+        let (center, _) = World::chunk_and_block_index(&Vector3::new(
+            position.x.floor() as isize,
+            position.y.floor() as isize,
+            position.z.floor() as isize,
+        ));
+        self.load_center = center;
+
+        // Keep one extra ring loaded so walking back and forth over a chunk border doesn't thrash
+        let unload_distance = view_distance + 1;
+        let in_range = |chunk_index: &Vector3<isize>| {
+            let offset = chunk_index - center;
+            offset.x.abs() <= unload_distance && offset.y.abs() <= unload_distance && offset.z.abs() <= unload_distance
+        };
+
+        // Without a save directory edits are simply dropped along with their chunk
+        let mut save_result = Ok(());
+        let unloaded_edits: Vec<_> = self.unsaved_chunks.iter().filter(|chunk_index| !in_range(chunk_index)).copied().collect();
+        if !unloaded_edits.is_empty() {
+            match &mut self.regions {
+                Some(_) if self.failed_unload_at == Some(center) => {}
+                Some(regions) => {
+                    for chunk_index in &unloaded_edits {
+                        if let Some(chunk) = self.chunks.get_mut(chunk_index) {
+                            chunk.compact();
+                        }
+                    }
+                    let chunks = &self.chunks;
+                    save_result = regions.save_chunks(unloaded_edits.iter().filter_map(|chunk_index| chunks.get(chunk_index).map(|chunk| (*chunk_index, chunk))));
+                    if save_result.is_ok() {
+                        for chunk_index in &unloaded_edits {
+                            self.unsaved_chunks.remove(chunk_index);
                         }
                     }
                 }
+                None => {
+                    for chunk_index in &unloaded_edits {
+                        self.unsaved_chunks.remove(chunk_index);
+                    }
+                }
             }
         }
-    }
-
-    fn gen_foliage(&mut self, chunk_index: &Vector3<isize>, chunk: &mut Chunk) {
-        for block_x in 0..CHUNK_SIZE {
-            for block_y in 0..CHUNK_SIZE {
-                for block_z in 0..CHUNK_SIZE {
-                    let global_x = block_x as isize + (chunk_index.x * CHUNK_SIZE as isize);
-                    let global_y = block_y as isize + (chunk_index.y * CHUNK_SIZE as isize);
-                    let global_z = block_z as isize + (chunk_index.z * CHUNK_SIZE as isize);
-                    let surface_y = self.surface_noise(global_x as f64, global_z as f64);
-                    if (global_y as f64) < surface_y {
-                        if global_y == surface_y.floor() as isize {
-                            let (_position, current_block_index) = World::chunk_and_block_index(&Vector3::new(global_x, global_y, global_z));
-                            if chunk.blocks[current_block_index.x][current_block_index.y][current_block_index.z] != 2 && chunk.blocks[current_block_index.x][current_block_index.y][current_block_index.z] != 3 {
-                                continue;
-                            }
-                            match rand::random::<usize>()%100 {
-                                50..=99 => {
-                                    let rand_val = rand::random::<usize>()%10;
-                                    let block_id = match rand_val {
-                                        0..=6 => block_index_by_name("Short Grass"),
-                                        7 => block_index_by_name("Fern"),
-                                        8 => block_index_by_name("Rose"),
-                                        _ => block_index_by_name("Dandelion"),
-                                    };
-                    
-                                    let (position, foliage_block_index) = World::chunk_and_block_index(&Vector3::new(global_x, global_y+1, global_z));
-                                    if let Some(chunk) = self.chunks.get_mut(&position) {
-                                        chunk.blocks[foliage_block_index.x][foliage_block_index.y][foliage_block_index.z] = block_id;
-                                    } else {
-                                        self.append_queued_block(block_id, &position, &foliage_block_index);
-                                    }
-                                }
-                                40 => {
-                                    self.place_tree(Vector3::new(global_x, global_y+1, global_z))
-                                }
-                                _ => {
-                    
-                                }
-                            }
-                        }
+        self.failed_unload_at = if save_result.is_err() { Some(center) } else { None };
+
+        let dirty_chunks = &mut self.dirty_chunks;
+        let unsaved_chunks = &self.unsaved_chunks;
+        self.chunks.retain(|chunk_index, _| {
+            let keep = in_range(chunk_index) || unsaved_chunks.contains(chunk_index);
+            if !keep {
+                dirty_chunks.insert(*chunk_index);
+            }
+            keep
+        });
+        self.jobs.retain_generation(in_range);
+
+        let mut missing = Vec::new();
+        for chunk_x in -view_distance..=view_distance {
+            for chunk_y in -view_distance..=view_distance {
+                for chunk_z in -view_distance..=view_distance {
+                    let chunk_index = center + Vector3::new(chunk_x, chunk_y, chunk_z);
+                    if !self.chunks.contains_key(&chunk_index) && !self.jobs.is_generating(&chunk_index) {
+                        missing.push(chunk_index);
                     }
                 }
             }
         }
+        missing.sort_by_key(|chunk_index| World::distance_squared(chunk_index, &center));
+        missing.truncate(generation_budget);
+
+        let queued = missing.len();
+        for chunk_index in missing {
+            match self.load_saved_chunk(&chunk_index) {
+                Some(chunk) => self.insert_chunk(chunk_index, chunk),
+                None => self.jobs.submit_generation(chunk_index),
+            }
+        }
+        self.poll_jobs();
+        save_result.map(|_| queued)
     }
 
-    fn place_enqueued(&mut self) {
-        for (position, chunk) in &mut self.chunks {
-            if let Some(queue) = self.generation_queue.get(position) {
-                for (block_pos, block_id) in queue {
-                    chunk.blocks[block_pos.x][block_pos.y][block_pos.z] = *block_id;
-                }
+    pub fn is_chunk_loaded_at(&self, world_pos: Vector3<isize>) -> bool {
+        let (chunk_index, _) = World::chunk_and_block_index(&world_pos);
+        self.chunks.contains_key(&chunk_index)
+    }
+
+    /// Reads a chunk from the save directory. Chunks which can't be read are generated instead,
+    /// and their region is no longer saved to so what's left in it isn't overwritten.
+    fn load_saved_chunk(&mut self, chunk_index: &Vector3<isize>) -> Option<Chunk> {
+        match self.regions.as_mut()?.load_chunk(chunk_index) {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                println!("Failed to load chunk {:?}, regenerating it: {}", chunk_index, error);
+                None
             }
         }
     }
 
-    fn append_queued_block(&mut self, block_id: usize, chunk_index: &Vector3<isize>, block_index: &Vector3<usize>) {
-        if let Some(list) = self.generation_queue.get_mut(chunk_index) {
-            list.push_back((*block_index, block_id));
-        } else {
-            self.generation_queue.insert(*chunk_index, LinkedList::new());
-            if let Some(list) = self.generation_queue.get_mut(chunk_index) {
-                list.push_back((*block_index, block_id));
+    /// Moves finished generation jobs into the world and stashes finished meshes until they're taken
+    fn poll_jobs(&mut self) {
+        for output in self.jobs.finished() {
+            match output {
+                JobOutput::Generated(chunk_index, chunk) => self.insert_chunk(chunk_index, *chunk),
+                JobOutput::Meshed(chunk_index, vertices, visibility) => {
+                    self.visibility.insert(chunk_index, visibility);
+                    self.finished_meshes.push((chunk_index, vertices));
+                }
+                JobOutput::LodMeshed(tile, mesh) => self.lod.mesh_built(tile, mesh),
             }
         }
     }
 
-    fn gen_caves(&mut self, chunk_index: &Vector3<isize>, chunk: &mut Chunk) {
-        let noise_scale = 0.1;
-        let cutoff = 0.6;
-        for block_x in 0..CHUNK_SIZE {
-            for block_y in 0..CHUNK_SIZE {
-                for block_z in 0..CHUNK_SIZE {
-                    let global_x = (block_x as isize + (chunk_index.x * CHUNK_SIZE as isize)) as f64;
-                    let global_y = (block_y as isize + (chunk_index.y * CHUNK_SIZE as isize)) as f64;
-                    let global_z = (block_z as isize + (chunk_index.z * CHUNK_SIZE as isize)) as f64;
-                    let noise = self.perlin.get([noise_scale * global_x, noise_scale * global_y, noise_scale * global_z]);
-                    if noise > cutoff {
-                        chunk.blocks[block_x][block_y][block_z] = 0;
-                    }
+    fn distance_squared(a: &Vector3<isize>, b: &Vector3<isize>) -> isize {
+        let offset = a - b;
+        offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+    }
+
+    fn insert_chunk(&mut self, chunk_index: Vector3<isize>, mut chunk: Chunk) {
+        // Blocks which neighbouring chunks spilled into this chunk before it was loaded
+        if let Some(queue) = self.generation_queue.remove(&chunk_index) {
+            let mut sources = Vec::new();
+            for (block_index, block_id, replace, source) in queue {
+                if !chunk.has_spill_from(&(source - chunk_index)) && replace.allows(chunk.block_at_chunk_pos(&block_index), &self.registry) {
+                    chunk.set_block_at_chunk_pos(&block_index, block_id);
                 }
+                sources.push(source);
+            }
+            for source in sources {
+                chunk.mark_spill_from(&(source - chunk_index));
+            }
+        }
+        self.chunks.insert(chunk_index, chunk);
+        let mut lighting = Lighting::new(&mut self.chunks, &self.registry);
+        lighting.light_chunk(&chunk_index);
+        self.dirty_chunks.extend(lighting.changed);
+        self.spill_into_neighbours(chunk_index);
+        self.schedule_chunk_fluids(&chunk_index);
+
+        // Neighbours may now have hidden faces along the shared border, or occluded corners
+        self.dirty_chunks.insert(chunk_index);
+        for offset in &NEIGHBOURS {
+            let neighbour_index = chunk_index + offset;
+            if self.chunks.contains_key(&neighbour_index) {
+                self.dirty_chunks.insert(neighbour_index);
             }
         }
     }
 
-    pub fn place_tree(&mut self, world_pos: Vector3<isize>) {
-        for y in 0..5 {
-            let (chunk_index, block_index) = World::chunk_and_block_index(&(world_pos + Vector3::new(0, y, 0)));
-            if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
-                chunk.blocks[block_index.x][block_index.y][block_index.z] = block_index_by_name("Oak Log");
-            } else {
-                self.append_queued_block(block_index_by_name("Oak Log"), &chunk_index, &block_index);
-            }
+    /// Writes the blocks a chunk spilled into its neighbours, queueing them for neighbours which
+    /// aren't loaded. Neighbours which already received them, for example before they were saved,
+    /// are skipped so reloading a chunk doesn't undo edits made to its neighbours.
+    fn spill_into_neighbours(&mut self, source: Vector3<isize>) {
+        let spilled = match self.chunks.get(&source) {
+            Some(chunk) if !chunk.spilled.is_empty() => chunk.spilled.clone(),
+            _ => return,
+        };
+        let mut by_chunk: HashMap<Vector3<isize>, Vec<_>> = HashMap::new();
+        for (world_pos, block_id, replace) in spilled {
+            let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
+            by_chunk.entry(chunk_index).or_default().push((block_index, block_id, replace));
         }
 
-        for x in -1..=1 {
-            for z in -1..=1 {
-                for y in 3..=5 {
-                    if (x == -1 && z == -1 && y == 5) || (x == 1 && z == 1 && y == 5) || (x == -1 && z == 1 && y == 5) || (x == 1 && z == -1 && y == 5) || (x == 0 && z == 0 && y == 3) {
-                        continue;
-                    }
-                    let (chunk_index, block_index) = World::chunk_and_block_index(&(world_pos + Vector3::new(x, y, z)));
-                    if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
-                        chunk.blocks[block_index.x][block_index.y][block_index.z] = block_index_by_name("Oak Leaves");
-                    } else {
-                        self.append_queued_block(block_index_by_name("Oak Leaves"), &chunk_index, &block_index);
+        for (chunk_index, blocks) in by_chunk {
+            let offset = source - chunk_index;
+            if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
+                if chunk.has_spill_from(&offset) {
+                    continue;
+                }
+                let mut written = Vec::new();
+                for (block_index, block_id, replace) in blocks {
+                    if replace.allows(chunk.block_at_chunk_pos(&block_index), &self.registry) {
+                        chunk.set_block_at_chunk_pos(&block_index, block_id);
+                        written.push(block_index);
                     }
                 }
+                chunk.mark_spill_from(&offset);
+                self.dirty_chunks.insert(chunk_index);
+                for block_index in written {
+                    self.relight_block(&(chunk_index * CHUNK_SIZE as isize + block_index.cast::<isize>().unwrap()));
+                }
+            } else {
+                let queue = self.generation_queue.entry(chunk_index).or_default();
+                // Already queued if the source was loaded before
+                if queue.iter().any(|(_, _, _, queued_source)| *queued_source == source) {
+                    continue;
+                }
+                for (block_index, block_id, replace) in blocks {
+                    queue.push((block_index, block_id, replace, source));
+                }
             }
         }
     }
 
     pub fn chunk_from_block_array(&mut self, chunk_index: Vector3<isize>, blocks: [[[usize; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) {
-        let new_chunk = Chunk::from_blocks(blocks, 16 * chunk_index);
+        let new_chunk = Chunk::from_blocks(blocks);
         self.chunks.insert(chunk_index, new_chunk);
-        self.gen_chunk_mesh(&chunk_index);
+        let mut lighting = Lighting::new(&mut self.chunks, &self.registry);
+        lighting.light_chunk(&chunk_index);
+        self.dirty_chunks.extend(lighting.changed);
+        self.dirty_chunks.insert(chunk_index);
+        self.unsaved_chunks.insert(chunk_index);
     }
 
-    fn surface_noise(&self, global_x: f64, global_z: f64) -> f64 {
-        5.0 * self.perlin.get([self.noise_scale * global_x + self.noise_offset.x, self.noise_scale * global_z + self.noise_offset.y])
-                            //+ (50.0 * self.perlin.get([0.1 * noise_scale * self.noise_offset.x - 100.0, self.noise_offset.y - 44310.0]) + 3.0)
-                            + 10.1
+    /// Queues remeshing for up to `max_meshes` of the chunks edited since the last call, closest to the
+    /// load center first, and returns every mesh which has finished building since then.
+    /// A chunk which was removed or no longer has any faces yields an empty mesh.
+    pub fn take_dirty_meshes(&mut self, max_meshes: usize) -> Vec<(Vector3<isize>, LayeredMesh)> {
+        let mut dirty: Vec<Vector3<isize>> = self.dirty_chunks.iter().cloned().collect();
+        // Meshes still being built for edited chunks are already out of date
+        for chunk_index in &dirty {
+            self.jobs.cancel_mesh(chunk_index);
+        }
+
+        let center = self.load_center;
+        dirty.sort_by_key(|chunk_index| World::distance_squared(chunk_index, &center));
+        dirty.truncate(max_meshes);
+        for chunk_index in dirty {
+            self.dirty_chunks.remove(&chunk_index);
+            match self.neighbourhood(&chunk_index) {
+                Some(neighbourhood) => self.jobs.submit_mesh(chunk_index, neighbourhood),
+                None => {
+                    self.visibility.remove(&chunk_index);
+                    self.finished_meshes.push((chunk_index, LayeredMesh::default()));
+                }
+            }
+        }
+
+        self.poll_jobs();
+        std::mem::take(&mut self.finished_meshes)
     }
 
-    pub fn render(&self, view_matrix: &Matrix4<f32>, perspective_matrix: &Matrix4<f32>, elapsed_time: f32) {
-        unsafe {
-            self.terrain_shader.use_program();
-            self.terrain_shader.set_mat4(c_str!("perspective_matrix"), perspective_matrix);
-            self.terrain_shader.set_mat4(c_str!("view_matrix"), view_matrix);
-            self.terrain_shader.set_vec3(c_str!("sunlight_direction"), &Vector3::new(0.0, 2.0_f32.sqrt(), 2.0_f32.sqrt()));
-            self.terrain_shader.set_float(c_str!("time"), elapsed_time);
+    /// Which faces of a chunk can see each other, as of its latest mesh.
+    /// `None` if the chunk hasn't been meshed yet or has been unloaded since.
+    pub fn chunk_visibility(&self, chunk_index: &Vector3<isize>) -> Option<ChunkVisibility> {
+        self.visibility.get(chunk_index).copied()
+    }
 
-            for (_position, chunk) in &self.chunks {
-                if let Some(m) = &*chunk.block_mesh {
-                    self.terrain_shader.set_mat4(c_str!("model_matrix"), &chunk.model_matrix);
-                    m.draw(&self.terrain_shader);
-                }
+    /// Copies the chunk at `chunk_index` and every chunk touching it, if the chunk is loaded
+    pub fn neighbourhood(&self, chunk_index: &Vector3<isize>) -> Option<ChunkNeighbourhood> {
+        let center = self.chunks.get(chunk_index)?.clone();
+        Some(ChunkNeighbourhood {
+            center,
+            neighbours: NEIGHBOURS.map(|offset| self.chunks.get(&(chunk_index + offset)).cloned()),
+            meshing_mode: self.meshing_mode,
+            registry: self.registry.clone(),
+            tints: self.column_tints(chunk_index),
+        })
+    }
+
+    /// Tint of the biome of each column of a chunk, indexed by x and z
+    fn column_tints(&self, chunk_index: &Vector3<isize>) -> [[[f32; 3]; CHUNK_SIZE]; CHUNK_SIZE] {
+        let origin = chunk_index * CHUNK_SIZE as isize;
+        let mut tints = [[[1.0; 3]; CHUNK_SIZE]; CHUNK_SIZE];
+        for (x, row) in tints.iter_mut().enumerate() {
+            for (z, tint) in row.iter_mut().enumerate() {
+                *tint = self.biome_at(origin + Vector3::new(x as isize, 0, z as isize)).tint;
             }
         }
+        tints
+    }
+
+    /// Builds the vertex buffers for a chunk on the calling thread, without touching any GPU state
+    pub fn gen_chunk_mesh(&self, chunk_index: &Vector3<isize>) -> LayeredMesh {
+        self.neighbourhood(chunk_index).map(|neighbourhood| neighbourhood.gen_mesh()).unwrap_or_default()
     }
 
     fn chunk_and_block_index(world_pos: &Vector3<isize>) -> (Vector3<isize>, Vector3<usize>) {
         let chunk_index = Vector3 {
-            x: (world_pos.x as f32 / CHUNK_SIZE as f32).floor() as isize,
-            y: (world_pos.y as f32 / CHUNK_SIZE as f32).floor() as isize,
-            z: (world_pos.z as f32 / CHUNK_SIZE as f32).floor() as isize,
+            x: world_pos.x.div_euclid(CHUNK_SIZE as isize),
+            y: world_pos.y.div_euclid(CHUNK_SIZE as isize),
+            z: world_pos.z.div_euclid(CHUNK_SIZE as isize),
         };
         let block_index = Vector3 {
             x: (world_pos.x.rem_euclid(CHUNK_SIZE as isize)) as usize,
@@ -317,109 +607,87 @@ impl World {
     pub fn interact_at_global_pos(&mut self, world_pos: Vector3<isize>) {
         let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
         if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
-            let block_id = chunk.blocks[block_index.x][block_index.y][block_index.z];
-            match block_id {
-                17 => {
-                    let state = chunk.metadata[block_index.x][block_index.y][block_index.z];
-                    chunk.metadata[block_index.x][block_index.y][block_index.z] = if state == 0 {1} else {0}
+            let block = &self.registry[chunk.block_at_chunk_pos(&block_index)];
+            match block.texture_map {
+                Some(TextureType::TopSideFrontActivatable(..)) => {
+                    let block_id = chunk.block_at_chunk_pos(&block_index);
+                    let state = chunk.metadata_at_chunk_pos(&block_index);
+                    let toggled = if state == 0 {1} else {0};
+                    chunk.set_metadata_at_chunk_pos(&block_index, toggled);
+                    self.recorded(|world| world.record_change(world_pos, (block_id, state), (block_id, toggled)));
                 }
-                _ => println!("Interacted with {}", BLOCKS[block_id].name)
+                _ => println!("Interacted with {}", block.name)
             }
-            chunk.update();
-            self.gen_chunk_mesh(&chunk_index);
+            self.dirty_chunks.insert(chunk_index);
+            self.unsaved_chunks.insert(chunk_index);
         }
     }
 
     pub fn destroy_at_global_pos(&mut self, world_pos: Vector3<isize>) {
-        let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
-        if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
-            chunk.blocks[block_index.x][block_index.y][block_index.z] = 0;
-            chunk.update();
-            self.gen_chunk_mesh(&chunk_index);
-            
-            if block_index.x == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(1, 0, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.x == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(1, 0, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            }
+        self.recorded(|world| world.set_block_at_global_pos(world_pos, AIR, 0));
+    }
 
-            if block_index.y == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(0, 1, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.y == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(0, 1, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            }
+    /// Places a block, leaves placed this way never decay
+    pub fn place_at_global_pos(&mut self, world_pos: Vector3<isize>, block_id: usize) {
+        let metadata = self.placed_metadata(block_id);
+        self.recorded(|world| world.set_block_at_global_pos(world_pos, block_id, metadata));
+    }
 
-            if block_index.z == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(0, 0, 1);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.z == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(0, 0, 1);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            }
-            
-        }
+    /// Metadata of a block placed by the player rather than generated
+    fn placed_metadata(&self, block_id: usize) -> usize {
+        if self.registry[block_id].block_type == BlockType::Leaves { PERSISTENT_LEAVES } else { 0 }
     }
 
-    pub fn place_at_global_pos(&mut self, world_pos: Vector3<isize>, block_id: usize) {
+    /// Replaces a block and its metadata, then updates the light and meshes around it and
+    /// notifies the blocks touching it. The change is part of the edit history if a transaction is open.
+    /// Returns false if the block's chunk isn't loaded.
+    fn set_block_at_global_pos(&mut self, world_pos: Vector3<isize>, block_id: usize, metadata: usize) -> bool {
         let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
-        if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
-            //chunk.destroy_at_chunk_pos(block_index);
-            chunk.blocks[block_index.x][block_index.y][block_index.z] = block_id;
-            self.gen_chunk_mesh(&chunk_index);
-            if block_index.x == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(1, 0, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.x == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(1, 0, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            }
-
-            if block_index.y == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(0, 1, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.y == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(0, 1, 0);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
+        let before = match self.chunks.get_mut(&chunk_index) {
+            Some(chunk) => {
+                let before = (chunk.block_at_chunk_pos(&block_index), chunk.metadata_at_chunk_pos(&block_index));
+                chunk.set_block_at_chunk_pos(&block_index, block_id);
+                chunk.set_metadata_at_chunk_pos(&block_index, metadata);
+                before
             }
+            None => return false,
+        };
+        self.record_change(world_pos, before, (block_id, metadata));
+        self.unsaved_chunks.insert(chunk_index);
+        self.relight_block(&world_pos);
+        self.mark_dirty_around(&world_pos);
+        self.notify_neighbours(&world_pos);
+        true
+    }
 
-            if block_index.z == 0 {
-                let adjacent_chunk_index = chunk_index - Vector3::new(0, 0, 1);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
-                }
-            } else if block_index.z == CHUNK_SIZE-1 {
-                let adjacent_chunk_index = chunk_index + Vector3::new(0, 0, 1);
-                if let Some(_) = self.chunks.get(&adjacent_chunk_index) {
-                    self.gen_chunk_mesh(&adjacent_chunk_index);
+    /// Marks every loaded chunk holding a block touching `world_pos` as dirty, since its faces
+    /// may have been uncovered or its corners occluded
+    fn mark_dirty_around(&mut self, world_pos: &Vector3<isize>) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let (chunk_index, _) = World::chunk_and_block_index(&(world_pos + Vector3::new(x, y, z)));
+                    if self.chunks.contains_key(&chunk_index) {
+                        self.dirty_chunks.insert(chunk_index);
+                    }
                 }
             }
         }
     }
 
+    /// Sky or block light level at a block, 0 if its chunk isn't loaded
+    pub fn light_at_global_pos(&self, world_pos: Vector3<isize>, channel: LightChannel) -> u8 {
+        let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
+        self.chunks.get(&chunk_index).map_or(0, |chunk| chunk.light_at_chunk_pos(&block_index, channel))
+    }
+
+    /// Propagates the change in light caused by editing the block at `world_pos`
+    fn relight_block(&mut self, world_pos: &Vector3<isize>) {
+        let mut lighting = Lighting::new(&mut self.chunks, &self.registry);
+        lighting.relight_block(world_pos);
+        self.dirty_chunks.extend(lighting.changed);
+    }
+
     pub fn block_at_global_pos(&self, world_pos: Vector3<isize>) -> usize {
         let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
         if let Some(chunk) = self.chunks.get(&chunk_index) {
@@ -429,175 +697,96 @@ impl World {
         }
     }
 
-    pub fn collision_at_world_pos(&self, world_pos: Vector3<isize>) -> bool {
-        0 != self.block_at_global_pos(world_pos)
+    pub fn metadata_at_global_pos(&self, world_pos: Vector3<isize>) -> usize {
+        let (chunk_index, block_index) = World::chunk_and_block_index(&world_pos);
+        self.chunks.get(&chunk_index).map_or(0, |chunk| chunk.metadata_at_chunk_pos(&block_index))
     }
 
-    pub fn gen_chunk_mesh(&mut self, chunk_index: &Vector3<isize>) {
-        let mut block_vertices = Vec::new();
+    /// Whether a ray stops at the block, which it doesn't for air and fluids
+    pub fn collision_at_world_pos(&self, world_pos: Vector3<isize>) -> bool {
+        let block_id = self.block_at_global_pos(world_pos);
+        block_id != AIR && self.registry[block_id].fluid.is_none()
+    }
+}
 
-        if let Some(current_chunk) = self.chunks.get(chunk_index) {
-            for x in 0..CHUNK_SIZE {
-                for y in 0..CHUNK_SIZE {
-                    for z in 0..CHUNK_SIZE {
-                        let i = current_chunk.blocks[x][y][z];
-                        if i == 0 {
-                            continue;
-                        }
-                        let cur = &block::BLOCKS[i];
-                        let tex_coords:[(f32, f32);  6] = if let Some(texture_type) = &cur.texture_map {
-                            let mut coords = [(0.0f32, 0.0f32); 6];
-                            match texture_type {
-                                block::TextureType::Single(x, y) => {
-                                    for i in 0..6 {
-                                        coords[i] = (*x, *y)
-                                    }
-                                },
-                                block::TextureType::TopAndSide((x_top, y_top), (x_side, y_side)) => {
-                                    coords[0] = (*x_side, *y_side);
-                                    coords[1] = (*x_side, *y_side);
-                                    coords[2] = (*x_top, *y_top);
-                                    coords[3] = (*x_side, *y_side);
-                                    coords[4] = (*x_side, *y_side);
-                                    coords[5] = (*x_side, *y_side);
-                                },
-                                block::TextureType::TopSideBottom((x_top, y_top), (x_side, y_side), (x_bottom, y_bottom)) => {
-                                    coords[0] = (*x_side, *y_side);
-                                    coords[1] = (*x_side, *y_side);
-                                    coords[2] = (*x_top, *y_top);
-                                    coords[3] = (*x_bottom, *y_bottom);
-                                    coords[4] = (*x_side, *y_side);
-                                    coords[5] = (*x_side, *y_side);
-                                },
-                                block::TextureType::TopSideFrontActivatable(
-                                    (x_front_inactive, y_front_inactive),
-                                    (x_front_active, y_front_active),
-                                    (x_side, y_side),
-                                    (x_top, y_top)
-                                ) => {
-                                    coords[0] = (*x_side, *y_side);
-                                    coords[1] = (*x_side, *y_side);
-                                    coords[2] = (*x_top, *y_top);
-                                    coords[3] = (*x_top, *y_top);
-                                    coords[4] = (*x_side, *y_side);
-                                    let active = current_chunk.metadata[x][y][z] == 1;
-                                    coords[5] = if active {
-                                        (*x_front_active, *y_front_active)
-                                     } else {
-                                         (*x_front_inactive, *y_front_inactive)
-                                     };
-                                }
-                            }
-                            coords
-                        } else {
-                            [(0.0, 0.0); 6]
-                        };
-
-                        let position = [x as f32, y as f32, z as f32];
-                        let vertex_type = cur.block_type as i32;
-                        match cur.mesh_type {
-                            MeshType::Block => {
-                                let x_right_adjacent = if x < 15 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x+1, y, z))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(1isize, 0, 0))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(0, y, z))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = x_right_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 0, &mut block_vertices, &tex_coords[0], vertex_type);
-                                    }
-                                }
-
-                                let x_left_adjacent = if x > 0 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x-1, y, z))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(-1isize, 0, 0))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(CHUNK_SIZE-1, y, z))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = x_left_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 1, &mut block_vertices, &tex_coords[1], vertex_type);
-                                    }
-                                }
-
-        
-                                let y_top_adjacent = if y < 15 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x, y+1, z))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(0, 1isize, 0))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(x,0, z))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = y_top_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 2, &mut block_vertices, &tex_coords[2], vertex_type);
-                                    }
-                                }
-        
-                                let y_bottom_adjacent = if y > 0 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x, y-1, z))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(0, -1isize, 0))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(x,CHUNK_SIZE-1, z))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = y_bottom_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 3, &mut block_vertices, &tex_coords[3], vertex_type);
-                                    }
-                                }
-
-                                let z_back_adjacent = if z < 15 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x, y, z+1))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(0, 0, 1isize))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(x, y, 0))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = z_back_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 4, &mut block_vertices, &tex_coords[4], vertex_type);
-                                    }
-                                }
-
-
-                                let z_front_adjacent = if z > 0 {
-                                    Some(BLOCKS[current_chunk.block_at_chunk_pos(&Vector3::new(x, y, z-1))])
-                                } else if let Some(chunk) = self.chunks.get(&(*chunk_index + Vector3::new(0, 0, -1isize))) {
-                                    Some(BLOCKS[chunk.block_at_chunk_pos(&Vector3::new(x, y, CHUNK_SIZE-1))])
-                                } else {
-                                    None
-                                };
-                                if let Some(adjacent_block) = z_front_adjacent {
-                                    if adjacent_block.transparent {
-                                        meshgen::push_face(&position, 5, &mut block_vertices, &tex_coords[5], vertex_type);
-                                    }
-                                }
-                            }
-                            MeshType::CrossedPlanes => {
-                                meshgen::push_face(&position, 6, &mut block_vertices, &tex_coords[0], vertex_type);
-                                meshgen::push_face(&position, 7, &mut block_vertices, &tex_coords[0], vertex_type);
-                                meshgen::push_face(&position, 8, &mut block_vertices, &tex_coords[0], vertex_type);
-                                meshgen::push_face(&position, 9, &mut block_vertices, &tex_coords[0], vertex_type);
-                            }
-                        }
-                        
-                    }
+#[cfg(test)]
+impl World {
+    /// World of nothing but air, with every chunk up to `radius` chunks from the origin loaded,
+    /// for tests which don't need generated terrain
+    pub(crate) fn empty(radius: isize) -> Self {
+        let mut world = World::new(0);
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    world.insert_chunk(Vector3::new(x, y, z), Chunk::uniform(AIR));
                 }
             }
-            //let mesh = Mesh::new(block_vertices, &self.texture, &self.world_shader);
-            //current_chunk.block_mesh = Some(mesh); 
-        } else {
-            return;
         }
+        world
+    }
+}
 
-        if !block_vertices.is_empty() {
-            if let Some(chunk) = self.chunks.get_mut(chunk_index) {
-                chunk.update_mesh(block_vertices, &self.texture, &self.terrain_shader);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_world_can_be_edited() {
+        let mut world = World::new(7);
+        let queued = world.load_chunks_around(&Vector3::new(8.0, 8.0, 8.0), 1, usize::MAX).unwrap();
+        assert_eq!(queued, 27);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    assert!(world.is_chunk_loaded_at(Vector3::new(x, y, z) * CHUNK_SIZE as isize));
+                }
             }
         }
+
+        let position = Vector3::new(3, 5, 7);
+        let glass = world.registry().id("core:glass").unwrap();
+        world.place_at_global_pos(position, glass);
+        assert_eq!(world.block_at_global_pos(position), glass);
+        world.destroy_at_global_pos(position);
+        assert_eq!(world.block_at_global_pos(position), AIR);
+
+        // Edits to chunks which aren't loaded are dropped
+        let unloaded = Vector3::new(1000, 5, 7);
+        world.place_at_global_pos(unloaded, glass);
+        assert!(!world.is_chunk_loaded_at(unloaded));
+        assert_eq!(world.block_at_global_pos(unloaded), AIR);
+
+        // Every loaded chunk gets a mesh, the edited one included
+        let meshes = world.take_dirty_meshes(usize::MAX);
+        assert_eq!(meshes.len(), 27);
+        assert!(meshes.iter().any(|(chunk_index, _)| *chunk_index == Vector3::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn meshes_are_built_without_gl() {
+        let mut world = World::empty(1);
+        let stone = world.registry().id("core:stone").unwrap();
+        let origin = Vector3::new(0, 0, 0);
+        assert!(world.gen_chunk_mesh(&origin).is_empty());
+
+        // Six faces of two triangles each
+        world.place_at_global_pos(Vector3::new(5, 5, 5), stone);
+        let mesh = world.gen_chunk_mesh(&origin);
+        assert_eq!(mesh.opaque.len(), 36);
+        assert_eq!(mesh.len(), 36);
+
+        // The faces two blocks share are hidden, and greedy meshing merges the rest into six quads
+        world.place_at_global_pos(Vector3::new(6, 5, 5), stone);
+        let mut neighbourhood = world.neighbourhood(&origin).unwrap();
+        assert_eq!(neighbourhood.gen_mesh().len(), 36);
+        neighbourhood.meshing_mode = MeshingMode::Naive;
+        assert_eq!(neighbourhood.gen_mesh().len(), 60);
+
+        world.destroy_at_global_pos(Vector3::new(5, 5, 5));
+        world.destroy_at_global_pos(Vector3::new(6, 5, 5));
+        assert!(world.gen_chunk_mesh(&origin).is_empty());
+        // Chunks which aren't loaded have no mesh
+        assert!(world.neighbourhood(&Vector3::new(5, 0, 0)).is_none());
+        assert!(world.gen_chunk_mesh(&Vector3::new(5, 0, 0)).is_empty());
     }
 }
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, io::Read};
+
+/// A value in Minecraft's Named Binary Tag format, which Sponge schematics are stored in
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Lists and compounds nested deeper than this are rejected, so a malicious file can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+impl Tag {
+    /// Reads the root tag of an uncompressed NBT stream, along with its name
+    pub(crate) fn read_root<R: Read>(reader: &mut R) -> Result<(String, Tag), String> {
+        let kind = read_u8(reader)?;
+        if kind != 10 {
+            return Err(format!("NBT root has to be a compound, not tag type {}", kind));
+        }
+        let name = read_string(reader)?;
+        Ok((name, Tag::read_payload(reader, kind, 0)?))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(tags) => tags.get(name),
+            _ => None,
+        }
+    }
+
+    /// The tag's value if it's any of the integer types
+    pub(crate) fn as_int(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some(*value as i64),
+            Tag::Short(value) => Some(*value as i64),
+            Tag::Int(value) => Some(*value as i64),
+            Tag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn read_payload<R: Read>(reader: &mut R, kind: u8, depth: usize) -> Result<Tag, String> {
+        if depth > MAX_DEPTH {
+            return Err(String::from("NBT is nested too deeply"));
+        }
+        let tag = match kind {
+            1 => Tag::Byte(read_array::<_, 1>(reader)?[0] as i8),
+            2 => Tag::Short(i16::from_be_bytes(read_array(reader)?)),
+            3 => Tag::Int(i32::from_be_bytes(read_array(reader)?)),
+            4 => Tag::Long(i64::from_be_bytes(read_array(reader)?)),
+            5 => Tag::Float(f32::from_be_bytes(read_array(reader)?)),
+            6 => Tag::Double(f64::from_be_bytes(read_array(reader)?)),
+            7 => {
+                let length = read_length(reader)?;
+                let mut bytes = Vec::new();
+                reader.take(length as u64).read_to_end(&mut bytes).map_err(read_error)?;
+                if bytes.len() != length {
+                    return Err(String::from("NBT ends in the middle of a byte array"));
+                }
+                Tag::ByteArray(bytes)
+            }
+            8 => Tag::String(read_string(reader)?),
+            9 => {
+                let element_kind = read_u8(reader)?;
+                let length = read_length(reader)?;
+                let mut elements = Vec::new();
+                for _ in 0..length {
+                    elements.push(Tag::read_payload(reader, element_kind, depth + 1)?);
+                }
+                Tag::List(elements)
+            }
+            10 => {
+                let mut tags = HashMap::new();
+                loop {
+                    let kind = read_u8(reader)?;
+                    if kind == 0 {
+                        break;
+                    }
+                    let name = read_string(reader)?;
+                    tags.insert(name, Tag::read_payload(reader, kind, depth + 1)?);
+                }
+                Tag::Compound(tags)
+            }
+            11 => {
+                let length = read_length(reader)?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i32::from_be_bytes(read_array(reader)?));
+                }
+                Tag::IntArray(values)
+            }
+            12 => {
+                let length = read_length(reader)?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i64::from_be_bytes(read_array(reader)?));
+                }
+                Tag::LongArray(values)
+            }
+            _ => return Err(format!("Unknown NBT tag type {}", kind)),
+        };
+        Ok(tag)
+    }
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).map_err(read_error)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, String> {
+    Ok(read_array::<_, 1>(reader)?[0])
+}
+
+fn read_length<R: Read>(reader: &mut R) -> Result<usize, String> {
+    let length = i32::from_be_bytes(read_array(reader)?);
+    if length < 0 {
+        return Err(format!("Negative NBT length {}", length));
+    }
+    Ok(length as usize)
+}
+
+/// Strings are modified UTF-8, which only differs from UTF-8 for characters no block name uses
+fn read_string<R: Read>(reader: &mut R) -> Result<String, String> {
+    let length = u16::from_be_bytes(read_array(reader)?) as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes).map_err(read_error)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_error(error: std::io::Error) -> String {
+    format!("Couldn't read NBT: {}", error)
+}
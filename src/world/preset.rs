@@ -0,0 +1,173 @@
+use std::{fs, path::Path};
+
+use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+
+use super::{biome::BiomeDefinition, block::BlockRegistry};
+
+/// Preset new worlds are generated with unless they're given another one
+pub const DEFAULT_PRESET: &str = include_str!("../../assets/presets/default.json");
+
+/// Most octaves a noise layer can add up, past this they're finer than a block anyway
+pub const MAX_OCTAVES: u32 = 12;
+
+/// Octaves of noise, usually Perlin, added on top of each other, each `lacunarity` times the frequency
+/// and `persistence` times the amplitude of the one before
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FractalNoise {
+    /// Frequency of the first octave, in cycles per block
+    pub scale: f64,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl FractalNoise {
+    /// Fractal noise at `point`, scaled back to roughly -1 to 1 however many octaves there are
+    pub fn sample<S: NoiseFn<[f64; N]>, const N: usize>(&self, source: &S, point: [f64; N]) -> f64 {
+        self.sum_octaves(|frequency| source.get(point.map(|coord| coord * frequency)))
+    }
+
+    /// Ridged noise at `point`, from 0 to 1. Each octave folds the noise around 0 and flips it,
+    /// which turns the smooth hills of Perlin noise into sharp ridges.
+    pub fn sample_ridged<S: NoiseFn<[f64; N]>, const N: usize>(&self, source: &S, point: [f64; N]) -> f64 {
+        self.sum_octaves(|frequency| {
+            let ridge = 1.0 - source.get(point.map(|coord| coord * frequency)).abs();
+            ridge * ridge
+        })
+    }
+
+    fn sum_octaves<F: Fn(f64) -> f64>(&self, octave: F) -> f64 {
+        let mut total = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = self.scale;
+        let mut amplitude = 1.0;
+        for _ in 0..self.octaves {
+            total += amplitude * octave(frequency);
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        total / total_amplitude
+    }
+
+    fn validate(&self, name: &str) -> Result<(), String> {
+        if self.octaves == 0 || self.octaves > MAX_OCTAVES {
+            return Err(format!("Noise \"{}\" needs between 1 and {} octaves", name, MAX_OCTAVES));
+        }
+        if self.scale <= 0.0 || self.lacunarity <= 0.0 || self.persistence <= 0.0 {
+            return Err(format!("Noise \"{}\" needs a positive scale, lacunarity and persistence", name));
+        }
+        Ok(())
+    }
+}
+
+/// Everything terrain generation can be tuned with, so worlds can be designed in a data file
+/// instead of in code. The same seed and preset always generate the same world.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorPreset {
+    /// Height the surface rolls around where there are no mountains
+    pub base_height: f64,
+    /// Rolling hills across the whole world
+    pub hills: FractalNoise,
+    /// Furthest the hills reach above or below `base_height`
+    pub hill_height: f64,
+    /// Ridges the mountains are made of
+    pub mountains: FractalNoise,
+    /// Height of the highest ridges above the hills
+    pub mountain_height: f64,
+    /// Low frequency noise deciding where mountain ranges are
+    pub mountain_mask: FractalNoise,
+    /// Mask level the mountains start rising from, higher values give fewer ranges
+    pub mountain_threshold: f64,
+    /// Noise which pushes the points the hills and mountains are sampled at around, bending
+    /// their outlines so they don't all line up with the noise grid
+    pub warp: FractalNoise,
+    /// Furthest in blocks a point is pushed by `warp`
+    pub warp_strength: f64,
+    /// 3D noise which carves out caves wherever it's above `cave_cutoff`
+    pub caves: FractalNoise,
+    pub cave_cutoff: f64,
+    /// Fraction of the surface height below which the biome's subsurface gives way to stone
+    pub stone_depth: f64,
+    /// Climate noise, which picks the biome of each column
+    pub temperature: FractalNoise,
+    pub humidity: FractalNoise,
+    /// How much further from a column's climate than its own biome a neighbouring biome can be
+    /// and still pull on the column's height, which smooths out the steps at biome borders
+    pub biome_blend: f64,
+    pub biomes: Vec<BiomeDefinition>,
+}
+
+impl GeneratorPreset {
+    /// Parses and checks a preset, including that every block its biomes name is in `registry`
+    pub fn from_json(source: &str, registry: &BlockRegistry) -> Result<Self, String> {
+        let preset: GeneratorPreset = serde_json::from_str(source).map_err(|error| format!("Invalid generator preset: {}", error))?;
+        preset.hills.validate("hills")?;
+        preset.mountains.validate("mountains")?;
+        preset.mountain_mask.validate("mountain_mask")?;
+        preset.warp.validate("warp")?;
+        preset.caves.validate("caves")?;
+        preset.temperature.validate("temperature")?;
+        preset.humidity.validate("humidity")?;
+        if preset.biome_blend <= 0.0 {
+            return Err(String::from("Biome blend has to be positive"));
+        }
+        if preset.biomes.is_empty() {
+            return Err(String::from("Generator preset has no biomes"));
+        }
+        for (i, biome) in preset.biomes.iter().enumerate() {
+            biome.validate(registry)?;
+            if preset.biomes[..i].iter().any(|other| other.id == biome.id) {
+                return Err(format!("Biome \"{}\" is defined more than once", biome.id));
+            }
+        }
+        Ok(preset)
+    }
+
+    /// Reads a preset from a JSON file, so worlds can be designed without rebuilding
+    pub fn load<P: AsRef<Path>>(path: P, registry: &BlockRegistry) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| format!("Couldn't read generator preset {}: {}", path.display(), error))?;
+        GeneratorPreset::from_json(&source, registry)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Default for GeneratorPreset {
+    fn default() -> Self {
+        GeneratorPreset::from_json(DEFAULT_PRESET, &BlockRegistry::core()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        let registry = BlockRegistry::core();
+        assert!(GeneratorPreset::from_json(DEFAULT_PRESET, &registry).is_ok());
+        let unknown_surface = DEFAULT_PRESET.replacen("\"surface\": \"core:grass\"", "\"surface\": \"core:missing\"", 1);
+        assert!(GeneratorPreset::from_json(&unknown_surface, &registry).is_err());
+        let unknown_plant = DEFAULT_PRESET.replacen("\"core:fern\"", "\"core:missing\"", 1);
+        assert!(GeneratorPreset::from_json(&unknown_plant, &registry).is_err());
+    }
+
+    #[test]
+    fn loads_presets_from_files() {
+        let registry = BlockRegistry::core();
+        let path = std::env::temp_dir().join(format!("voxel_preset_{}.json", std::process::id()));
+        let mut preset = GeneratorPreset::default();
+        preset.base_height += 10.0;
+        fs::write(&path, preset.to_json()).unwrap();
+        assert_eq!(GeneratorPreset::load(&path, &registry), Ok(preset));
+        fs::remove_file(&path).unwrap();
+        assert!(GeneratorPreset::load(&path, &registry).is_err());
+    }
+}
@@ -0,0 +1,487 @@
+use std::{collections::{HashMap, HashSet}, fs, io::{self, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::Arc};
+
+use cgmath::Vector3;
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+
+use super::{Chunk, block::{AIR, BlockRegistry}, feature::Replace, storage::PaletteStorage};
+
+/// Width of a region along each axis, in chunks
+pub const REGION_SIZE: isize = 8;
+/// Bumped whenever the layout of the header or of a chunk's data changes
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"VXRG";
+const SLOTS: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+/// Magic, version and an (offset, length) pair for every chunk slot
+const HEADER_SIZE: usize = 8 + SLOTS * 8;
+
+/// Byte range of each chunk within its region file, a length of zero means the chunk isn't stored
+type RegionIndex = Box<[(u32, u32); SLOTS]>;
+
+/// Saves chunks to and loads them from region files in a directory.
+///
+/// Every file holds an 8x8x8 cube of chunks, starting with the format version and an index
+/// of where each chunk's data is in the file. Each chunk is deflated on its own so single
+/// chunks can be read without decompressing the rest of the region. Besides its blocks a chunk
+/// keeps the blocks it spilled into its neighbours, which are written again when it's loaded.
+/// Block ids are saved along with their namespaced identifiers, so saves keep working when
+/// the registry numbers blocks differently.
+///
+/// A region which fails to read is never written to again, so saving into it returns an error
+/// instead of replacing chunks which might still be recovered from the file.
+pub struct RegionStore {
+    directory: PathBuf,
+    registry: Arc<BlockRegistry>,
+    /// Region indices read so far, `None` for regions which have no file
+    indices: HashMap<Vector3<isize>, Option<RegionIndex>>,
+    /// Regions which couldn't be read and why
+    unreadable: HashMap<Vector3<isize>, String>,
+}
+
+impl RegionStore {
+    /// Opens the region files in `directory`, creating the directory if it doesn't exist
+    pub fn open<P: AsRef<Path>>(directory: P, registry: Arc<BlockRegistry>) -> io::Result<Self> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            registry,
+            indices: HashMap::new(),
+            unreadable: HashMap::new(),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Reads a single chunk, returns `None` if it has never been saved.
+    /// If it fails the chunk's region is marked unreadable, see `is_unreadable`.
+    pub fn load_chunk(&mut self, chunk_index: &Vector3<isize>) -> io::Result<Option<Chunk>> {
+        let (region, _) = RegionStore::region_and_slot(chunk_index);
+        let result = self.read_chunk(chunk_index);
+        if let Err(error) = &result {
+            self.unreadable.insert(region, error.to_string());
+        }
+        result
+    }
+
+    /// Whether the region holding `chunk_index` failed to read, in which case it isn't saved to anymore
+    pub fn is_unreadable(&self, chunk_index: &Vector3<isize>) -> bool {
+        let (region, _) = RegionStore::region_and_slot(chunk_index);
+        self.unreadable.contains_key(&region)
+    }
+
+    fn read_chunk(&mut self, chunk_index: &Vector3<isize>) -> io::Result<Option<Chunk>> {
+        let (region, slot) = RegionStore::region_and_slot(chunk_index);
+        let (offset, length) = match self.index(&region)? {
+            Some(index) => index[slot],
+            None => return Ok(None),
+        };
+        if length == 0 {
+            return Ok(None);
+        }
+
+        // Only this chunk's bytes are read, not the rest of the region
+        let mut file = fs::File::open(self.region_path(&region))?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut data = vec![0u8; length as usize];
+        file.read_exact(&mut data)?;
+        decode_chunk(&data, &self.registry).map(Some)
+    }
+
+    /// Writes `chunks` to their region files, keeping every other chunk already stored.
+    /// Each region is written to a temporary file first so a crash can't leave it half written.
+    /// Regions which can't be read or written don't stop the others from being saved, the first
+    /// error is returned once every region has been tried.
+    pub fn save_chunks<'a, I: IntoIterator<Item = (Vector3<isize>, &'a Chunk)>>(&mut self, chunks: I) -> io::Result<()> {
+        let mut by_region: HashMap<Vector3<isize>, Vec<(usize, Vec<u8>)>> = HashMap::new();
+        for (chunk_index, chunk) in chunks {
+            let (region, slot) = RegionStore::region_and_slot(&chunk_index);
+            by_region.entry(region).or_default().push((slot, encode_chunk(chunk, &self.registry)?));
+        }
+
+        let mut result = Ok(());
+        for (region, encoded) in by_region {
+            if let Err(error) = self.save_region(&region, encoded) {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+        result
+    }
+
+    /// Rewrites a region with the encoded chunks replacing the ones in their slots
+    fn save_region(&mut self, region: &Vector3<isize>, encoded: Vec<(usize, Vec<u8>)>) -> io::Result<()> {
+        let path = self.region_path(region);
+        if let Some(reason) = self.unreadable.get(region) {
+            return Err(invalid_data(&format!("refusing to overwrite {}, which couldn't be read: {}", path.display(), reason)));
+        }
+        let slots = match self.read_slots(region) {
+            Ok(slots) => slots,
+            Err(error) => {
+                self.unreadable.insert(*region, error.to_string());
+                return Err(error);
+            }
+        };
+        self.write_region(region, slots, encoded)
+    }
+
+    /// Data of every chunk stored in a region
+    fn read_slots(&mut self, region: &Vector3<isize>) -> io::Result<Vec<Option<Vec<u8>>>> {
+        let path = self.region_path(region);
+        let mut slots: Vec<Option<Vec<u8>>> = vec![None; SLOTS];
+        let index = match self.index(region)? {
+            Some(index) => index.clone(),
+            None => return Ok(slots),
+        };
+        let bytes = fs::read(&path)?;
+        for (slot, (offset, length)) in index.iter().enumerate() {
+            if *length > 0 {
+                let data = bytes.get(*offset as usize..*offset as usize + *length as usize)
+                    .ok_or_else(|| invalid_data("chunk data lies outside of the region file"))?;
+                slots[slot] = Some(data.to_vec());
+            }
+        }
+        Ok(slots)
+    }
+
+    fn write_region(&mut self, region: &Vector3<isize>, mut slots: Vec<Option<Vec<u8>>>, encoded: Vec<(usize, Vec<u8>)>) -> io::Result<()> {
+        let path = self.region_path(region);
+        for (slot, data) in encoded {
+            slots[slot] = Some(data);
+        }
+
+        let mut index: RegionIndex = Box::new([(0, 0); SLOTS]);
+        let mut body = Vec::new();
+        for (slot, data) in slots.iter().enumerate() {
+            if let Some(data) = data {
+                index[slot] = ((HEADER_SIZE + body.len()) as u32, data.len() as u32);
+                body.extend_from_slice(data);
+            }
+        }
+
+        let mut file = Vec::with_capacity(HEADER_SIZE + body.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for (offset, length) in index.iter() {
+            file.extend_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&length.to_le_bytes());
+        }
+        file.extend_from_slice(&body);
+
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, &file)?;
+        fs::rename(&temporary_path, &path)?;
+        self.indices.insert(*region, Some(index));
+        Ok(())
+    }
+
+    fn region_and_slot(chunk_index: &Vector3<isize>) -> (Vector3<isize>, usize) {
+        let region = Vector3::new(
+            chunk_index.x.div_euclid(REGION_SIZE),
+            chunk_index.y.div_euclid(REGION_SIZE),
+            chunk_index.z.div_euclid(REGION_SIZE),
+        );
+        let local = chunk_index - region * REGION_SIZE;
+        let slot = ((local.x * REGION_SIZE + local.y) * REGION_SIZE + local.z) as usize;
+        (region, slot)
+    }
+
+    fn region_path(&self, region: &Vector3<isize>) -> PathBuf {
+        self.directory.join(format!("r.{}.{}.{}.region", region.x, region.y, region.z))
+    }
+
+    /// Returns the index of a region, reading it the first time the region is used
+    fn index(&mut self, region: &Vector3<isize>) -> io::Result<Option<&RegionIndex>> {
+        if !self.indices.contains_key(region) {
+            let index = self.read_index(region)?;
+            self.indices.insert(*region, index);
+        }
+        Ok(self.indices[region].as_ref())
+    }
+
+    fn read_index(&self, region: &Vector3<isize>) -> io::Result<Option<RegionIndex>> {
+        let mut file = match fs::File::open(self.region_path(region)) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let mut header = vec![0u8; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data("not a region file"));
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != FORMAT_VERSION {
+            return Err(invalid_data(&format!("unsupported region format version {}", version)));
+        }
+
+        let mut index: RegionIndex = Box::new([(0, 0); SLOTS]);
+        for (slot, entry) in header[8..].chunks_exact(8).enumerate() {
+            index[slot] = (
+                u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+            );
+        }
+        Ok(Some(index))
+    }
+}
+
+fn encode_chunk(chunk: &Chunk, registry: &BlockRegistry) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+
+    // Identifiers of every block id used below
+    let mut ids: Vec<usize> = chunk.blocks.palette().to_vec();
+    for (_, block_id, replace) in &chunk.spilled {
+        ids.push(*block_id);
+        if let Replace::Only(replaced) = replace {
+            ids.push(*replaced);
+        }
+    }
+    let ids: HashSet<usize> = ids.into_iter().collect();
+    encoder.write_all(&(ids.len() as u32).to_le_bytes())?;
+    for id in ids {
+        let identifier = registry.get(id).map_or("", |block| block.identifier.as_str());
+        encoder.write_all(&(id as u32).to_le_bytes())?;
+        encoder.write_all(&(identifier.len() as u16).to_le_bytes())?;
+        encoder.write_all(identifier.as_bytes())?;
+    }
+
+    chunk.blocks.write_to(&mut encoder)?;
+    chunk.metadata.write_to(&mut encoder)?;
+    encoder.write_all(&chunk.spill_sources.to_le_bytes())?;
+    encoder.write_all(&(chunk.spilled.len() as u32).to_le_bytes())?;
+    for (world_pos, block_id, replace) in &chunk.spilled {
+        for coord in &[world_pos.x, world_pos.y, world_pos.z] {
+            encoder.write_all(&(*coord as i64).to_le_bytes())?;
+        }
+        encoder.write_all(&(*block_id as u32).to_le_bytes())?;
+        let (rule, replaced) = match replace {
+            Replace::NonSolid => (0u8, 0),
+            Replace::Only(replaced) => (1, *replaced),
+            Replace::Always => (2, 0),
+        };
+        encoder.write_all(&[rule])?;
+        encoder.write_all(&(replaced as u32).to_le_bytes())?;
+    }
+    encoder.finish()
+}
+
+fn decode_chunk(data: &[u8], registry: &BlockRegistry) -> io::Result<Chunk> {
+    let mut decoder = DeflateDecoder::new(data);
+    let mut word = [0u8; 4];
+
+    // Maps the ids the chunk was saved with to the ids of the current registry
+    let mut ids = HashMap::new();
+    decoder.read_exact(&mut word)?;
+    let id_count = u32::from_le_bytes(word);
+    for _ in 0..id_count {
+        decoder.read_exact(&mut word)?;
+        let saved_id = u32::from_le_bytes(word) as usize;
+        let mut length = [0u8; 2];
+        decoder.read_exact(&mut length)?;
+        let mut identifier = vec![0u8; u16::from_le_bytes(length) as usize];
+        decoder.read_exact(&mut identifier)?;
+        let identifier = String::from_utf8(identifier).map_err(|_| invalid_data("block identifier isn't valid UTF-8"))?;
+        let id = registry.id(&identifier).unwrap_or_else(|| {
+            println!("Unknown block \"{}\" in saved chunk, replacing it with air", identifier);
+            AIR
+        });
+        ids.insert(saved_id, id);
+    }
+    let map_id = |saved_id: usize| ids.get(&saved_id).copied().ok_or_else(|| invalid_data("block id without an identifier"));
+
+    let mut blocks = PaletteStorage::read_from(&mut decoder)?;
+    for saved_id in blocks.palette() {
+        map_id(*saved_id)?;
+    }
+    blocks.map_palette(|saved_id| ids[&saved_id]);
+    let metadata = PaletteStorage::read_from(&mut decoder)?;
+
+    decoder.read_exact(&mut word)?;
+    let spill_sources = u32::from_le_bytes(word);
+    decoder.read_exact(&mut word)?;
+    let spilled_len = u32::from_le_bytes(word);
+    let mut spilled = Vec::new();
+    for _ in 0..spilled_len {
+        let mut coords = [0isize; 3];
+        for coord in coords.iter_mut() {
+            let mut bytes = [0u8; 8];
+            decoder.read_exact(&mut bytes)?;
+            *coord = i64::from_le_bytes(bytes) as isize;
+        }
+        decoder.read_exact(&mut word)?;
+        let block_id = map_id(u32::from_le_bytes(word) as usize)?;
+        let mut rule = [0u8; 1];
+        decoder.read_exact(&mut rule)?;
+        decoder.read_exact(&mut word)?;
+        let replace = match rule[0] {
+            0 => Replace::NonSolid,
+            1 => Replace::Only(map_id(u32::from_le_bytes(word) as usize)?),
+            2 => Replace::Always,
+            _ => return Err(invalid_data("unknown replace rule")),
+        };
+        spilled.push((Vector3::new(coords[0], coords[1], coords[2]), block_id, replace));
+    }
+
+    Ok(Chunk { blocks, metadata, light: PaletteStorage::uniform(0), spilled, spill_sources })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CHUNK_SIZE, World};
+
+    /// Empty directory for a test to save into
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("voxel_region_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn test_chunk(registry: &BlockRegistry) -> Chunk {
+        let stone = registry.id("core:stone").unwrap();
+        let glass = registry.id("core:glass").unwrap();
+        let mut chunk = Chunk::uniform(stone);
+        chunk.set_block_at_chunk_pos(&Vector3::new(1, 2, 3), glass);
+        chunk.set_block_at_chunk_pos(&Vector3::new(15, 15, 15), AIR);
+        chunk.spilled.push((Vector3::new(-1, 4, 5), glass, Replace::Only(stone)));
+        chunk.spilled.push((Vector3::new(16, 0, 0), stone, Replace::NonSolid));
+        chunk.spill_sources = 0b101;
+        chunk
+    }
+
+    fn assert_same_chunk(loaded: &Chunk, expected: &Chunk) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let position = Vector3::new(x, y, z);
+                    assert_eq!(loaded.block_at_chunk_pos(&position), expected.block_at_chunk_pos(&position));
+                }
+            }
+        }
+        assert_eq!(loaded.spilled, expected.spilled);
+        assert_eq!(loaded.spill_sources, expected.spill_sources);
+    }
+
+    /// Region file claiming to be of format `version`, holding the raw `chunks` deflated
+    fn raw_region(version: u32, chunks: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let mut index = vec![(0u32, 0u32); SLOTS];
+        let mut body = Vec::new();
+        for (slot, data) in chunks {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            let data = encoder.finish().unwrap();
+            index[*slot] = ((HEADER_SIZE + body.len()) as u32, data.len() as u32);
+            body.extend_from_slice(&data);
+        }
+        let mut file = Vec::new();
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&version.to_le_bytes());
+        for (offset, length) in index {
+            file.extend_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&length.to_le_bytes());
+        }
+        file.extend_from_slice(&body);
+        file
+    }
+
+    #[test]
+    fn saved_chunks_load_back() {
+        let directory = test_directory("round_trip");
+        let registry = BlockRegistry::core();
+        let chunk = test_chunk(&registry);
+        let neighbour = Chunk::uniform(registry.id("core:dirt").unwrap());
+
+        let mut store = RegionStore::open(&directory, registry.clone()).unwrap();
+        store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk), (Vector3::new(-1, 0, 0), &neighbour)]).unwrap();
+        // Saving another chunk of the same region keeps the first one
+        store.save_chunks(vec![(Vector3::new(1, 0, 0), &neighbour)]).unwrap();
+
+        let mut store = RegionStore::open(&directory, registry).unwrap();
+        assert_same_chunk(&store.load_chunk(&Vector3::new(0, 0, 0)).unwrap().unwrap(), &chunk);
+        assert_same_chunk(&store.load_chunk(&Vector3::new(-1, 0, 0)).unwrap().unwrap(), &neighbour);
+        assert_same_chunk(&store.load_chunk(&Vector3::new(1, 0, 0)).unwrap().unwrap(), &neighbour);
+        assert!(store.load_chunk(&Vector3::new(2, 0, 0)).unwrap().is_none());
+        assert!(store.load_chunk(&Vector3::new(100, 0, 0)).unwrap().is_none());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_regions_are_not_overwritten() {
+        let directory = test_directory("corrupt");
+        let registry = BlockRegistry::core();
+        let chunk = test_chunk(&registry);
+        let mut store = RegionStore::open(&directory, registry.clone()).unwrap();
+        store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk)]).unwrap();
+
+        // A damaged header
+        let path = store.region_path(&Vector3::new(0, 0, 0));
+        fs::write(&path, b"garbage").unwrap();
+        let mut store = RegionStore::open(&directory, registry.clone()).unwrap();
+        assert!(store.load_chunk(&Vector3::new(1, 0, 0)).is_err());
+        assert!(store.is_unreadable(&Vector3::new(0, 0, 0)));
+        assert!(store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk)]).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"garbage");
+
+        // A readable header pointing at damaged chunk data
+        let mut file = raw_region(FORMAT_VERSION, &[(0, Vec::new())]);
+        file.truncate(file.len() - 1);
+        fs::write(&path, &file).unwrap();
+        let mut store = RegionStore::open(&directory, registry.clone()).unwrap();
+        assert!(store.load_chunk(&Vector3::new(0, 0, 0)).is_err());
+        assert!(store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk)]).is_err());
+        assert_eq!(fs::read(&path).unwrap(), file);
+
+        // Regions from a newer version than this one
+        let mut store = RegionStore::open(&directory, registry).unwrap();
+        let newer = raw_region(FORMAT_VERSION + 1, &[]);
+        fs::write(&path, &newer).unwrap();
+        assert!(store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk)]).is_err());
+        assert_eq!(fs::read(&path).unwrap(), newer);
+        // Other regions are still saved
+        assert!(store.save_chunks(vec![(Vector3::new(0, 0, 0), &chunk), (Vector3::new(8, 0, 0), &chunk)]).is_err());
+        assert_same_chunk(&store.load_chunk(&Vector3::new(8, 0, 0)).unwrap().unwrap(), &chunk);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn world_keeps_edits_it_cant_save() {
+        let directory = test_directory("world");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("r.0.0.0.region"), b"garbage").unwrap();
+        let mut world = World::new(3);
+        world.open_save_directory(&directory).unwrap();
+        let position = Vector3::new(8.0, 8.0, 8.0);
+        world.load_chunks_around(&position, 1, usize::MAX).unwrap();
+
+        // The chunk at the origin can't be read, so it's generated and its region never written
+        let edited = Vector3::new(2, 3, 4);
+        let glass = world.registry().id("core:glass").unwrap();
+        world.place_at_global_pos(edited, glass);
+        assert!(world.save().is_err());
+
+        let far_away = Vector3::new(1000.0, 8.0, 1000.0);
+        assert!(world.load_chunks_around(&far_away, 1, usize::MAX).is_err());
+        assert!(world.is_chunk_loaded_at(edited));
+        assert_eq!(world.block_at_global_pos(edited), glass);
+        // Not retried until the world moves again
+        assert!(world.load_chunks_around(&far_away, 1, usize::MAX).is_ok());
+
+        // Once the region can be written again the edit is saved and unloaded
+        fs::remove_file(directory.join("r.0.0.0.region")).unwrap();
+        world.regions.as_mut().unwrap().unreadable.clear();
+        world.load_chunks_around(&(far_away + Vector3::new(16.0, 0.0, 0.0)), 1, usize::MAX).unwrap();
+        assert!(!world.is_chunk_loaded_at(edited));
+        let mut store = RegionStore::open(&directory, world.registry.clone()).unwrap();
+        let (chunk_index, block_index) = World::chunk_and_block_index(&edited);
+        assert_eq!(store.load_chunk(&chunk_index).unwrap().unwrap().block_at_chunk_pos(&block_index), glass);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+use std::io::{self, Read, Write};
+
+use super::CHUNK_SIZE;
+
+const VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Values for every block of a chunk, stored as indices into a per-chunk palette.
+/// The indices are bit-packed using as few bits as the palette needs, so a chunk made of
+/// a single value (all air, all stone) stores no indices at all.
+#[derive(Clone, Debug)]
+pub struct PaletteStorage {
+    palette: Vec<usize>,
+    /// Width of a packed index, 0 while the storage is uniform
+    bits: u32,
+    /// Indices packed without straddling words, `64 / bits` to a word
+    words: Vec<u64>,
+}
+
+impl PaletteStorage {
+    /// Storage where every block holds `value`
+    pub fn uniform(value: usize) -> Self {
+        Self {
+            palette: vec![value],
+            bits: 0,
+            words: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> usize {
+        if self.bits == 0 {
+            return self.palette[0];
+        }
+        self.palette[self.index_at(Self::position(x, y, z))]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: usize) {
+        let palette_index = match self.palette.iter().position(|v| *v == value) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(value);
+                let needed = Self::bits_for(self.palette.len());
+                if needed > self.bits {
+                    self.repack(needed);
+                }
+                self.palette.len() - 1
+            }
+        };
+        if self.bits == 0 {
+            return;
+        }
+        self.set_index_at(Self::position(x, y, z), palette_index);
+    }
+
+    /// Whether every block holds the same value, in which case it's returned
+    pub fn uniform_value(&self) -> Option<usize> {
+        if self.bits == 0 {
+            Some(self.palette[0])
+        } else {
+            None
+        }
+    }
+
+    /// Every distinct value the storage may hold, unused ones included until `compact` is called
+    pub fn palette(&self) -> &[usize] {
+        &self.palette
+    }
+
+    /// Replaces every value with `map(value)` without touching the packed indices
+    pub fn map_palette<F: Fn(usize) -> usize>(&mut self, map: F) {
+        for value in self.palette.iter_mut() {
+            *value = map(*value);
+        }
+    }
+
+    /// Drops palette entries which are no longer used and shrinks the indices to match.
+    /// Writes only ever grow the palette, so this is worth calling after bulk edits.
+    pub fn compact(&mut self) {
+        if self.bits == 0 {
+            return;
+        }
+        let mut used = vec![false; self.palette.len()];
+        for position in 0..VOLUME {
+            used[self.index_at(position)] = true;
+        }
+        if used.iter().all(|used| *used) {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old_index, value) in self.palette.iter().enumerate() {
+            if used[old_index] {
+                remap[old_index] = palette.len();
+                palette.push(*value);
+            }
+        }
+
+        let indices: Vec<usize> = (0..VOLUME).map(|position| remap[self.index_at(position)]).collect();
+        self.palette = palette;
+        self.bits = 0;
+        self.words = Vec::new();
+        let bits = Self::bits_for(self.palette.len());
+        if bits > 0 {
+            self.bits = bits;
+            self.words = vec![0; Self::word_count(bits)];
+            for (position, index) in indices.into_iter().enumerate() {
+                self.set_index_at(position, index);
+            }
+        }
+    }
+
+    /// Writes the palette and packed indices as little-endian integers
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.palette.len() as u32).to_le_bytes())?;
+        for value in &self.palette {
+            writer.write_all(&(*value as u32).to_le_bytes())?;
+        }
+        writer.write_all(&[self.bits as u8])?;
+        for word in &self.words {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads storage written by `write_to`, rejecting data whose palette and indices don't agree
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let palette_len = read_u32(reader)? as usize;
+        if palette_len == 0 || palette_len > VOLUME {
+            return Err(invalid("palette size out of range"));
+        }
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            palette.push(read_u32(reader)? as usize);
+        }
+
+        let mut bits = [0u8];
+        reader.read_exact(&mut bits)?;
+        let bits = bits[0] as u32;
+        if bits != Self::bits_for(palette_len) {
+            return Err(invalid("index width doesn't match the palette"));
+        }
+
+        let mut words = Vec::new();
+        if bits > 0 {
+            let mut word = [0u8; 8];
+            for _ in 0..Self::word_count(bits) {
+                reader.read_exact(&mut word)?;
+                words.push(u64::from_le_bytes(word));
+            }
+        }
+
+        let storage = Self { palette, bits, words };
+        if bits > 0 && (0..VOLUME).any(|position| storage.index_at(position) >= storage.palette.len()) {
+            return Err(invalid("index outside of the palette"));
+        }
+        Ok(storage)
+    }
+
+    fn position(x: usize, y: usize, z: usize) -> usize {
+        (x * CHUNK_SIZE + y) * CHUNK_SIZE + z
+    }
+
+    fn bits_for(palette_len: usize) -> u32 {
+        if palette_len <= 1 {
+            0
+        } else {
+            usize::BITS - (palette_len - 1).leading_zeros()
+        }
+    }
+
+    fn word_count(bits: u32) -> usize {
+        let per_word = (64 / bits) as usize;
+        VOLUME.div_ceil(per_word)
+    }
+
+    fn index_at(&self, position: usize) -> usize {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (position % per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.words[position / per_word] >> shift) & mask) as usize
+    }
+
+    fn set_index_at(&mut self, position: usize, palette_index: usize) {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (position % per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        let word = &mut self.words[position / per_word];
+        *word = (*word & !(mask << shift)) | ((palette_index as u64 & mask) << shift);
+    }
+
+    /// Rewrites the indices with a width of `bits`
+    fn repack(&mut self, bits: u32) {
+        let indices: Vec<usize> = if self.bits == 0 {
+            vec![0; VOLUME]
+        } else {
+            (0..VOLUME).map(|position| self.index_at(position)).collect()
+        };
+        self.bits = bits;
+        self.words = vec![0; Self::word_count(bits)];
+        for (position, index) in indices.into_iter().enumerate() {
+            self.set_index_at(position, index);
+        }
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    /// Storage with a few hundred random writes, and the same values in a plain array
+    fn random_storage(seed: u64, values: usize) -> (PaletteStorage, Vec<usize>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut storage = PaletteStorage::uniform(0);
+        let mut expected = vec![0; VOLUME];
+        for _ in 0..500 {
+            let (x, y, z) = (rng.gen_range(0, CHUNK_SIZE), rng.gen_range(0, CHUNK_SIZE), rng.gen_range(0, CHUNK_SIZE));
+            let value = rng.gen_range(0, values);
+            storage.set(x, y, z, value);
+            expected[PaletteStorage::position(x, y, z)] = value;
+        }
+        (storage, expected)
+    }
+
+    fn assert_holds(storage: &PaletteStorage, expected: &[usize]) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    assert_eq!(storage.get(x, y, z), expected[PaletteStorage::position(x, y, z)], "at {} {} {}", x, y, z);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn writes_survive_growing_the_palette() {
+        let (storage, expected) = random_storage(1, 40);
+        assert_eq!(storage.bits, 6);
+        assert_holds(&storage, &expected);
+    }
+
+    #[test]
+    fn compact_drops_unused_values() {
+        let (mut storage, mut expected) = random_storage(2, 40);
+        // Leave only two of the values in use
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let value = if x < 8 { 7 } else { 1000 };
+                    storage.set(x, y, z, value);
+                    expected[PaletteStorage::position(x, y, z)] = value;
+                }
+            }
+        }
+        storage.compact();
+        assert_eq!(storage.palette(), &[7, 1000]);
+        assert_eq!(storage.bits, 1);
+        assert_holds(&storage, &expected);
+    }
+
+    #[test]
+    fn compact_makes_single_value_uniform() {
+        let mut storage = PaletteStorage::uniform(0);
+        storage.set(1, 2, 3, 7);
+        assert_eq!(storage.uniform_value(), None);
+        storage.set(1, 2, 3, 0);
+        storage.compact();
+        assert_eq!(storage.uniform_value(), Some(0));
+        assert!(storage.words.is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let (storage, expected) = random_storage(3, 5);
+        let mut bytes = Vec::new();
+        storage.write_to(&mut bytes).unwrap();
+        let read = PaletteStorage::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.palette(), storage.palette());
+        assert_holds(&read, &expected);
+
+        let mut uniform = Vec::new();
+        PaletteStorage::uniform(9).write_to(&mut uniform).unwrap();
+        assert_eq!(PaletteStorage::read_from(&mut uniform.as_slice()).unwrap().uniform_value(), Some(9));
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let (storage, _) = random_storage(4, 5);
+        let mut bytes = Vec::new();
+        storage.write_to(&mut bytes).unwrap();
+
+        assert!(PaletteStorage::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+
+        // Index width of the 5 entry palette
+        let bits_at = 4 + storage.palette().len() * 4;
+        let mut wrong_width = bytes.clone();
+        wrong_width[bits_at] = 4;
+        assert!(PaletteStorage::read_from(&mut wrong_width.as_slice()).is_err());
+
+        // Index 7 in every slot of the first word, past the end of the palette
+        let mut outside = bytes;
+        outside[bits_at + 1..bits_at + 9].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(PaletteStorage::read_from(&mut outside.as_slice()).is_err());
+    }
+}
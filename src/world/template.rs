@@ -0,0 +1,438 @@
+use std::{collections::HashMap, fs, io::Read, path::Path, sync::Arc};
+
+use cgmath::Vector3;
+use flate2::read::GzDecoder;
+use rand::{Rng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use super::{CHUNK_SIZE, block::{AIR, BlockRegistry}, feature::{Feature, Replace}, generator::SpilledBlocks, nbt::Tag, vox::ColourTable};
+
+/// Template ships with the game, a small well with a roof
+pub const WELL_TEMPLATE: &str = include_str!("../../assets/templates/well.json");
+
+/// Quarter turns about the vertical axis, clockwise seen from above
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+/// How a template is turned when it's placed. Mirroring flips it along the x axis before it's rotated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    pub mirror: bool,
+}
+
+impl Transform {
+    /// Any of the eight ways to turn a template, picked with equal chance
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let rotation = match rng.gen_range(0, 4) {
+            0 => Rotation::None,
+            1 => Rotation::Clockwise90,
+            2 => Rotation::Clockwise180,
+            _ => Rotation::Clockwise270,
+        };
+        Self { rotation, mirror: rng.gen() }
+    }
+
+    fn apply(&self, offset: Vector3<isize>) -> Vector3<isize> {
+        let x = if self.mirror { -offset.x } else { offset.x };
+        let (x, z) = match self.rotation {
+            Rotation::None => (x, offset.z),
+            Rotation::Clockwise90 => (-offset.z, x),
+            Rotation::Clockwise180 => (-x, -offset.z),
+            Rotation::Clockwise270 => (offset.z, -x),
+        };
+        Vector3::new(x, offset.y, z)
+    }
+}
+
+/// A template as it's written in a JSON file, with blocks given by their identifier
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateDefinition {
+    pub size: [usize; 3],
+    /// Cell which ends up at the position the template is placed at
+    pub origin: [isize; 3],
+    /// Block identifiers, `null` for cells which leave whatever was there before
+    pub palette: Vec<Option<String>>,
+    /// Index into `palette` of every cell, x first, then z, then y, like Sponge schematics
+    pub blocks: Vec<usize>,
+}
+
+/// A box of blocks designed outside the game, such as a house or a tree, which generation or
+/// the player can place into the world
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    size: Vector3<usize>,
+    origin: Vector3<isize>,
+    /// Block ids, `None` for cells which leave whatever was there before
+    palette: Vec<Option<usize>>,
+    blocks: Vec<usize>,
+}
+
+impl Template {
+    /// Template of `size` from the block of every cell, x first, then z, then y.
+    /// `None` cells leave whatever was there before.
+    pub fn from_cells(size: Vector3<usize>, origin: Vector3<isize>, cells: &[Option<usize>]) -> Self {
+        let mut palette = Vec::new();
+        let mut indices = HashMap::new();
+        let blocks = cells.iter()
+            .map(|cell| *indices.entry(*cell).or_insert_with(|| {
+                palette.push(*cell);
+                palette.len() - 1
+            }))
+            .collect();
+        Self { size, origin, palette, blocks }
+    }
+
+    pub fn from_definition(definition: &TemplateDefinition, registry: &BlockRegistry) -> Result<Self, String> {
+        let [width, height, length] = definition.size;
+        if width * height * length != definition.blocks.len() {
+            return Err(format!("Template is {}x{}x{} but has {} blocks", width, height, length, definition.blocks.len()));
+        }
+        if let Some(index) = definition.blocks.iter().find(|index| **index >= definition.palette.len()) {
+            return Err(format!("Template block {} isn't in its palette of {}", index, definition.palette.len()));
+        }
+        let palette = definition.palette.iter()
+            .map(|identifier| match identifier {
+                Some(identifier) => registry.id(identifier).map(Some).ok_or_else(|| format!("Template needs \"{}\", which isn't in the block registry", identifier)),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            size: Vector3::from(definition.size),
+            origin: Vector3::from(definition.origin),
+            palette,
+            blocks: definition.blocks.clone(),
+        })
+    }
+
+    pub fn to_definition(&self, registry: &BlockRegistry) -> TemplateDefinition {
+        TemplateDefinition {
+            size: self.size.into(),
+            origin: self.origin.into(),
+            palette: self.palette.iter()
+                .map(|block_id| block_id.map(|block_id| registry[block_id].identifier.clone()))
+                .collect(),
+            blocks: self.blocks.clone(),
+        }
+    }
+
+    pub fn from_json(source: &str, registry: &BlockRegistry) -> Result<Self, String> {
+        let definition: TemplateDefinition = serde_json::from_str(source).map_err(|error| format!("Invalid template: {}", error))?;
+        Template::from_definition(&definition, registry)
+    }
+
+    pub fn to_json(&self, registry: &BlockRegistry) -> String {
+        serde_json::to_string_pretty(&self.to_definition(registry)).unwrap()
+    }
+
+    /// Reads a gzipped Sponge schematic, version 1 to 3. Blocks are matched to the registry by
+    /// their identifier with any block states dropped, and `minecraft:` blocks fall back to the
+    /// `core:` block of the same name. Blocks which can't be matched leave the world as it is.
+    /// The origin is the position WorldEdit copied the schematic from if it's recorded,
+    /// otherwise the middle of the bottom layer.
+    pub fn from_schem(data: &[u8], registry: &BlockRegistry) -> Result<Self, String> {
+        let mut decoder = GzDecoder::new(data);
+        let (_, root) = Tag::read_root(&mut decoder)?;
+        // Version 3 wraps everything in another compound
+        let schematic = root.get("Schematic").unwrap_or(&root);
+
+        let dimension = |name: &str| schematic.get(name).and_then(Tag::as_int)
+            .map(|value| value as u16 as usize)
+            .ok_or_else(|| format!("Schematic has no {}", name));
+        let size = Vector3::new(dimension("Width")?, dimension("Height")?, dimension("Length")?);
+        let (palette_tag, data_tag) = match schematic.get("Blocks") {
+            Some(blocks) => (blocks.get("Palette"), blocks.get("Data")),
+            None => (schematic.get("Palette"), schematic.get("BlockData")),
+        };
+        let (names, data) = match (palette_tag, data_tag) {
+            (Some(Tag::Compound(names)), Some(Tag::ByteArray(data))) => (names, data),
+            _ => return Err(String::from("Schematic has no block palette and data")),
+        };
+
+        let mut palette = vec![None; names.len()];
+        let mut mapped: HashMap<&str, Option<usize>> = HashMap::new();
+        for (name, index) in names {
+            let index = index.as_int().filter(|index| (0..names.len() as i64).contains(index))
+                .ok_or_else(|| format!("Schematic palette entry \"{}\" has an invalid index", name))?;
+            let identifier = name.split('[').next().unwrap_or(name);
+            palette[index as usize] = *mapped.entry(identifier).or_insert_with(|| schem_block(identifier, registry));
+        }
+
+        // Palette indices are stored as varints, 7 bits to a byte with the high bit set on all but the last
+        let mut blocks = Vec::with_capacity(size.x * size.y * size.z);
+        let mut value = 0usize;
+        let mut shift = 0;
+        for byte in data {
+            value |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 != 0 {
+                shift += 7;
+                if shift > 28 {
+                    return Err(String::from("Schematic block data has an oversized varint"));
+                }
+                continue;
+            }
+            if value >= palette.len() {
+                return Err(format!("Schematic block {} isn't in its palette of {}", value, palette.len()));
+            }
+            blocks.push(value);
+            value = 0;
+            shift = 0;
+        }
+        if blocks.len() != size.x * size.y * size.z {
+            return Err(format!("Schematic is {}x{}x{} but has {} blocks", size.x, size.y, size.z, blocks.len()));
+        }
+
+        let offset = |name: &str| schematic.get("Metadata").and_then(|metadata| metadata.get(name)).and_then(Tag::as_int);
+        let origin = match (offset("WEOffsetX"), offset("WEOffsetY"), offset("WEOffsetZ")) {
+            (Some(x), Some(y), Some(z)) => Vector3::new(-x as isize, -y as isize, -z as isize),
+            _ => Vector3::new(size.x as isize / 2, 0, size.z as isize / 2),
+        };
+        Ok(Self { size, origin, palette, blocks })
+    }
+
+    /// Loads a `.schem` Sponge schematic, a `.vox` MagicaVoxel model coloured by the core colour table,
+    /// or a JSON template for any other extension
+    pub fn load<P: AsRef<Path>>(path: P, registry: &BlockRegistry) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut data = Vec::new();
+        fs::File::open(path).and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|error| format!("Couldn't read template {}: {}", path.display(), error))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("schem") => Template::from_schem(&data, registry),
+            Some("vox") => Template::from_vox(&data, &ColourTable::core(registry)),
+            _ => Template::from_json(&String::from_utf8_lossy(&data), registry),
+        }
+    }
+
+    pub fn size(&self) -> Vector3<usize> {
+        self.size
+    }
+
+    pub fn origin(&self) -> Vector3<isize> {
+        self.origin
+    }
+
+    /// Block at a cell of the untransformed template, `None` if it leaves the world as it is
+    pub fn block_at(&self, cell: &Vector3<usize>) -> Option<usize> {
+        self.palette[self.blocks[(cell.y * self.size.z + cell.z) * self.size.x + cell.x]]
+    }
+
+    /// Furthest any of the template's blocks is from its origin along an axis
+    pub fn reach(&self) -> usize {
+        let mut reach = 0;
+        for y in 0..self.size.y {
+            for z in 0..self.size.z {
+                for x in 0..self.size.x {
+                    let cell = Vector3::new(x, y, z);
+                    if self.block_at(&cell).is_some() {
+                        let offset = cell.cast::<isize>().unwrap() - self.origin;
+                        reach = reach.max(offset.x.unsigned_abs()).max(offset.y.unsigned_abs()).max(offset.z.unsigned_abs());
+                    }
+                }
+            }
+        }
+        reach
+    }
+
+    /// Adds the template's blocks with its origin at `world_pos`, turned by `transform`
+    pub fn place(&self, world_pos: Vector3<isize>, transform: Transform, replace: Replace, blocks: &mut SpilledBlocks) {
+        for y in 0..self.size.y {
+            for z in 0..self.size.z {
+                for x in 0..self.size.x {
+                    let cell = Vector3::new(x, y, z);
+                    if let Some(block_id) = self.block_at(&cell) {
+                        let offset = cell.cast::<isize>().unwrap() - self.origin;
+                        blocks.push((world_pos + transform.apply(offset), block_id, replace));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Block a Sponge schematic's block identifier stands for, without its block states
+fn schem_block(identifier: &str, registry: &BlockRegistry) -> Option<usize> {
+    if let Some(block_id) = registry.id(identifier) {
+        return Some(block_id);
+    }
+    let name = identifier.strip_prefix("minecraft:")?;
+    let name = match name {
+        "air" | "cave_air" | "void_air" => return Some(AIR),
+        "structure_void" => return None,
+        "grass_block" => "grass",
+        "coal_ore" => "coal",
+        // Called grass before it was renamed to short grass
+        "grass" | "tall_grass" => "short_grass",
+        "poppy" => "rose",
+        name => name,
+    };
+    let block_id = registry.id(&format!("core:{}", name));
+    if block_id.is_none() {
+        println!("Unknown block \"{}\" in schematic, leaving it out", identifier);
+    }
+    block_id
+}
+
+/// Places a template turned in a random direction, its origin on the spot the feature is placed at.
+/// Features only reach into the chunks around the one they're placed in, so the template's blocks
+/// can be at most a chunk from its origin.
+pub struct TemplateFeature {
+    template: Arc<Template>,
+    replace: Replace,
+}
+
+impl TemplateFeature {
+    /// Fails if the template reaches further than a chunk from its origin
+    pub fn new(template: Arc<Template>, replace: Replace) -> Result<Self, String> {
+        let reach = template.reach();
+        if reach > CHUNK_SIZE {
+            return Err(format!("Template reaches {} blocks from its origin, but features can only reach {}", reach, CHUNK_SIZE));
+        }
+        Ok(Self { template, replace })
+    }
+
+    pub fn template(&self) -> &Arc<Template> {
+        &self.template
+    }
+}
+
+impl Feature for TemplateFeature {
+    fn place(&self, origin: Vector3<isize>, rng: &mut StdRng, blocks: &mut SpilledBlocks) {
+        self.template.place(origin, Transform::random(rng), self.replace, blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    /// Named NBT tag of type `kind` with an already encoded payload
+    fn tag(kind: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn short(name: &str, value: i16) -> Vec<u8> {
+        tag(2, name, &value.to_be_bytes())
+    }
+
+    fn int(name: &str, value: i32) -> Vec<u8> {
+        tag(3, name, &value.to_be_bytes())
+    }
+
+    fn byte_array(name: &str, values: &[u8]) -> Vec<u8> {
+        let mut payload = (values.len() as i32).to_be_bytes().to_vec();
+        payload.extend_from_slice(values);
+        tag(7, name, &payload)
+    }
+
+    fn compound(name: &str, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut payload = children.concat();
+        payload.push(0);
+        tag(10, name, &payload)
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Palette of 128 differently stated stone blocks followed by `names`, so the indices of
+    /// `names` take two bytes each
+    fn palette(names: &[&str]) -> Vec<u8> {
+        let stone = (0..128).map(|index| int(&format!("minecraft:stone[variant={}]", index), index));
+        let rest = names.iter().enumerate().map(|(index, name)| int(name, 128 + index as i32));
+        compound("Palette", &stone.chain(rest).collect::<Vec<_>>())
+    }
+
+    /// A 2x1x2 version 2 schematic with the given block data
+    fn version_2(data: &[u8]) -> Vec<u8> {
+        let names = ["minecraft:grass_block[snowy=false]", "minecraft:air", "minecraft:structure_void", "minecraft:unknown_block"];
+        gzip(&compound("Schematic", &[
+            int("Version", 2),
+            short("Width", 2),
+            short("Height", 1),
+            short("Length", 2),
+            palette(&names),
+            byte_array("BlockData", data),
+            compound("Metadata", &[int("WEOffsetX", -1), int("WEOffsetY", 0), int("WEOffsetZ", -1)]),
+        ]))
+    }
+
+    #[test]
+    fn reads_version_2_schematics() {
+        let registry = BlockRegistry::core();
+        // Grass, air, structure void and stone, the first three as two byte varints
+        let template = Template::from_schem(&version_2(&[0x80, 0x01, 0x81, 0x01, 0x82, 0x01, 0x05]), &registry).unwrap();
+        assert_eq!(template.size(), Vector3::new(2, 1, 2));
+        assert_eq!(template.origin(), Vector3::new(1, 0, 1));
+        assert_eq!(template.block_at(&Vector3::new(0, 0, 0)), registry.id("core:grass"));
+        assert_eq!(template.block_at(&Vector3::new(1, 0, 0)), Some(AIR));
+        assert_eq!(template.block_at(&Vector3::new(0, 0, 1)), None);
+        assert_eq!(template.block_at(&Vector3::new(1, 0, 1)), registry.id("core:stone"));
+
+        // Blocks which aren't in the registry leave the world as it is
+        let template = Template::from_schem(&version_2(&[0x83, 0x01, 0x00, 0x00, 0x00]), &registry).unwrap();
+        assert_eq!(template.block_at(&Vector3::new(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn reads_version_3_schematics() {
+        let registry = BlockRegistry::core();
+        let data = gzip(&compound("", &[compound("Schematic", &[
+            int("Version", 3),
+            short("Width", 3),
+            short("Height", 1),
+            short("Length", 1),
+            compound("Blocks", &[
+                compound("Palette", &[int("core:glass", 0), int("minecraft:coal_ore", 1)]),
+                byte_array("Data", &[1, 0, 1]),
+            ]),
+        ])]));
+        let template = Template::from_schem(&data, &registry).unwrap();
+        // Without WorldEdit's offset the origin is the middle of the bottom layer
+        assert_eq!(template.origin(), Vector3::new(1, 0, 0));
+        assert_eq!(template.block_at(&Vector3::new(0, 0, 0)), registry.id("core:coal"));
+        assert_eq!(template.block_at(&Vector3::new(1, 0, 0)), registry.id("core:glass"));
+    }
+
+    #[test]
+    fn rejects_bad_block_data() {
+        let registry = BlockRegistry::core();
+        // Too few and too many blocks
+        assert!(Template::from_schem(&version_2(&[0x05, 0x05, 0x05]), &registry).is_err());
+        assert!(Template::from_schem(&version_2(&[0x05; 5]), &registry).is_err());
+        // A varint which never ends, and one longer than 32 bits
+        assert!(Template::from_schem(&version_2(&[0x05, 0x05, 0x05, 0x80]), &registry).is_err());
+        assert!(Template::from_schem(&version_2(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x05, 0x05, 0x05]), &registry).is_err());
+        // Index 132 is past the end of the palette
+        assert!(Template::from_schem(&version_2(&[0x84, 0x01, 0x05, 0x05, 0x05]), &registry).is_err());
+        assert!(Template::from_schem(b"not a schematic", &registry).is_err());
+    }
+
+    #[test]
+    fn features_reach_at_most_a_chunk() {
+        let registry = BlockRegistry::core();
+        let stone = registry.id("core:stone");
+        let pillar = |height: usize, origin: isize| Template::from_cells(Vector3::new(1, height, 1), Vector3::new(0, origin, 0), &vec![stone; height]);
+        assert_eq!(pillar(17, 0).reach(), 16);
+        assert!(TemplateFeature::new(Arc::new(pillar(17, 0)), Replace::Always).is_ok());
+        assert!(TemplateFeature::new(Arc::new(pillar(18, 0)), Replace::Always).is_err());
+        assert!(TemplateFeature::new(Arc::new(pillar(18, 8)), Replace::Always).is_ok());
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use cgmath::Vector3;
+use rand::Rng;
+
+use super::{CHUNK_SIZE, FACE_NEIGHBOURS, World, block::{AIR, BlockRegistry, BlockType}, light::LightChannel};
+
+/// Blocks picked at random in every loaded chunk each tick
+pub const RANDOM_TICKS_PER_CHUNK: usize = 3;
+/// Metadata of leaves which never decay, set on leaves placed by the player
+pub const PERSISTENT_LEAVES: usize = 1;
+
+/// Furthest leaves can be from a log, counted in steps through other leaves, before they decay
+const LEAF_SUPPORT_DISTANCE: usize = 4;
+/// Ticks leaves wait before checking for a log, plus up to as many again so a tree doesn't vanish all at once
+const LEAF_DECAY_DELAY: u64 = 20;
+/// Light grass needs above a dirt block to spread onto it
+const GRASS_SPREAD_LIGHT: u8 = 9;
+const ABOVE: usize = 2;
+const BELOW: usize = 3;
+
+/// Runs for the block at the given position, on a random or scheduled tick
+pub type TickHandler = fn(&mut World, Vector3<isize>);
+/// Runs for the block at the first position after the block at the second position changed.
+/// A block which was just placed gets notified about itself.
+pub type NeighbourHandler = fn(&mut World, Vector3<isize>, Vector3<isize>);
+
+/// What each kind of block does when it's ticked, by numeric id
+#[derive(Default)]
+pub(crate) struct TickHandlers {
+    random: HashMap<usize, TickHandler>,
+    scheduled: HashMap<usize, TickHandler>,
+    neighbour_changed: HashMap<usize, NeighbourHandler>,
+}
+
+impl TickHandlers {
+    /// Handlers for the behaviours which ship with the game: grass spreading, plants needing
+    /// soil, leaf decay and flowing fluids
+    pub(crate) fn core(registry: &BlockRegistry) -> Self {
+        let mut handlers = Self::default();
+        if let Some(grass) = registry.id("core:grass") {
+            handlers.random.insert(grass, spread_grass);
+        }
+        for block in registry.iter() {
+            match block.block_type {
+                BlockType::Grass => {
+                    handlers.neighbour_changed.insert(block.id, check_soil);
+                }
+                BlockType::Leaves => {
+                    handlers.neighbour_changed.insert(block.id, schedule_leaf_decay);
+                    handlers.scheduled.insert(block.id, decay_leaves);
+                }
+                BlockType::Fluid => {
+                    handlers.neighbour_changed.insert(block.id, |world, position, _| world.schedule_fluid(position));
+                    handlers.scheduled.insert(block.id, World::update_fluid);
+                }
+                BlockType::Block => {}
+            }
+        }
+        handlers
+    }
+
+    fn ticks_randomly(&self, block_id: usize) -> bool {
+        self.random.contains_key(&block_id)
+    }
+}
+
+/// Blocks waiting for a scheduled tick, by the tick they're due on. A block is only ever
+/// scheduled once, later requests for the same position are dropped until it has run.
+#[derive(Default)]
+pub(crate) struct ScheduledTicks {
+    due: BTreeMap<u64, Vec<Vector3<isize>>>,
+    scheduled: HashSet<Vector3<isize>>,
+}
+
+impl ScheduledTicks {
+    fn schedule(&mut self, position: Vector3<isize>, tick: u64) {
+        if self.scheduled.insert(position) {
+            self.due.entry(tick).or_default().push(position);
+        }
+    }
+
+    fn take_due(&mut self, tick: u64) -> Vec<Vector3<isize>> {
+        let later = self.due.split_off(&(tick + 1));
+        let due: Vec<Vector3<isize>> = std::mem::replace(&mut self.due, later).into_values().flatten().collect();
+        for position in &due {
+            self.scheduled.remove(position);
+        }
+        due
+    }
+}
+
+impl World {
+    /// Advances the simulation by one tick: scheduled ticks which are due run first,
+    /// then a few random blocks in every loaded chunk are ticked
+    pub fn tick(&mut self) {
+        self.current_tick += 1;
+        // Whatever the simulation changes isn't part of an edit, even with a transaction open
+        self.history.set_suspended(true);
+
+        for position in self.scheduled_ticks.take_due(self.current_tick) {
+            let block_id = self.block_at_global_pos(position);
+            if let Some(handler) = self.tick_handlers.scheduled.get(&block_id).copied() {
+                handler(self, position);
+            }
+        }
+
+        let handlers = &self.tick_handlers;
+        let ticking: Vec<Vector3<isize>> = self.chunks.iter()
+            .filter(|(_, chunk)| chunk.may_contain(|block_id| handlers.ticks_randomly(block_id)))
+            .map(|(chunk_index, _)| *chunk_index)
+            .collect();
+        for chunk_index in ticking {
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let block_index = Vector3::new(
+                    self.tick_rng.gen_range(0, CHUNK_SIZE as isize),
+                    self.tick_rng.gen_range(0, CHUNK_SIZE as isize),
+                    self.tick_rng.gen_range(0, CHUNK_SIZE as isize),
+                );
+                let position = chunk_index * CHUNK_SIZE as isize + block_index;
+                let block_id = self.block_at_global_pos(position);
+                if let Some(handler) = self.tick_handlers.random.get(&block_id).copied() {
+                    handler(self, position);
+                }
+            }
+        }
+        self.history.set_suspended(false);
+    }
+
+    /// Runs the scheduled tick handler of whatever block is at `world_pos` in `delay` ticks
+    pub fn schedule_tick(&mut self, world_pos: Vector3<isize>, delay: u64) {
+        self.scheduled_ticks.schedule(world_pos, self.current_tick + delay.max(1));
+    }
+
+    /// Makes `handler` run for blocks of `block_id` picked by random ticks
+    pub fn set_random_tick_handler(&mut self, block_id: usize, handler: TickHandler) {
+        self.tick_handlers.random.insert(block_id, handler);
+    }
+
+    /// Makes `handler` run for blocks of `block_id` when their scheduled tick is due
+    pub fn set_scheduled_tick_handler(&mut self, block_id: usize, handler: TickHandler) {
+        self.tick_handlers.scheduled.insert(block_id, handler);
+    }
+
+    /// Makes `handler` run for blocks of `block_id` when a block next to them changes
+    pub fn set_neighbour_handler(&mut self, block_id: usize, handler: NeighbourHandler) {
+        self.tick_handlers.neighbour_changed.insert(block_id, handler);
+    }
+
+    /// Tells the block at `world_pos` and the six blocks touching it that it changed,
+    /// whichever chunk they're in
+    pub(super) fn notify_neighbours(&mut self, world_pos: &Vector3<isize>) {
+        let positions = std::iter::once(*world_pos).chain(FACE_NEIGHBOURS.iter().map(|offset| world_pos + offset));
+        for position in positions {
+            self.notify(position, *world_pos);
+        }
+    }
+
+    /// Like `notify_neighbours` for a batch of changed blocks, but a block touching several of
+    /// them is only notified about the first
+    pub(super) fn notify_neighbours_of_all(&mut self, changed: &[Vector3<isize>]) {
+        let changed_set: HashSet<Vector3<isize>> = changed.iter().copied().collect();
+        let mut notified = HashSet::new();
+        for world_pos in changed {
+            self.notify(*world_pos, *world_pos);
+            for offset in &FACE_NEIGHBOURS {
+                let neighbour = world_pos + offset;
+                if !changed_set.contains(&neighbour) && notified.insert(neighbour) {
+                    self.notify(neighbour, *world_pos);
+                }
+            }
+        }
+    }
+
+    fn notify(&mut self, position: Vector3<isize>, changed: Vector3<isize>) {
+        if !self.is_chunk_loaded_at(position) {
+            return;
+        }
+        let block_id = self.block_at_global_pos(position);
+        if let Some(handler) = self.tick_handlers.neighbour_changed.get(&block_id).copied() {
+            handler(self, position, changed);
+        }
+    }
+}
+
+/// Turns grass covered by an opaque block back into dirt, otherwise spreads it to a nearby
+/// dirt block which is lit and uncovered
+fn spread_grass(world: &mut World, world_pos: Vector3<isize>) {
+    let (grass, dirt) = match (world.registry.id("core:grass"), world.registry.id("core:dirt")) {
+        (Some(grass), Some(dirt)) => (grass, dirt),
+        _ => return,
+    };
+    let uncovered = |world: &World, position: Vector3<isize>| world.registry[world.block_at_global_pos(position + FACE_NEIGHBOURS[ABOVE])].transparent;
+
+    if !uncovered(world, world_pos) {
+        world.set_block_at_global_pos(world_pos, dirt, 0);
+        return;
+    }
+    let target = world_pos + Vector3::new(
+        world.tick_rng.gen_range(-1, 2),
+        world.tick_rng.gen_range(-3, 2),
+        world.tick_rng.gen_range(-1, 2),
+    );
+    let above = target + FACE_NEIGHBOURS[ABOVE];
+    let light = world.light_at_global_pos(above, LightChannel::Sky).max(world.light_at_global_pos(above, LightChannel::Block));
+    if world.block_at_global_pos(target) == dirt && uncovered(world, target) && light >= GRASS_SPREAD_LIGHT {
+        world.set_block_at_global_pos(target, grass, 0);
+    }
+}
+
+/// Removes plants which no longer stand on soil they can grow in
+fn check_soil(world: &mut World, world_pos: Vector3<isize>, _changed: Vector3<isize>) {
+    let below = world_pos + FACE_NEIGHBOURS[BELOW];
+    if !world.is_chunk_loaded_at(below) {
+        return;
+    }
+    let soil = ["core:grass", "core:dirt", "core:snowy_grass", "core:sand"].map(|identifier| world.registry.id(identifier));
+    if !soil.contains(&Some(world.block_at_global_pos(below))) {
+        world.set_block_at_global_pos(world_pos, AIR, 0);
+    }
+}
+
+fn schedule_leaf_decay(world: &mut World, world_pos: Vector3<isize>, changed: Vector3<isize>) {
+    if changed != world_pos && world.metadata_at_global_pos(world_pos) != PERSISTENT_LEAVES {
+        let delay = LEAF_DECAY_DELAY + world.tick_rng.gen_range(0, LEAF_DECAY_DELAY);
+        world.schedule_tick(world_pos, delay);
+    }
+}
+
+/// Removes leaves which can't reach a log through at most `LEAF_SUPPORT_DISTANCE` other leaves.
+/// Leaves next to unloaded chunks are kept, since the log may be in there.
+fn decay_leaves(world: &mut World, world_pos: Vector3<isize>) {
+    if world.metadata_at_global_pos(world_pos) == PERSISTENT_LEAVES {
+        return;
+    }
+    let log = world.registry.id("core:oak_log");
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(world_pos);
+    queue.push_back((world_pos, 0));
+    while let Some((position, distance)) = queue.pop_front() {
+        for offset in &FACE_NEIGHBOURS {
+            let neighbour = position + offset;
+            if !world.is_chunk_loaded_at(neighbour) {
+                return;
+            }
+            let block_id = world.block_at_global_pos(neighbour);
+            if Some(block_id) == log {
+                return;
+            }
+            if distance + 1 < LEAF_SUPPORT_DISTANCE && world.registry[block_id].block_type == BlockType::Leaves && visited.insert(neighbour) {
+                queue.push_back((neighbour, distance + 1));
+            }
+        }
+    }
+    world.set_block_at_global_pos(world_pos, AIR, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Chunk;
+
+    #[test]
+    fn grass_spreads_to_lit_dirt_and_decays_under_cover() {
+        let mut world = World::empty(1);
+        let grass = world.registry.id("core:grass").unwrap();
+        let dirt = world.registry.id("core:dirt").unwrap();
+        let stone = world.registry.id("core:stone").unwrap();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                world.place_at_global_pos(Vector3::new(x, 0, z), dirt);
+            }
+        }
+        world.place_at_global_pos(Vector3::new(0, 0, 0), grass);
+        world.place_at_global_pos(Vector3::new(1, 1, 1), stone);
+
+        for _ in 0..500 {
+            spread_grass(&mut world, Vector3::new(0, 0, 0));
+        }
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let expected = if (x, z) == (1, 1) { dirt } else { grass };
+                assert_eq!(world.block_at_global_pos(Vector3::new(x, 0, z)), expected);
+            }
+        }
+
+        world.place_at_global_pos(Vector3::new(0, 1, 0), stone);
+        spread_grass(&mut world, Vector3::new(0, 0, 0));
+        assert_eq!(world.block_at_global_pos(Vector3::new(0, 0, 0)), dirt);
+
+        // Random ticks land on covered grass in a layer of it often enough to turn some of it
+        let mut world = World::new(0);
+        let mut chunk = Chunk::uniform(AIR);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block_at_chunk_pos(&Vector3::new(x, 0, z), grass);
+                chunk.set_block_at_chunk_pos(&Vector3::new(x, 1, z), stone);
+            }
+        }
+        world.insert_chunk(Vector3::new(0, 0, 0), chunk);
+        for _ in 0..100 {
+            world.tick();
+        }
+        let mut layer = Vec::new();
+        for x in 0..CHUNK_SIZE as isize {
+            for z in 0..CHUNK_SIZE as isize {
+                layer.push(world.block_at_global_pos(Vector3::new(x, 0, z)));
+            }
+        }
+        assert!(layer.iter().all(|block_id| *block_id == grass || *block_id == dirt));
+        assert!(layer.contains(&dirt));
+    }
+
+    #[test]
+    fn plants_pop_off_without_soil() {
+        let mut world = World::empty(1);
+        let id = |identifier| world.registry.id(identifier).unwrap();
+        let (dirt, stone, sand, rose, dead_bush) = (id("core:dirt"), id("core:stone"), id("core:sand"), id("core:rose"), id("core:dead_bush"));
+
+        world.place_at_global_pos(Vector3::new(0, 0, 0), dirt);
+        world.place_at_global_pos(Vector3::new(0, 1, 0), rose);
+        assert_eq!(world.block_at_global_pos(Vector3::new(0, 1, 0)), rose);
+        world.destroy_at_global_pos(Vector3::new(0, 0, 0));
+        assert_eq!(world.block_at_global_pos(Vector3::new(0, 1, 0)), AIR);
+
+        // Plants placed somewhere they can't grow pop off straight away
+        world.place_at_global_pos(Vector3::new(3, 0, 0), stone);
+        world.place_at_global_pos(Vector3::new(3, 1, 0), rose);
+        assert_eq!(world.block_at_global_pos(Vector3::new(3, 1, 0)), AIR);
+
+        world.place_at_global_pos(Vector3::new(6, 0, 0), sand);
+        world.place_at_global_pos(Vector3::new(6, 1, 0), dead_bush);
+        world.place_at_global_pos(Vector3::new(7, 1, 0), stone);
+        assert_eq!(world.block_at_global_pos(Vector3::new(6, 1, 0)), dead_bush);
+    }
+
+    #[test]
+    fn scheduled_ticks_fire_on_their_tick() {
+        let mut world = World::empty(0);
+        let glass = world.registry.id("core:glass").unwrap();
+        let stone = world.registry.id("core:stone").unwrap();
+        // Swaps glass and stone, so every time the handler runs shows
+        fn swap(world: &mut World, world_pos: Vector3<isize>) {
+            let (glass, stone) = (world.registry.id("core:glass").unwrap(), world.registry.id("core:stone").unwrap());
+            let swapped = if world.block_at_global_pos(world_pos) == glass { stone } else { glass };
+            world.set_block_at_global_pos(world_pos, swapped, 0);
+        }
+        world.set_scheduled_tick_handler(glass, swap);
+        world.set_scheduled_tick_handler(stone, swap);
+
+        let position = Vector3::new(1, 2, 3);
+        world.place_at_global_pos(position, glass);
+        world.schedule_tick(position, 3);
+        // Already scheduled, so this is dropped
+        world.schedule_tick(position, 5);
+        for _ in 0..2 {
+            world.tick();
+            assert_eq!(world.block_at_global_pos(position), glass);
+        }
+        world.tick();
+        assert_eq!(world.block_at_global_pos(position), stone);
+        for _ in 0..5 {
+            world.tick();
+            assert_eq!(world.block_at_global_pos(position), stone);
+        }
+
+        // A delay of zero still waits for the next tick
+        world.schedule_tick(position, 0);
+        assert_eq!(world.block_at_global_pos(position), stone);
+        world.tick();
+        assert_eq!(world.block_at_global_pos(position), glass);
+    }
+}
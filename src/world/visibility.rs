@@ -0,0 +1,81 @@
+use cgmath::Vector3;
+
+use super::{CHUNK_SIZE, Chunk, FACE_NEIGHBOURS, block::BlockRegistry};
+
+/// Which faces of a chunk can see each other through the chunk, found by flood filling the blocks
+/// which aren't opaque. Faces are numbered in the same order as `FACE_NEIGHBOURS`.
+/// The renderer walks from the camera's chunk through these connections to skip chunks which
+/// are hidden behind solid rock, even when they're inside the view frustum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    /// Bit `b` of entry `a` is set if face `a` can be seen from face `b`
+    connections: [u8; 6],
+}
+
+impl ChunkVisibility {
+    /// Every face sees every other face, like an empty chunk
+    pub const OPEN: ChunkVisibility = ChunkVisibility { connections: [0b111111; 6] };
+    /// No face sees any other face, like a chunk of solid stone
+    pub const CLOSED: ChunkVisibility = ChunkVisibility { connections: [0; 6] };
+
+    pub fn new(chunk: &Chunk, registry: &BlockRegistry) -> Self {
+        if !chunk.may_contain(|block_id| !registry[block_id].transparent) {
+            return ChunkVisibility::OPEN;
+        }
+        if !chunk.may_contain(|block_id| registry[block_id].transparent) {
+            return ChunkVisibility::CLOSED;
+        }
+
+        let index = |position: &Vector3<usize>| (position.x * CHUNK_SIZE + position.y) * CHUNK_SIZE + position.z;
+        let mut visited = vec![false; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+        let mut visibility = ChunkVisibility::CLOSED;
+        let mut stack = Vec::new();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let start = Vector3::new(x, y, z);
+                    if visited[index(&start)] || !registry[chunk.block_at_chunk_pos(&start)].transparent {
+                        continue;
+                    }
+                    // Faces the pocket of see-through blocks around `start` reaches
+                    let mut faces = 0u8;
+                    visited[index(&start)] = true;
+                    stack.push(start);
+                    while let Some(position) = stack.pop() {
+                        for (face, offset) in FACE_NEIGHBOURS.iter().enumerate() {
+                            let next = position.cast::<isize>().unwrap() + offset;
+                            if next.x < 0 || next.y < 0 || next.z < 0 || next.x >= CHUNK_SIZE as isize || next.y >= CHUNK_SIZE as isize || next.z >= CHUNK_SIZE as isize {
+                                faces |= 1 << face;
+                                continue;
+                            }
+                            let next = next.cast::<usize>().unwrap();
+                            if !visited[index(&next)] && registry[chunk.block_at_chunk_pos(&next)].transparent {
+                                visited[index(&next)] = true;
+                                stack.push(next);
+                            }
+                        }
+                    }
+                    for face in 0..6 {
+                        if faces & (1 << face) != 0 {
+                            visibility.connections[face] |= faces;
+                        }
+                    }
+                    if visibility == ChunkVisibility::OPEN {
+                        return visibility;
+                    }
+                }
+            }
+        }
+        visibility
+    }
+
+    /// Whether something entering the chunk through face `from` can be seen leaving through face `to`
+    pub fn connects(&self, from: usize, to: usize) -> bool {
+        self.connections[from] & (1 << to) != 0
+    }
+}
+
+/// Face on the other side of the chunk from `face`, which is the face a neighbour is entered through
+pub fn opposite_face(face: usize) -> usize {
+    face ^ 1
+}
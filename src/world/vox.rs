@@ -0,0 +1,395 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
+
+use super::{World, block::{AIR, BlockRegistry}, edit::Bounds, template::{Template, Transform}};
+
+/// Colours the core blocks are given in MagicaVoxel models
+pub const CORE_VOX_COLOURS: &str = include_str!("../../assets/vox_colours.json");
+
+/// Longest a model can be along each axis, since MagicaVoxel stores coordinates in a byte
+pub const MAX_VOX_SIZE: usize = 256;
+
+/// MagicaVoxel file format version written by `to_vox`, which every version of MagicaVoxel reads
+const VOX_VERSION: u32 = 150;
+
+/// A block and the colour it stands for, as written in a colour table's JSON file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColourDefinition {
+    pub block: String,
+    pub colour: [u8; 3],
+}
+
+/// Which block each colour of a MagicaVoxel palette turns into, and which colour a block is
+/// exported as. Colours which aren't in the table turn into the block with the closest colour,
+/// so models painted with slightly different shades still import.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColourTable {
+    entries: Vec<([u8; 3], usize)>,
+}
+
+impl ColourTable {
+    /// The colours of the core blocks, leaving out any which aren't in `registry`
+    pub fn core(registry: &BlockRegistry) -> Self {
+        let definitions = ColourTable::parse(CORE_VOX_COLOURS).unwrap_or_default();
+        let mut table = ColourTable::default();
+        for definition in definitions {
+            if let Some(block_id) = registry.id(&definition.block) {
+                table.insert(definition.colour, block_id);
+            }
+        }
+        table
+    }
+
+    pub fn from_json(source: &str, registry: &BlockRegistry) -> Result<Self, String> {
+        let mut table = ColourTable::default();
+        for definition in ColourTable::parse(source)? {
+            let block_id = registry.id(&definition.block)
+                .ok_or_else(|| format!("Colour table needs \"{}\", which isn't in the block registry", definition.block))?;
+            table.insert(definition.colour, block_id);
+        }
+        Ok(table)
+    }
+
+    fn parse(source: &str) -> Result<Vec<ColourDefinition>, String> {
+        serde_json::from_str(source).map_err(|error| format!("Invalid colour table: {}", error))
+    }
+
+    /// Makes `colour` turn into `block_id`, replacing whatever block it stood for before
+    pub fn insert(&mut self, colour: [u8; 3], block_id: usize) {
+        match self.entries.iter_mut().find(|(existing, _)| *existing == colour) {
+            Some(entry) => entry.1 = block_id,
+            None => self.entries.push((colour, block_id)),
+        }
+    }
+
+    /// Block with the colour closest to `colour`, `None` if the table is empty
+    pub fn block_for(&self, colour: [u8; 3]) -> Option<usize> {
+        let distance = |other: &[u8; 3]| (0..3).map(|channel| (colour[channel] as i32 - other[channel] as i32).pow(2)).sum::<i32>();
+        self.entries.iter().min_by_key(|(other, _)| distance(other)).map(|(_, block_id)| *block_id)
+    }
+
+    /// Colour a block is exported as, the first one it was given if there are several
+    pub fn colour_for(&self, block_id: usize) -> Option<[u8; 3]> {
+        self.entries.iter().find(|(_, other)| *other == block_id).map(|(colour, _)| *colour)
+    }
+}
+
+/// MagicaVoxel models are z up, so their y axis becomes the world's z axis, flipped to keep it right handed
+impl Template {
+    /// Reads a MagicaVoxel `.vox` file, turning each voxel into the block its colour stands for in `table`.
+    /// Empty voxels leave the world as it is. Only the first model of files with several is read,
+    /// and its origin is the middle of its bottom layer.
+    pub fn from_vox(data: &[u8], table: &ColourTable) -> Result<Self, String> {
+        if data.len() < 8 || &data[0..4] != b"VOX " {
+            return Err(String::from("Not a MagicaVoxel file"));
+        }
+        let (id, _, children, _) = read_chunk(&data[8..])?;
+        if id != *b"MAIN" {
+            return Err(String::from("MagicaVoxel file doesn't start with a MAIN chunk"));
+        }
+
+        let mut size = None;
+        let mut voxels = None;
+        let mut models = 0;
+        let mut palette = default_palette();
+        let mut rest = children;
+        while !rest.is_empty() {
+            let (id, content, _, next) = read_chunk(rest)?;
+            rest = next;
+            match &id {
+                b"SIZE" if size.is_none() => {
+                    let dimension = |axis: usize| read_u32(&content[axis * 4..]).map(|value| value as usize);
+                    if content.len() < 12 {
+                        return Err(String::from("MagicaVoxel SIZE chunk is too short"));
+                    }
+                    size = Some(Vector3::new(dimension(0)?, dimension(1)?, dimension(2)?));
+                }
+                b"XYZI" => {
+                    models += 1;
+                    if voxels.is_none() {
+                        let count = read_u32(content)? as usize;
+                        // The count comes straight from the file, so it's checked before it's trusted
+                        let end = count.checked_mul(4).and_then(|bytes| bytes.checked_add(4))
+                            .filter(|end| *end <= content.len())
+                            .ok_or_else(|| format!("MagicaVoxel model has {} voxels but only room for {}", count, (content.len() - 4) / 4))?;
+                        voxels = Some(&content[4..end]);
+                    }
+                }
+                // Entry i is the colour of index i + 1, index 0 means empty
+                b"RGBA" => {
+                    for (index, colour) in content.chunks_exact(4).take(255).enumerate() {
+                        palette[index + 1] = [colour[0], colour[1], colour[2]];
+                    }
+                }
+                _ => {}
+            }
+        }
+        let (size, voxels) = match (size, voxels) {
+            (Some(size), Some(voxels)) => (size, voxels),
+            _ => return Err(String::from("MagicaVoxel file has no model")),
+        };
+        if size.x > MAX_VOX_SIZE || size.y > MAX_VOX_SIZE || size.z > MAX_VOX_SIZE {
+            return Err(format!("MagicaVoxel model is {}x{}x{}, which is bigger than {} along an axis", size.x, size.y, size.z, MAX_VOX_SIZE));
+        }
+        if models > 1 {
+            println!("MagicaVoxel file has {} models, only reading the first", models);
+        }
+        if table.entries.is_empty() {
+            return Err(String::from("Colour table is empty"));
+        }
+
+        let world_size = Vector3::new(size.x, size.z, size.y);
+        let mut cells = vec![None; world_size.x * world_size.y * world_size.z];
+        let mut blocks: HashMap<u8, Option<usize>> = HashMap::new();
+        for voxel in voxels.chunks_exact(4) {
+            let (x, y, z, index) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize, voxel[3]);
+            if x >= size.x || y >= size.y || z >= size.z || index == 0 {
+                continue;
+            }
+            let cell = Vector3::new(x, z, size.y - 1 - y);
+            cells[(cell.y * world_size.z + cell.z) * world_size.x + cell.x] = *blocks.entry(index)
+                .or_insert_with(|| table.block_for(palette[index as usize]));
+        }
+        let origin = Vector3::new(world_size.x as isize / 2, 0, world_size.z as isize / 2);
+        Ok(Template::from_cells(world_size, origin, &cells))
+    }
+
+    /// Writes the template as a MagicaVoxel `.vox` file, coloured by `table`. Air and cells which
+    /// leave the world as it is are empty, and blocks which have no colour in `table` are left out.
+    pub fn to_vox(&self, table: &ColourTable) -> Result<Vec<u8>, String> {
+        let size = self.size();
+        if size.x > MAX_VOX_SIZE || size.y > MAX_VOX_SIZE || size.z > MAX_VOX_SIZE {
+            return Err(format!("Template is {}x{}x{}, MagicaVoxel models can be at most {} along each axis", size.x, size.y, size.z, MAX_VOX_SIZE));
+        }
+
+        // Palette indices are given out in the order colours are first used, starting at 1
+        let mut colours: Vec<[u8; 3]> = Vec::new();
+        let mut indices: HashMap<usize, Option<u8>> = HashMap::new();
+        let mut voxels = Vec::new();
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let block_id = match self.block_at(&Vector3::new(x, y, z)) {
+                        Some(AIR) | None => continue,
+                        Some(block_id) => block_id,
+                    };
+                    let index = match indices.get(&block_id) {
+                        Some(index) => *index,
+                        None => {
+                            let index = match table.colour_for(block_id) {
+                                Some(colour) => match colours.iter().position(|existing| *existing == colour) {
+                                    Some(position) => Some(position as u8 + 1),
+                                    None if colours.len() < 255 => {
+                                        colours.push(colour);
+                                        Some(colours.len() as u8)
+                                    }
+                                    None => return Err(String::from("Template needs more than the 255 colours a MagicaVoxel palette holds")),
+                                },
+                                None => {
+                                    println!("Block {} has no colour in the colour table, leaving it out", block_id);
+                                    None
+                                }
+                            };
+                            indices.insert(block_id, index);
+                            index
+                        }
+                    };
+                    if let Some(index) = index {
+                        voxels.extend_from_slice(&[x as u8, (size.z - 1 - z) as u8, y as u8, index]);
+                    }
+                }
+            }
+        }
+
+        let mut size_content = Vec::new();
+        for dimension in &[size.x, size.z, size.y] {
+            size_content.extend_from_slice(&(*dimension as u32).to_le_bytes());
+        }
+        let mut voxel_content = ((voxels.len() / 4) as u32).to_le_bytes().to_vec();
+        voxel_content.extend_from_slice(&voxels);
+        let mut palette_content = Vec::with_capacity(256 * 4);
+        for index in 0..256 {
+            let [r, g, b] = colours.get(index).copied().unwrap_or([0; 3]);
+            palette_content.extend_from_slice(&[r, g, b, 255]);
+        }
+
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size_content, &[]);
+        write_chunk(&mut children, b"XYZI", &voxel_content, &[]);
+        write_chunk(&mut children, b"RGBA", &palette_content, &[]);
+        let mut data = b"VOX ".to_vec();
+        data.extend_from_slice(&VOX_VERSION.to_le_bytes());
+        write_chunk(&mut data, b"MAIN", &[], &children);
+        Ok(data)
+    }
+}
+
+impl World {
+    /// Stamps a MagicaVoxel model into the world with the middle of its bottom layer at `world_pos`,
+    /// returning the bounds of the blocks which changed like `paste` does
+    pub fn import_vox<P: AsRef<Path>>(&mut self, path: P, world_pos: Vector3<isize>, table: &ColourTable) -> Result<Option<Bounds>, String> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|error| format!("Couldn't read MagicaVoxel file {}: {}", path.display(), error))?;
+        let template = Template::from_vox(&data, table)?;
+        Ok(self.paste(&template, world_pos, Transform::default()))
+    }
+
+    /// Saves the blocks in `bounds` as a MagicaVoxel model, leaving out blocks in chunks which aren't loaded
+    pub fn export_vox<P: AsRef<Path>>(&self, bounds: Bounds, path: P, table: &ColourTable) -> Result<(), String> {
+        let path = path.as_ref();
+        let data = self.copy(bounds, bounds.min).to_vox(table)?;
+        fs::write(path, data).map_err(|error| format!("Couldn't write MagicaVoxel file {}: {}", path.display(), error))
+    }
+}
+
+/// Id, content and children of a chunk, followed by whatever comes after it in the file
+type VoxChunk<'a> = ([u8; 4], &'a [u8], &'a [u8], &'a [u8]);
+
+/// Splits the chunk at the start of `data` into its parts
+fn read_chunk(data: &[u8]) -> Result<VoxChunk<'_>, String> {
+    if data.len() < 12 {
+        return Err(String::from("MagicaVoxel file ends in the middle of a chunk header"));
+    }
+    let id = [data[0], data[1], data[2], data[3]];
+    let content_length = read_u32(&data[4..])? as usize;
+    let children_length = read_u32(&data[8..])? as usize;
+    let end = 12usize.checked_add(content_length).and_then(|end| end.checked_add(children_length))
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| format!("MagicaVoxel {} chunk is longer than the file", String::from_utf8_lossy(&id)))?;
+    Ok((id, &data[12..12 + content_length], &data[12 + content_length..end], &data[end..]))
+}
+
+fn write_chunk(data: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    data.extend_from_slice(id);
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    data.extend_from_slice(content);
+    data.extend_from_slice(children);
+}
+
+fn read_u32(data: &[u8]) -> Result<u32, String> {
+    match data {
+        [a, b, c, d, ..] => Ok(u32::from_le_bytes([*a, *b, *c, *d])),
+        _ => Err(String::from("MagicaVoxel file ends in the middle of a number")),
+    }
+}
+
+/// Palette MagicaVoxel uses for files without an RGBA chunk, indexed by colour index.
+/// It's a 6x6x6 colour cube without black, followed by ramps of red, green, blue and grey.
+fn default_palette() -> [[u8; 3]; 256] {
+    const CUBE_STEPS: [u8; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+    const RAMP_STEPS: [u8; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut palette = [[0; 3]; 256];
+    let mut index = 1;
+    for r in &CUBE_STEPS {
+        for g in &CUBE_STEPS {
+            for b in &CUBE_STEPS {
+                if index < 216 {
+                    palette[index] = [*r, *g, *b];
+                    index += 1;
+                }
+            }
+        }
+    }
+    for channel in 0..4 {
+        for step in &RAMP_STEPS {
+            palette[index] = match channel {
+                0 => [*step, 0, 0],
+                1 => [0, *step, 0],
+                2 => [0, 0, *step],
+                _ => [*step; 3],
+            };
+            index += 1;
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A model made of a MAIN chunk with `children`
+    fn vox_file(children: &[u8]) -> Vec<u8> {
+        let mut data = b"VOX ".to_vec();
+        data.extend_from_slice(&VOX_VERSION.to_le_bytes());
+        write_chunk(&mut data, b"MAIN", &[], children);
+        data
+    }
+
+    #[test]
+    fn templates_round_trip() {
+        let registry = BlockRegistry::core();
+        let table = ColourTable::core(&registry);
+        let [stone, glass, sand] = ["core:stone", "core:glass", "core:sand"].map(|identifier| registry.id(identifier));
+        // Lopsided along every axis, so a flipped axis shows up
+        let size = Vector3::new(4, 3, 2);
+        let mut cells = vec![None; 24];
+        for (x, block_id) in [stone, stone, Some(AIR), stone].iter().enumerate() {
+            cells[x] = *block_id;
+        }
+        cells[(size.z + 1) * size.x + 3] = sand;
+        cells[2 * size.z * size.x] = glass;
+        let template = Template::from_cells(size, Vector3::new(2, 0, 1), &cells);
+
+        let read = Template::from_vox(&template.to_vox(&table).unwrap(), &table).unwrap();
+        assert_eq!(read.size(), size);
+        assert_eq!(read.origin(), template.origin());
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let cell = Vector3::new(x, y, z);
+                    // Air is written as an empty voxel
+                    let expected = template.block_at(&cell).filter(|block_id| *block_id != AIR);
+                    assert_eq!(read.block_at(&cell), expected, "at {:?}", cell);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn colours_match_the_closest_block() {
+        let registry = BlockRegistry::core();
+        let mut table = ColourTable::default();
+        let stone = registry.id("core:stone").unwrap();
+        let sand = registry.id("core:sand").unwrap();
+        table.insert([128, 128, 128], stone);
+        table.insert([220, 210, 160], sand);
+        assert_eq!(table.block_for([120, 125, 130]), Some(stone));
+        assert_eq!(table.block_for([255, 255, 200]), Some(sand));
+        assert_eq!(table.colour_for(sand), Some([220, 210, 160]));
+        assert!(ColourTable::from_json(r#"[{ "block": "core:missing", "colour": [1, 2, 3] }]"#, &registry).is_err());
+    }
+
+    #[test]
+    fn handles_bad_models() {
+        let table = ColourTable::core(&BlockRegistry::core());
+        let mut size = Vec::new();
+        for dimension in &[2u32, 2, 2] {
+            size.extend_from_slice(&dimension.to_le_bytes());
+        }
+
+        // A voxel count far bigger than the chunk, which mustn't overflow working out its length
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size, &[]);
+        write_chunk(&mut children, b"XYZI", &u32::MAX.to_le_bytes(), &[]);
+        assert!(Template::from_vox(&vox_file(&children), &table).is_err());
+
+        // Voxels outside of the model are skipped
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size, &[]);
+        write_chunk(&mut children, b"XYZI", &[2, 0, 0, 0, 5, 0, 0, 1, 1, 1, 0, 1], &[]);
+        let template = Template::from_vox(&vox_file(&children), &table).unwrap();
+        assert_eq!(template.size(), Vector3::new(2, 2, 2));
+        assert!(template.block_at(&Vector3::new(1, 0, 0)).is_some());
+        assert_eq!((0..8).filter(|cell| template.block_at(&Vector3::new(cell & 1, (cell >> 1) & 1, cell >> 2)).is_some()).count(), 1);
+
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size, &[]);
+        assert!(Template::from_vox(&vox_file(&children), &table).is_err());
+        assert!(Template::from_vox(b"VOX \x96\0\0\0MAIN\xff\xff\xff\x7f", &table).is_err());
+    }
+}